@@ -1,8 +1,8 @@
 use pixi::{
-    app::{ControlFlow, EventLoop, PhysicalSize, Window, WindowEvent},
+    app::{ControlFlow, EventLoop, StartContext, Window, WindowEvent},
+    assets::{Assets, BackgroundLoader, Loaded},
     batch::Batch,
     blend,
-    image::{Image, ImageLoader},
     layout::Layout,
     sprite::Animation,
     target::Target,
@@ -10,74 +10,67 @@ use pixi::{
 };
 use std::time::Instant;
 
-fn main() {
+fn main() -> Result<(), pixi::Error> {
     let event_loop = EventLoop::new();
     let window = Window::new(&event_loop).unwrap();
     window.set_title("Animations Example");
 
-    pixi::app::run::<Animations>(event_loop, window, Default::default());
+    pixi::app::try_run::<Animations>(event_loop, window, Default::default())
 }
 
+const PATHS: [&str; 12] = [
+    "examples/assets/rabbit/rabbit.png",
+    "examples/assets/rabbit/rabbit_ash.png",
+    "examples/assets/rabbit/rabbit_batman.png",
+    "examples/assets/rabbit/rabbit_bb8.png",
+    "examples/assets/rabbit/rabbit_neo.png",
+    "examples/assets/rabbit/rabbit_sonic.png",
+    "examples/assets/rabbit/rabbit_spidey.png",
+    "examples/assets/rabbit/rabbit_stormtrooper.png",
+    "examples/assets/rabbit/rabbit_superman.png",
+    "examples/assets/rabbit/rabbit_tron.png",
+    "examples/assets/rabbit/rabbit_wolverine.png",
+    "examples/assets/rabbit/rabbit_frankenstein.png",
+];
+
 struct Animations {
-    images: Vec<Image>,
-    batches: Vec<Batch>,
+    assets: Assets,
+    loader: BackgroundLoader,
+    /// Batch and size of each frame, once it's loaded.
+    frames: Vec<Option<(Batch, f32, f32)>>,
     anim: Animation<()>,
     ticker: Instant,
     layout: Layout,
+    sampler: wgpu::Sampler,
+    format: wgpu::TextureFormat,
 }
 
 impl pixi::app::Game for Animations {
     type UserEvent = ();
 
-    fn start(
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        format: wgpu::TextureFormat,
-        _size: PhysicalSize<u32>,
-        _scale_factor: f64,
-    ) -> Self {
-        let path = [
-            "examples/assets/rabbit/rabbit.png",
-            "examples/assets/rabbit/rabbit_ash.png",
-            "examples/assets/rabbit/rabbit_batman.png",
-            "examples/assets/rabbit/rabbit_bb8.png",
-            "examples/assets/rabbit/rabbit_neo.png",
-            "examples/assets/rabbit/rabbit_sonic.png",
-            "examples/assets/rabbit/rabbit_spidey.png",
-            "examples/assets/rabbit/rabbit_stormtrooper.png",
-            "examples/assets/rabbit/rabbit_superman.png",
-            "examples/assets/rabbit/rabbit_tron.png",
-            "examples/assets/rabbit/rabbit_wolverine.png",
-            "examples/assets/rabbit/rabbit_frankenstein.png",
-        ];
-
-        let mut loader = ImageLoader::new(device);
-        let images: Vec<Image> = path
-            .iter()
-            .map(|path| loader.srgb_premul(device, path).unwrap())
-            .collect();
-        queue.submit(&[loader.finish()]);
-
-        let sampler = pixi::nearest_sampler(device);
-        let layout = Layout::new(device);
-        let batches: Vec<Batch> = images
-            .iter()
-            .map(|image| {
-                let image = layout.bind_image(device, &image, &sampler);
-                Batch::new(device, &layout, format, blend::PMA_NORMAL, image)
-            })
-            .collect();
-
-        let mut anim = Animation::new(vec![(); batches.len()], Vec::new());
+    fn start(context: StartContext<Self::UserEvent>) -> Self {
+        let (device, format, proxy) = (context.device, context.format, context.proxy);
+        // frames are decoded on worker threads, each wakes up the event loop
+        let mut loader = BackgroundLoader::new(4, move || {
+            let _ = proxy.send_event(());
+        });
+        for path in PATHS.iter() {
+            loader.load_image(*path);
+        }
+
+        let mut anim = Animation::new(vec![(); PATHS.len()], Vec::new());
         anim.play();
         anim.set_speed(5.0);
 
         Self {
-            images,
-            batches,
+            assets: Assets::new(),
+            loader,
+            frames: PATHS.iter().map(|_| None).collect(),
             anim,
             ticker: Instant::now(),
-            layout,
+            layout: Layout::new(device),
+            sampler: pixi::nearest_sampler(device),
+            format,
         }
     }
 
@@ -85,6 +78,31 @@ impl pixi::app::Game for Animations {
         pixi::app::exit_helper(&event, control_flow);
     }
 
+    fn user_event(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        _event: (),
+        _control_flow: &mut ControlFlow,
+    ) {
+        for (path, result) in self.loader.poll(device, &mut self.assets) {
+            let index = PATHS.iter().position(|p| path.as_os_str() == *p).unwrap();
+            let image = match result {
+                Ok(Loaded::Image(image)) => image,
+                Ok(Loaded::Sheet(_)) => unreachable!(),
+                Err(err) => panic!("{}: {}", path.display(), err),
+            };
+
+            let (layout, format) = (&self.layout, self.format);
+            let image = &self.assets[&image];
+            let (w, h) = (image.width as f32, image.height as f32);
+            let bind_group = layout.bind_image(device, image, &self.sampler);
+            let batch = Batch::new(device, layout, format, blend::PMA_NORMAL, bind_group);
+            self.frames[index] = Some((batch, w, h));
+        }
+        self.assets.submit(queue);
+    }
+
     fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, target: Target) {
         let dt = {
             let now = Instant::now();
@@ -93,9 +111,6 @@ impl pixi::app::Game for Animations {
             dt.as_secs_f32()
         };
 
-        let _ = self.anim.update(dt);
-        let frame = self.anim.current_frame();
-
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("default encoder"),
         });
@@ -105,11 +120,20 @@ impl pixi::app::Game for Animations {
             ..target
         };
 
-        pixi::clear_color(&mut encoder, &target.view, [0.3, 0.3, 0.4, 1.0]);
+        // fades in while loading
+        let progress = self.loader.progress() as f64;
+        let color = [0.3 * progress, 0.3 * progress, 0.4 * progress, 1.0];
+        pixi::clear_color(&mut encoder, &target.view, color);
 
-        let batch = &mut self.batches[frame];
-        let w = self.images[frame].width as f32;
-        let h = self.images[frame].height as f32;
+        if !self.loader.is_done() {
+            queue.submit(&[encoder.finish()]);
+            return;
+        }
+
+        let _ = self.anim.update(dt);
+        let frame = self.anim.current_frame();
+        let (batch, w, h) = self.frames[frame].as_mut().unwrap();
+        let (w, h) = (*w, *h);
 
         let (x, y) = (10.25, 10.25);
 
@@ -117,8 +141,8 @@ impl pixi::app::Game for Animations {
         let max = [x + w, y + h];
 
         batch.add_sprite(min, max);
-        batch.flush(&mut encoder, &device, &self.layout, &target);
+        batch.flush(&mut encoder, &device, &target);
 
-        queue.submit(&[encoder.finish()]);
+        self.layout.submit(queue, encoder);
     }
 }
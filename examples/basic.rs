@@ -1,38 +1,35 @@
 use pixi::{
-    app::{ControlFlow, EventLoop, PhysicalSize, Window, WindowEvent},
+    app::{ControlFlow, EventLoop, StartContext, Window, WindowEvent},
     batch::Batch,
     blend,
     image::{Image, ImageLoader},
     layout::Layout,
+    renderer::{Draw, Renderer},
     target::Target,
     wgpu,
 };
 
-fn main() {
+fn main() -> Result<(), pixi::Error> {
     let event_loop = EventLoop::new();
     let window = Window::new(&event_loop).unwrap();
     window.set_title("Basic Example");
 
-    pixi::app::run::<Basic>(event_loop, window, Default::default());
+    pixi::app::try_run::<Basic>(event_loop, window, Default::default())
 }
 
 struct Basic {
     batch: Batch,
     bunny: Image,
     x: [Batch; 3],
+    renderer: Renderer,
     layout: Layout,
 }
 
 impl pixi::app::Game for Basic {
     type UserEvent = ();
 
-    fn start(
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        format: wgpu::TextureFormat,
-        _size: PhysicalSize<u32>,
-        _scale_factor: f64,
-    ) -> Self {
+    fn start(context: StartContext<Self::UserEvent>) -> Self {
+        let (device, queue, format) = (context.device, context.queue, context.format);
         let path = [
             "examples/assets/bunny.png",
             "examples/assets/blending/x-red.png",
@@ -62,10 +59,13 @@ impl pixi::app::Game for Basic {
         let xb = Batch::new(device, &layout, format, blend::PMA_NORMAL, xb_bind_group);
         let x = [xr, xg, xb];
 
+        let renderer = Renderer::new(device, &layout);
+
         Self {
             batch,
             bunny,
             x,
+            renderer,
             layout,
         }
     }
@@ -93,8 +93,7 @@ impl pixi::app::Game for Basic {
         let max = [x + w, y + h];
 
         self.batch.add_sprite(min, max);
-        self.batch
-            .flush(&mut encoder, &device, &self.layout, &target);
+        self.batch.prepare(&mut encoder, device);
 
         for (i, batch) in self.x.iter_mut().enumerate() {
             let (w, h) = (12.0, 12.0);
@@ -103,9 +102,17 @@ impl pixi::app::Game for Basic {
             let min = [x, y];
             let max = [x + w, y + h];
             batch.add_sprite(min, max);
-            batch.flush(&mut encoder, &device, &self.layout, &target);
+            batch.prepare(&mut encoder, device);
+        }
+
+        // all batches are drawn in one render pass
+        let mut pass = self.renderer.begin(target);
+        pass.submit(&self.batch);
+        for batch in &self.x {
+            pass.submit(batch);
         }
+        pass.finish(&mut encoder, device);
 
-        queue.submit(&[encoder.finish()]);
+        self.layout.submit(queue, encoder);
     }
 }
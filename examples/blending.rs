@@ -1,15 +1,15 @@
 use pixi::{
-    app::{ControlFlow, EventLoop, LogicalSize, PhysicalSize, WindowBuilder, WindowEvent},
+    app::{ControlFlow, EventLoop, LogicalSize, StartContext, WindowBuilder, WindowEvent},
     blend::{self, Blend},
     linear_sampler,
     image::{ImageBindGroup, ImageLoader},
-    layout::{Layout, Shader},
+    layout::Layout,
     sprite::SpritePipeline,
     target::{RenderTarget, Target},
     wgpu,
 };
 
-fn main() {
+fn main() -> Result<(), pixi::Error> {
     let event_loop = EventLoop::new();
 
     let [width, height] = [500, 300];
@@ -19,7 +19,7 @@ fn main() {
         .build(&event_loop)
         .unwrap();
 
-    pixi::app::run::<Blending>(event_loop, window, Default::default());
+    pixi::app::try_run::<Blending>(event_loop, window, Default::default())
 }
 
 pub struct Quad {
@@ -70,13 +70,8 @@ struct BlendPipeline {
 }
 
 impl BlendPipeline {
-    fn new(
-        device: &wgpu::Device,
-        layout: &Layout,
-        shader: &Shader,
-        format: wgpu::TextureFormat,
-    ) -> Self {
-        let create = |blend: Blend| SpritePipeline::new(device, &layout, &shader, format, blend);
+    fn new(device: &wgpu::Device, layout: &Layout, format: wgpu::TextureFormat) -> Self {
+        let create = |blend: Blend| SpritePipeline::new(device, &layout, format, blend);
 
         Self {
             src: create(blend::PMA_SRC),
@@ -118,13 +113,8 @@ struct Blending {
 impl pixi::app::Game for Blending {
     type UserEvent = ();
 
-    fn start(
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        format: wgpu::TextureFormat,
-        _size: PhysicalSize<u32>,
-        _scale_factor: f64,
-    ) -> Self {
+    fn start(context: StartContext<Self::UserEvent>) -> Self {
+        let (device, queue, format) = (context.device, context.queue, context.format);
         let layout = Layout::new(device);
 
         let path = [
@@ -145,13 +135,11 @@ impl pixi::app::Game for Blending {
         let dst_bind_group = layout.bind_image(device, &dst, &sampler);
         let src_bind_group = layout.bind_image(device, &src, &sampler);
 
-        let shader = Shader::new(device);
+        let pipeline = BlendPipeline::new(device, &layout, format);
+        let target = RenderTarget::new(device, &layout, &sampler, format, 100, 100, 1);
 
-        let pipeline = BlendPipeline::new(device, &layout, &shader, format);
-        let target = RenderTarget::new(device, &layout, &sampler, format, 100, 100);
-
-        let normal = SpritePipeline::normal(device, &layout, &shader, format);
-        let replace = SpritePipeline::replace(device, &layout, &shader, format);
+        let normal = SpritePipeline::normal(device, &layout, format);
+        let replace = SpritePipeline::replace(device, &layout, format);
 
         Self {
             layout,
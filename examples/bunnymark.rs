@@ -1,6 +1,6 @@
 use pixi::{
-    app::{ControlFlow, EventLoop, PhysicalSize, Window, WindowEvent},
-    batch::Batch,
+    app::{winit::event::ElementState, ControlFlow, EventLoop, StartContext, Window, WindowEvent},
+    batch::{Batch, InstanceBatch},
     blend,
     image::{Image, ImageLoader},
     layout::Layout,
@@ -10,17 +10,25 @@ use pixi::{
 };
 use std::time::Instant;
 
-fn main() {
+fn main() -> Result<(), pixi::Error> {
     let event_loop = EventLoop::new();
     let window = Window::new(&event_loop).unwrap();
     window.set_title("BunnyMark");
 
-    pixi::app::run::<Basic>(event_loop, window, Default::default());
+    pixi::app::try_run::<Basic>(event_loop, window, Default::default())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Quads,
+    Instances,
 }
 
 struct Basic {
     perf: Perf,
+    mode: Mode,
     batch: Batch,
+    instanced: InstanceBatch,
     rabbit: Image,
     layout: Layout,
     entities: Vec<Entity>,
@@ -30,13 +38,8 @@ struct Basic {
 impl pixi::app::Game for Basic {
     type UserEvent = ();
 
-    fn start(
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        format: wgpu::TextureFormat,
-        _size: PhysicalSize<u32>,
-        _scale_factor: f64,
-    ) -> Self {
+    fn start(context: StartContext<Self::UserEvent>) -> Self {
+        let (device, queue, format) = (context.device, context.queue, context.format);
         let path = [
             "examples/assets/rabbit/rabbit.png",
             "examples/assets/rabbit/rabbit_ash.png",
@@ -62,6 +65,20 @@ impl pixi::app::Game for Basic {
         let bind_group = layout.bind_image(device, &rabbit, &sampler);
 
         let batch = Batch::new(device, &layout, format, blend::PMA_NORMAL, bind_group);
+        let instanced = InstanceBatch::new(
+            device,
+            &layout,
+            format,
+            blend::PMA_NORMAL,
+            &[&rabbit],
+            &sampler,
+        );
+
+        let mode = if std::env::args().any(|arg| arg == "--instanced") {
+            Mode::Instances
+        } else {
+            Mode::Quads
+        };
 
         let count = 100_000;
         let mut entities = Vec::with_capacity(count);
@@ -69,12 +86,14 @@ impl pixi::app::Game for Basic {
             entities.push(Entity::new());
         }
 
-        let perf = Perf::new(device, format);
+        let perf = Perf::new(device, &layout, format);
 
         Self {
             perf,
 
+            mode,
             batch,
+            instanced,
             rabbit,
             layout,
             entities,
@@ -85,6 +104,17 @@ impl pixi::app::Game for Basic {
 
     fn update(&mut self, event: WindowEvent, control_flow: &mut ControlFlow) {
         pixi::app::exit_helper(&event, control_flow);
+
+        if let WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            ..
+        } = event
+        {
+            self.mode = match self.mode {
+                Mode::Quads => Mode::Instances,
+                Mode::Instances => Mode::Quads,
+            };
+        }
     }
 
     fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, target: Target) {
@@ -115,17 +145,37 @@ impl pixi::app::Game for Basic {
         pixi::clear_color(&mut encoder, &target.view, [0.3, 0.3, 0.4, 1.0]);
 
         let (w, h) = (self.rabbit.width as f32, self.rabbit.height as f32);
-        for e in &self.entities {
-            let (x, y) = (e.position.x, e.position.y);
-            self.batch.add_sprite([x, y], [x + w, y + h]);
-        }
+        match self.mode {
+            Mode::Quads => {
+                for e in &self.entities {
+                    let (x, y) = (e.position.x, e.position.y);
+                    self.batch.add_sprite([x, y], [x + w, y + h]);
+                }
+
+                self.batch.flush(&mut encoder, &device, &target);
+            }
+            Mode::Instances => {
+                for e in &self.entities {
+                    let (x, y) = (e.position.x, e.position.y);
+                    self.instanced.add_sprite([x, y], [x + w, y + h], 0);
+                }
 
-        self.batch
-            .flush(&mut encoder, &device, &self.layout, &target);
+                self.instanced.flush(&mut encoder, &device, &target);
+            }
+        }
 
         self.perf.draw(&mut encoder, device, &target);
 
-        queue.submit(&[encoder.finish()]);
+        self.layout.submit(queue, encoder);
+    }
+
+    fn title(&self) -> Option<String> {
+        let mode = match self.mode {
+            Mode::Quads => "quads",
+            Mode::Instances => "instances",
+        };
+        let count = self.entities.len();
+        Some(format!("BunnyMark: {} {} (click to switch)", count, mode))
     }
 }
 
@@ -0,0 +1,105 @@
+use pixi::{
+    app::{ControlFlow, EventLoop, StartContext, Window, WindowEvent},
+    batch::Batch,
+    blend,
+    filter::{BlurFilter, DropShadowFilter, Filter, FilterSystem, GlowFilter},
+    image::{Image, ImageLoader},
+    layout::Layout,
+    math::{Bounds, Point},
+    target::Target,
+    wgpu,
+};
+use std::sync::Arc;
+
+fn main() -> Result<(), pixi::Error> {
+    let event_loop = EventLoop::new();
+    let window = Window::new(&event_loop).unwrap();
+    window.set_title("Filters Example");
+
+    pixi::app::try_run::<Filters>(event_loop, window, Default::default())
+}
+
+struct Filters {
+    batch: Batch,
+    bunny: Image,
+    layout: Arc<Layout>,
+
+    system: FilterSystem,
+    blur: BlurFilter,
+    shadow: DropShadowFilter,
+    glow: GlowFilter,
+}
+
+impl pixi::app::Game for Filters {
+    type UserEvent = ();
+
+    fn start(context: StartContext<Self::UserEvent>) -> Self {
+        let (device, queue, format) = (context.device, context.queue, context.format);
+        let mut loader = ImageLoader::new(device);
+        let bunny = loader
+            .srgb_premul(device, "examples/assets/bunny.png")
+            .unwrap();
+        queue.submit(&[loader.finish()]);
+
+        let layout = Arc::new(Layout::new(device));
+        let sampler = pixi::linear_sampler(device);
+
+        let bind_group = layout.bind_image(device, &bunny, &sampler);
+        let batch = Batch::new(device, &layout, format, blend::PMA_NORMAL, bind_group);
+
+        let system = FilterSystem::new(device, layout.clone(), format);
+        let blur = BlurFilter::new(device, &system, 2.0);
+        let shadow = DropShadowFilter::new(device, &system);
+        let mut glow = GlowFilter::new(device, &system, 3.0);
+        glow.color = [1.0, 0.8, 0.2];
+
+        Self {
+            batch,
+            bunny,
+            layout,
+
+            system,
+            blur,
+            shadow,
+            glow,
+        }
+    }
+
+    fn update(&mut self, event: WindowEvent, control_flow: &mut ControlFlow) {
+        pixi::app::exit_helper(&event, control_flow);
+    }
+
+    fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, target: Target) {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("default encoder"),
+        });
+
+        let target = Target {
+            scale: 4.0,
+            ..target
+        };
+
+        pixi::clear_color(&mut encoder, &target.view, [0.3, 0.3, 0.4, 1.0]);
+
+        let (w, h) = (self.bunny.width as f32, self.bunny.height as f32);
+        let filters: [&dyn Filter; 3] = [&self.blur, &self.shadow, &self.glow];
+
+        for (i, filter) in filters.iter().enumerate() {
+            let (x, y) = (10.0 + 3.0 * w * i as f32, 10.0);
+            let bounds = Bounds::new(Point { x, y }, Point { x: x + w, y: y + h });
+
+            let filters = [*filter];
+            let state = self
+                .system
+                .push(&mut encoder, device, &filters, bounds, &target);
+
+            if let Some(state) = state {
+                self.batch.add_sprite([x, y], [x + w, y + h]);
+                self.batch.flush(&mut encoder, device, &state.target());
+                self.system.pop(&mut encoder, device, state, &target);
+            }
+        }
+
+        self.layout.submit(queue, encoder);
+    }
+}
@@ -0,0 +1,45 @@
+use pixi::{
+    batch::Batch,
+    blend,
+    context::Context,
+    image::ImageLoader,
+    layout::Layout,
+    renderer::{Draw, Renderer},
+    target::RenderTarget,
+    wgpu,
+};
+
+fn main() {
+    let Context { device, queue, .. } = Context::headless().expect("no suitable adapter");
+
+    let mut loader = ImageLoader::new(&device);
+    let bunny = loader
+        .srgb_premul(&device, "examples/assets/bunny.png")
+        .unwrap();
+    queue.submit(&[loader.finish()]);
+
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let layout = Layout::new(&device);
+    let sampler = pixi::linear_sampler(&device);
+    let target = RenderTarget::new(&device, &layout, &sampler, format, 128, 128, 1);
+
+    let bind_group = layout.bind_image(&device, &bunny, &sampler);
+    let mut batch = Batch::new(&device, &layout, format, blend::PMA_NORMAL, bind_group);
+    let mut renderer = Renderer::new(&device, &layout);
+
+    let label = Some("screenshot");
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label });
+    target.clear(&mut encoder);
+
+    let (w, h) = (bunny.width as f32, bunny.height as f32);
+    batch.add_sprite([8.0, 8.0], [8.0 + w, 8.0 + h]);
+    batch.prepare(&mut encoder, &device);
+
+    let mut pass = renderer.begin(target.target(4.0));
+    pass.submit(&batch);
+    pass.finish(&mut encoder, &device);
+    queue.submit(&[encoder.finish()]);
+
+    let pixels = target.read_pixels(&device, &queue, true).unwrap();
+    pixels.save_png("screenshot.png").unwrap();
+}
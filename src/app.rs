@@ -4,12 +4,14 @@ pub use winit;
 pub use winit::{
     dpi::{LogicalSize, PhysicalSize},
     event::WindowEvent,
-    event_loop::{ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     window::{Window, WindowBuilder},
 };
 
 use crate::app::swap_chain::SwapChain;
+use crate::context::Context;
 use crate::target::Target;
+use crate::Error;
 use winit::event::{Event, StartCause};
 
 pub struct Options {
@@ -21,6 +23,9 @@ pub struct Options {
 
     pub format: wgpu::TextureFormat,
     pub present_mode: wgpu::PresentMode,
+    /// 1 or 2, 4 or 8 to draw into a multisampled framebuffer resolved into the
+    /// swap chain, see [`Target::sample_count`].
+    pub sample_count: u32,
 }
 
 impl Default for Options {
@@ -36,22 +41,48 @@ impl Default for Options {
 
             format: wgpu::TextureFormat::Bgra8UnormSrgb,
             present_mode: wgpu::PresentMode::Mailbox,
+            sample_count: 1,
         }
     }
 }
 
+/// Device and window state [`Game::start`] sets up rendering with.
+pub struct StartContext<'a, E: 'static> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub format: wgpu::TextureFormat,
+    /// Pipelines drawing into the frame target must be created for it, see
+    /// [`Batch::set_sample_count`](crate::batch::Batch::set_sample_count).
+    pub sample_count: u32,
+    pub size: PhysicalSize<u32>,
+    pub scale_factor: f64,
+    /// Sends [`Game::UserEvent`]s from other threads, e.g. from a
+    /// [`BackgroundLoader`](crate::assets::BackgroundLoader).
+    pub proxy: EventLoopProxy<E>,
+}
+
 pub trait Game: 'static + Sized {
     type UserEvent: 'static;
 
-    fn start(
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        format: wgpu::TextureFormat,
-        size: PhysicalSize<u32>,
-        scale_factor: f64,
-    ) -> Self;
+    fn start(context: StartContext<Self::UserEvent>) -> Self;
     fn update(&mut self, event: WindowEvent, control_flow: &mut ControlFlow);
+    fn user_event(
+        &mut self,
+        _device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        _event: Self::UserEvent,
+        _control_flow: &mut ControlFlow,
+    ) {
+    }
     fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, target: Target);
+
+    /// Window title, the window keeps its title while this is `None`.
+    ///
+    /// Checked after every [`Game::update`], the window is only retitled
+    /// when the title changes.
+    fn title(&self) -> Option<String> {
+        None
+    }
 }
 
 pub fn exit_helper(event: &WindowEvent, control_flow: &mut ControlFlow) {
@@ -68,15 +99,29 @@ pub fn exit_helper(event: &WindowEvent, control_flow: &mut ControlFlow) {
     }
 }
 
+/// Runs the game, panics if rendering can't be set up.
+///
+/// Prefer [`try_run`], which returns the error instead.
 pub fn run<App: Game>(event_loop: EventLoop<App::UserEvent>, window: Window, options: Options) {
-    futures::executor::block_on(run_async::<App>(event_loop, window, options));
+    if let Err(err) = try_run::<App>(event_loop, window, options) {
+        panic!("{}", err);
+    }
+}
+
+/// Runs the game, returns only if rendering can't be set up.
+pub fn try_run<App: Game>(
+    event_loop: EventLoop<App::UserEvent>,
+    window: Window,
+    options: Options,
+) -> Result<(), Error> {
+    futures::executor::block_on(run_async::<App>(event_loop, window, options))
 }
 
 async fn run_async<App: Game>(
     event_loop: EventLoop<App::UserEvent>,
     window: Window,
     options: Options,
-) {
+) -> Result<(), Error> {
     let (device, queue, mut swap_chain, mut app) = {
         let Options {
             power_preference,
@@ -87,28 +132,39 @@ async fn run_async<App: Game>(
 
             format,
             present_mode,
+            sample_count,
         } = options;
 
         let size = window.inner_size();
         let scale_factor = window.scale_factor();
         let surface = wgpu::Surface::create(&window);
 
-        let options = wgpu::RequestAdapterOptions {
-            power_preference,
-            compatible_surface: Some(&surface),
-        };
-
-        let (device, queue) = wgpu::Adapter::request(&options, backends)
-            .await
-            .unwrap()
-            .request_device(&wgpu::DeviceDescriptor { extensions, limits })
-            .await;
-
-        let sc = SwapChain::new(&device, surface, size, scale_factor, format, present_mode);
-        let app = App::start(&device, &queue, format, size, scale_factor);
+        let desc = wgpu::DeviceDescriptor { extensions, limits };
+        let Context { device, queue, .. } =
+            Context::request(power_preference, backends, &desc, Some(&surface))
+                .await
+                .ok_or(Error::NoAdapter)?;
+
+        let mut sc = SwapChain::new(&device, surface, size, scale_factor, format, present_mode);
+        sc.set_sample_count(&device, sample_count);
+        let proxy = event_loop.create_proxy();
+        let app = App::start(StartContext {
+            device: &device,
+            queue: &queue,
+            format,
+            sample_count,
+            size,
+            scale_factor,
+            proxy,
+        });
         (device, queue, sc, app)
     };
 
+    let mut title = app.title();
+    if let Some(title) = &title {
+        window.set_title(title);
+    }
+
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
 
@@ -128,24 +184,36 @@ async fn run_async<App: Game>(
                     }
 
                     app.update(event, control_flow);
+
+                    if let Some(next) = app.title() {
+                        if title.as_ref() != Some(&next) {
+                            window.set_title(&next);
+                            title = Some(next);
+                        }
+                    }
                 }
             }
 
             Event::DeviceEvent { .. } => {}
-            Event::UserEvent(_event) => {}
+            Event::UserEvent(event) => app.user_event(&device, &queue, event, control_flow),
             Event::Suspended => {}
             Event::Resumed => {}
 
             Event::MainEventsCleared => window.request_redraw(),
             Event::RedrawRequested(window_id) => {
                 if window_id == window.id() {
-                    let frame = swap_chain.next_frame();
+                    // a timed out frame is skipped, the next redraw tries again
+                    let frame = match swap_chain.next_frame() {
+                        Ok(frame) => frame,
+                        Err(_) => return,
+                    };
 
-                    let target = Target {
-                        view: &frame.view,
-                        width: swap_chain.size.width,
-                        height: swap_chain.size.height,
-                        scale: swap_chain.scale_factor as f32,
+                    let PhysicalSize { width, height } = swap_chain.size;
+                    let scale = swap_chain.scale_factor as f32;
+                    let target = Target::new(&frame.view, width, height, scale);
+                    let target = match &swap_chain.msaa {
+                        Some((_, msaa)) => target.multisampled(msaa, swap_chain.sample_count),
+                        None => target,
                     };
 
                     app.render(&device, &queue, target);
@@ -1,3 +1,4 @@
+use crate::{target::multisampled, Error};
 use winit::dpi::PhysicalSize;
 
 pub struct SwapChain {
@@ -8,6 +9,10 @@ pub struct SwapChain {
     pub format: wgpu::TextureFormat,
     pub size: PhysicalSize<u32>,
     pub scale_factor: f64,
+
+    pub sample_count: u32,
+    /// Multisampled framebuffer resolved into the swap chain texture.
+    pub msaa: Option<(wgpu::Texture, wgpu::TextureView)>,
 }
 
 impl SwapChain {
@@ -37,9 +42,26 @@ impl SwapChain {
             format,
             size,
             scale_factor,
+
+            sample_count: 1,
+            msaa: None,
         }
     }
 
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.create_msaa(device);
+    }
+
+    fn create_msaa(&mut self, device: &wgpu::Device) {
+        let size = wgpu::Extent3d {
+            width: self.size.width,
+            height: self.size.height,
+            depth: 1,
+        };
+        self.msaa = multisampled(device, self.format, size, self.sample_count);
+    }
+
     pub fn resize(&mut self, device: &wgpu::Device, size: PhysicalSize<u32>) {
         self.size = size;
 
@@ -52,11 +74,10 @@ impl SwapChain {
         };
 
         self.swap_chain = device.create_swap_chain(&self.surface, &desc);
+        self.create_msaa(device);
     }
 
-    pub fn next_frame(&mut self) -> wgpu::SwapChainOutput {
-        self.swap_chain
-            .get_next_texture()
-            .expect("Timeout when acquiring next swap chain texture")
+    pub fn next_frame(&mut self) -> Result<wgpu::SwapChainOutput, Error> {
+        Ok(self.swap_chain.get_next_texture()?)
     }
 }
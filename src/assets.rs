@@ -0,0 +1,366 @@
+//! Assets loaded by path into typed handles.
+//!
+//! Loading a path again returns a handle to the same asset. Assets are kept
+//! while any of their handles is alive and dropped by [`Assets::collect`].
+
+pub mod background;
+
+pub use self::background::{BackgroundLoader, Decoded, Loaded};
+
+use crate::{
+    image::{Image, ImageLoader, ImageSource, Mipmaps},
+    sprite::spritesheet::raw::Sheet,
+    Error,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+    ops::Index,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+};
+
+/// Reference counted handle to an asset of type `T`.
+pub struct Handle<T> {
+    index: usize,
+    refs: Arc<()>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index,
+            refs: self.refs.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.refs, &other.refs)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.index).finish()
+    }
+}
+
+impl<T> Handle<T> {
+    /// Number of handles to the asset.
+    pub fn ref_count(&self) -> usize {
+        Arc::strong_count(&self.refs)
+    }
+}
+
+/// Font file contents, parsed by the text renderer using them.
+pub struct Font {
+    pub bytes: Vec<u8>,
+}
+
+struct Slot<T> {
+    asset: T,
+    path: Option<PathBuf>,
+    refs: Weak<()>,
+}
+
+/// Slots of one asset type, reused after their asset is collected.
+pub(crate) struct Storage<T> {
+    slots: Vec<Option<Slot<T>>>,
+    free: Vec<usize>,
+    paths: HashMap<PathBuf, usize>,
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            paths: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Storage<T> {
+    /// Handle to the live asset loaded from `path`.
+    pub(crate) fn find(&self, path: &Path) -> Option<Handle<T>> {
+        let index = *self.paths.get(path)?;
+        let refs = self.slots[index].as_ref()?.refs.upgrade()?;
+        Some(Handle {
+            index,
+            refs,
+            marker: PhantomData,
+        })
+    }
+
+    pub(crate) fn insert(&mut self, path: Option<PathBuf>, asset: T) -> Handle<T> {
+        let refs = Arc::new(());
+        let slot = Slot {
+            asset,
+            path: path.clone(),
+            refs: Arc::downgrade(&refs),
+        };
+
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index] = Some(slot);
+                index
+            }
+            None => {
+                self.slots.push(Some(slot));
+                self.slots.len() - 1
+            }
+        };
+        if let Some(path) = path {
+            self.paths.insert(path, index);
+        }
+
+        Handle {
+            index,
+            refs,
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn get(&self, handle: &Handle<T>) -> &T {
+        &self.slots[handle.index].as_ref().unwrap().asset
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    /// Drops assets without handles, returns how many.
+    pub(crate) fn collect(&mut self) -> usize {
+        let mut count = 0;
+        for (index, entry) in self.slots.iter_mut().enumerate() {
+            let unused = entry
+                .as_ref()
+                .map_or(false, |slot| slot.refs.strong_count() == 0);
+            if unused {
+                // a reload may have replaced the path before collecting
+                if let Some(path) = entry.take().and_then(|slot| slot.path) {
+                    if self.paths.get(&path) == Some(&index) {
+                        self.paths.remove(&path);
+                    }
+                }
+                self.free.push(index);
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+/// Loads images, spritesheets, fonts and shaders.
+///
+/// Image uploads are recorded into one command buffer, call
+/// [`Assets::submit`] before drawing newly loaded images.
+#[derive(Default)]
+pub struct Assets {
+    loader: Option<ImageLoader>,
+    images: Storage<Image>,
+    sheets: Storage<Sheet>,
+    fonts: Storage<Font>,
+    shaders: Storage<wgpu::ShaderModule>,
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a premultiplied sRGB image.
+    pub fn load_image(
+        &mut self,
+        device: &wgpu::Device,
+        path: impl AsRef<Path>,
+    ) -> Result<Handle<Image>, Error> {
+        let path = path.as_ref();
+        if let Some(handle) = self.images.find(path) {
+            return Ok(handle);
+        }
+
+        let source = ImageSource::srgb_premul(path)?;
+        Ok(self.add_image_source(device, path, &source))
+    }
+
+    /// Uploads a decoded image loaded from `path`, or returns the one already loaded.
+    pub fn add_image_source(
+        &mut self,
+        device: &wgpu::Device,
+        path: impl AsRef<Path>,
+        source: &ImageSource,
+    ) -> Handle<Image> {
+        let path = path.as_ref();
+        if let Some(handle) = self.images.find(path) {
+            return handle;
+        }
+
+        let loader = self.loader.get_or_insert_with(|| ImageLoader::new(device));
+        let image = loader.upload(device, source, Mipmaps::None);
+        self.images.insert(Some(path.into()), image)
+    }
+
+    pub fn load_sheet(&mut self, path: impl AsRef<Path>) -> Result<Handle<Sheet>, Error> {
+        let path = path.as_ref();
+        if let Some(handle) = self.sheets.find(path) {
+            return Ok(handle);
+        }
+
+        let sheet = Sheet::open(path)?;
+        Ok(self.add_sheet(path, sheet))
+    }
+
+    /// Adds a spritesheet loaded from `path`, or returns the one already loaded.
+    pub fn add_sheet(&mut self, path: impl AsRef<Path>, sheet: Sheet) -> Handle<Sheet> {
+        let path = path.as_ref();
+        match self.sheets.find(path) {
+            Some(handle) => handle,
+            None => self.sheets.insert(Some(path.into()), sheet),
+        }
+    }
+
+    pub fn load_font(&mut self, path: impl AsRef<Path>) -> Result<Handle<Font>, Error> {
+        let path = path.as_ref();
+        if let Some(handle) = self.fonts.find(path) {
+            return Ok(handle);
+        }
+
+        let bytes = std::fs::read(path)?;
+        Ok(self.fonts.insert(Some(path.into()), Font { bytes }))
+    }
+
+    /// Loads a SPIR-V shader module.
+    pub fn load_shader(
+        &mut self,
+        device: &wgpu::Device,
+        path: impl AsRef<Path>,
+    ) -> Result<Handle<wgpu::ShaderModule>, Error> {
+        let path = path.as_ref();
+        if let Some(handle) = self.shaders.find(path) {
+            return Ok(handle);
+        }
+
+        let file = std::fs::File::open(path)?;
+        let spirv = wgpu::read_spirv(std::io::BufReader::new(file))?;
+        let module = device.create_shader_module(&spirv);
+        Ok(self.shaders.insert(Some(path.into()), module))
+    }
+
+    /// Adds an image created elsewhere, it isn't found by path.
+    pub fn add_image(&mut self, image: Image) -> Handle<Image> {
+        self.images.insert(None, image)
+    }
+
+    /// Uploads of the images loaded since the last call.
+    pub fn finish(&mut self) -> Option<wgpu::CommandBuffer> {
+        self.loader.take().map(ImageLoader::finish)
+    }
+
+    /// Submits the uploads of the images loaded since the last call.
+    pub fn submit(&mut self, queue: &wgpu::Queue) {
+        if let Some(uploads) = self.finish() {
+            queue.submit(&[uploads]);
+        }
+    }
+
+    /// Drops assets without handles, returns how many.
+    ///
+    /// Textures are freed once no bind group uses them.
+    pub fn collect(&mut self) -> usize {
+        self.images.collect()
+            + self.sheets.collect()
+            + self.fonts.collect()
+            + self.shaders.collect()
+    }
+
+    /// Number of loaded images, including ones waiting to be collected.
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+}
+
+impl Index<&Handle<Image>> for Assets {
+    type Output = Image;
+    fn index(&self, handle: &Handle<Image>) -> &Image {
+        self.images.get(handle)
+    }
+}
+
+impl Index<&Handle<Sheet>> for Assets {
+    type Output = Sheet;
+    fn index(&self, handle: &Handle<Sheet>) -> &Sheet {
+        self.sheets.get(handle)
+    }
+}
+
+impl Index<&Handle<Font>> for Assets {
+    type Output = Font;
+    fn index(&self, handle: &Handle<Font>) -> &Font {
+        self.fonts.get(handle)
+    }
+}
+
+impl Index<&Handle<wgpu::ShaderModule>> for Assets {
+    type Output = wgpu::ShaderModule;
+    fn index(&self, handle: &Handle<wgpu::ShaderModule>) -> &wgpu::ShaderModule {
+        self.shaders.get(handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedup_and_collect() {
+        let mut storage = Storage::default();
+        let a = storage.insert(Some("a".into()), 1);
+        let b = storage.find(Path::new("a")).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.ref_count(), 2);
+        assert!(storage.find(Path::new("b")).is_none());
+
+        drop(b);
+        assert_eq!(storage.collect(), 0);
+        assert_eq!(*storage.get(&a), 1);
+
+        drop(a);
+        assert_eq!(storage.collect(), 1);
+        assert_eq!(storage.len(), 0);
+        assert!(storage.find(Path::new("a")).is_none());
+
+        // the slot is reused
+        let c = storage.insert(None, 2);
+        assert_eq!(c.index, 0);
+        assert_eq!(*storage.get(&c), 2);
+
+        // reloading before collecting keeps the new path
+        drop(storage.insert(Some("d".into()), 3));
+        let d = storage.insert(Some("d".into()), 4);
+        assert_eq!(storage.collect(), 1);
+        assert_eq!(storage.find(Path::new("d")), Some(d));
+    }
+
+    #[test]
+    fn fonts() {
+        let path = std::env::temp_dir().join("pixi_assets_font.ttf");
+        std::fs::write(&path, b"font").unwrap();
+
+        let mut assets = Assets::new();
+        let a = assets.load_font(&path).unwrap();
+        let b = assets.load_font(&path).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(assets[&a].bytes, b"font");
+        assert!(assets.load_font("missing.ttf").is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
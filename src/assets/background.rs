@@ -0,0 +1,251 @@
+//! Decoding assets on worker threads.
+//!
+//! Workers decode files and notify the main thread, which uploads them with
+//! [`BackgroundLoader::poll`]. With the `app` feature the notification is
+//! usually an `EventLoopProxy` sending a `Game::UserEvent`.
+//!
+//! A worker that panics while decoding reports an error for that file and
+//! keeps going. Dropping the loader skips files that are still queued and
+//! only waits for the ones being decoded.
+
+use super::{Assets, Handle};
+use crate::{
+    image::{Image, ImageSource},
+    sprite::spritesheet::raw::Sheet,
+    Error,
+};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+};
+
+enum Job {
+    Image(PathBuf),
+    Sheet(PathBuf),
+}
+
+impl Job {
+    fn path(&self) -> &Path {
+        match self {
+            Job::Image(path) | Job::Sheet(path) => path,
+        }
+    }
+
+    fn decode(&self) -> Result<Decoded, Error> {
+        match self {
+            Job::Image(path) => ImageSource::srgb_premul(path).map(Decoded::Image),
+            Job::Sheet(path) => Sheet::open(path).map(Decoded::Sheet),
+        }
+    }
+}
+
+/// File decoded by a worker, not uploaded yet.
+pub enum Decoded {
+    Image(ImageSource),
+    Sheet(Sheet),
+}
+
+/// Asset added by [`BackgroundLoader::poll`].
+pub enum Loaded {
+    Image(Handle<Image>),
+    Sheet(Handle<Sheet>),
+}
+
+/// Decodes images and spritesheets on worker threads.
+pub struct BackgroundLoader {
+    jobs: Option<Sender<Job>>,
+    results: Receiver<(PathBuf, Result<Decoded, Error>)>,
+    workers: Vec<JoinHandle<()>>,
+    cancelled: Arc<AtomicBool>,
+    queued: usize,
+    finished: usize,
+}
+
+impl BackgroundLoader {
+    /// Starts `threads` workers, each calls `notify` after decoding a file.
+    pub fn new<F>(threads: usize, notify: F) -> Self
+    where
+        F: Fn() + Clone + Send + 'static,
+    {
+        let (jobs, job_receiver) = channel::<Job>();
+        let (result_sender, results) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let workers = (0..threads.max(1))
+            .map(|index| {
+                let jobs = job_receiver.clone();
+                let results = result_sender.clone();
+                let notify = notify.clone();
+                let cancelled = cancelled.clone();
+
+                thread::Builder::new()
+                    .name(format!("pixi loader {}", index))
+                    .spawn(move || loop {
+                        // the lock is released before decoding
+                        let job = jobs.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                        let job = match job {
+                            Ok(job) if !cancelled.load(Ordering::SeqCst) => job,
+                            _ => break,
+                        };
+
+                        let path = job.path().to_owned();
+                        let decoded = panic::catch_unwind(AssertUnwindSafe(|| job.decode()))
+                            .unwrap_or_else(|_| {
+                                let message = format!("decoding {} panicked", path.display());
+                                Err(Error::Format(message))
+                            });
+                        if results.send((path, decoded)).is_err() {
+                            break;
+                        }
+                        notify();
+                    })
+                    .expect("failed to spawn loader thread")
+            })
+            .collect();
+
+        Self {
+            jobs: Some(jobs),
+            results,
+            workers,
+            cancelled,
+            queued: 0,
+            finished: 0,
+        }
+    }
+
+    /// Queues a premultiplied sRGB image.
+    pub fn load_image(&mut self, path: impl Into<PathBuf>) {
+        self.send(Job::Image(path.into()));
+    }
+
+    pub fn load_sheet(&mut self, path: impl Into<PathBuf>) {
+        self.send(Job::Sheet(path.into()));
+    }
+
+    fn send(&mut self, job: Job) {
+        if let Some(jobs) = &self.jobs {
+            // workers only stop after the sender is dropped
+            let _ = jobs.send(job);
+            self.queued += 1;
+        }
+    }
+
+    /// Fraction of the queued files that finished, 1.0 if nothing is queued.
+    pub fn progress(&self) -> f32 {
+        if self.queued == 0 {
+            1.0
+        } else {
+            self.finished as f32 / self.queued as f32
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.finished == self.queued
+    }
+
+    /// Takes the next decoded file without uploading it, `None` if no file is
+    /// ready.
+    pub fn try_next(&mut self) -> Option<(PathBuf, Result<Decoded, Error>)> {
+        let next = self.results.try_recv().ok()?;
+        self.finished += 1;
+        Some(next)
+    }
+
+    /// Waits for the next decoded file, `None` once all queued files finished.
+    pub fn wait(&mut self) -> Option<(PathBuf, Result<Decoded, Error>)> {
+        if self.is_done() {
+            return None;
+        }
+        let next = self.results.recv().ok()?;
+        self.finished += 1;
+        Some(next)
+    }
+
+    /// Adds decoded files to `assets`, returns them with their paths.
+    ///
+    /// Images are recorded into the uploads of `assets`, submit them with
+    /// [`Assets::submit`] afterwards.
+    pub fn poll(
+        &mut self,
+        device: &wgpu::Device,
+        assets: &mut Assets,
+    ) -> Vec<(PathBuf, Result<Loaded, Error>)> {
+        let mut loaded = Vec::new();
+        while let Some((path, result)) = self.try_next() {
+            let result = result.map(|decoded| match decoded {
+                Decoded::Image(source) => {
+                    Loaded::Image(assets.add_image_source(device, &path, &source))
+                }
+                Decoded::Sheet(sheet) => Loaded::Sheet(assets.add_sheet(&path, sheet)),
+            });
+            loaded.push((path, result));
+        }
+        loaded
+    }
+}
+
+impl Drop for BackgroundLoader {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::atomic::AtomicUsize, time::Duration};
+
+    #[test]
+    fn progress() {
+        let notified = Arc::new(AtomicUsize::new(0));
+        let counter = notified.clone();
+        let mut loader = BackgroundLoader::new(2, move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        assert_eq!(loader.progress(), 1.0);
+
+        loader.load_image("missing.png");
+        loader.load_sheet("missing.json");
+        assert_eq!(loader.progress(), 0.0);
+
+        // missing files finish with an error, without needing a device
+        let mut paths = Vec::new();
+        while let Some((path, result)) = loader.wait() {
+            assert!(result.is_err());
+            paths.push(path);
+        }
+        paths.sort();
+        assert_eq!(paths, [PathBuf::from("missing.json"), "missing.png".into()]);
+        assert!(loader.is_done());
+
+        drop(loader);
+        assert_eq!(notified.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn drop_skips_queued_files() {
+        let notified = Arc::new(AtomicUsize::new(0));
+        let counter = notified.clone();
+        let mut loader = BackgroundLoader::new(1, move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(10));
+        });
+
+        for i in 0..100 {
+            loader.load_image(format!("missing-{}.png", i));
+        }
+        drop(loader);
+        assert!(notified.load(Ordering::SeqCst) < 10);
+    }
+}
@@ -1,46 +1,153 @@
 use crate::{
-    image::ImageBindGroup,
-    layout::{Layout, Shader, Vertex},
-    target::Target,
-    utils::quad_indices16,
+    blend::Blend,
+    image::{Image, ImageBindGroup},
+    layout::{CompactVertex, Layout, PipelineKey, ShaderId, Vertex},
+    math::pack_uv,
+    renderer::{Draw, Pass},
+    target::{Projection, Target},
+    utils::{quad_indices16, write_buffer, RingBuffer},
 };
 
+use std::sync::Arc;
+
+pub mod instanced;
+
+pub use self::instanced::{Instance, InstanceBatch};
+
+/// Index of `blend` in `blends`, pushed on first use.
+fn blend_index(blends: &mut Vec<Blend>, blend: Blend) -> u32 {
+    match blends.iter().position(|b| *b == blend) {
+        Some(index) => index as u32,
+        None => {
+            blends.push(blend);
+            blends.len() as u32 - 1
+        }
+    }
+}
+
+/// Pipelines of a batch, indexed like the blend modes it has used.
+struct BlendPipelines {
+    key: PipelineKey,
+    pipelines: Vec<Arc<wgpu::RenderPipeline>>,
+}
+
+impl BlendPipelines {
+    /// Pipeline for the blend mode of `key` at index 0.
+    fn new(device: &wgpu::Device, layout: &Layout, key: PipelineKey) -> Self {
+        let pipelines = vec![layout.cached_pipeline(device, key.clone())];
+        Self { key, pipelines }
+    }
+
+    /// Takes pipelines for the blend modes added to `blends` since the last
+    /// call from the layout cache.
+    fn sync(&mut self, device: &wgpu::Device, layout: &Layout, blends: &[Blend]) {
+        for blend in &blends[self.pipelines.len()..] {
+            let key = PipelineKey {
+                blend: blend.clone(),
+                ..self.key.clone()
+            };
+            self.pipelines.push(layout.cached_pipeline(device, key));
+        }
+    }
+
+    /// Replaces all pipelines with variants for `sample_count`, keeping indices.
+    fn set_sample_count(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &Layout,
+        blends: &[Blend],
+        sample_count: u32,
+    ) {
+        self.key.sample_count = sample_count;
+        self.pipelines.clear();
+        self.sync(device, layout, blends);
+    }
+
+    /// Checks in debug builds that the pipelines are for as many samples as `target`.
+    fn check_target(&self, target: &Target) {
+        debug_assert!(
+            self.key.sample_count == target.sample_count,
+            "pipelines for {} samples can't draw into a target with {}, see `set_sample_count`",
+            self.key.sample_count,
+            target.sample_count,
+        );
+    }
+
+    fn get(&self, index: u32) -> &wgpu::RenderPipeline {
+        &self.pipelines[index as usize]
+    }
+}
+
+#[derive(Clone, Copy, Default)]
 struct DrawQuad {
     end: u32,
     base: i32,
+    pipeline: u32,
 }
 
-struct QuadBatch {
+/// Vertices of a batch split into draws by blend mode, without any GPU state.
+///
+/// Every batch records into one, it can also be built without a device, e.g.
+/// for the reference rasterizer in [`soft`](crate::soft).
+pub struct Quads<V> {
     cmd_first: DrawQuad,
     cmd: Vec<DrawQuad>,
-    vtx: Vec<Vertex>,
-    idx: wgpu::Buffer,
+    vtx: Vec<V>,
+    /// Blend modes in order of first use, indexed by the pipeline of each draw.
+    blends: Vec<Blend>,
+    /// Pipeline index for the following quads.
+    pipeline: u32,
 }
 
-impl QuadBatch {
+impl<V: Clone> Quads<V> {
     const MAX_QUADS: u32 = 0x1_0000 / 4;
     const MAX_INDEX: u32 = Self::MAX_QUADS * 6;
 
-    fn new(device: &wgpu::Device) -> Self {
-        let idx: Vec<u16> = quad_indices16().collect();
+    pub fn new(blend: Blend) -> Self {
         Self {
-            cmd_first: DrawQuad { end: 0, base: 0 },
+            cmd_first: DrawQuad::default(),
             cmd: Vec::new(),
             vtx: Vec::new(),
-            idx: device.create_buffer_with_data(crate::cast_slice(&idx), wgpu::BufferUsage::INDEX),
+            blends: vec![blend],
+            pipeline: 0,
         }
     }
 
-    #[inline]
-    fn clear(&mut self) {
-        self.cmd_first = DrawQuad { end: 0, base: 0 };
-        self.cmd.clear();
-        self.vtx.clear();
+    pub fn is_empty(&self) -> bool {
+        self.vtx.is_empty()
+    }
+
+    /// Index of `blend` for [`Quads::add_quad_blend`], blend modes are
+    /// numbered in order of first use.
+    pub fn blend_index(&mut self, blend: Blend) -> u32 {
+        blend_index(&mut self.blends, blend)
+    }
+
+    /// Blend mode of the quads added from now on.
+    ///
+    /// Returns its index, see [`Quads::blend_index`].
+    pub fn set_blend(&mut self, blend: Blend) -> u32 {
+        self.pipeline = self.blend_index(blend);
+        self.pipeline
+    }
+
+    pub fn blend(&self) -> &Blend {
+        &self.blends[self.pipeline as usize]
+    }
+
+    /// Blend modes in order of first use.
+    pub fn blends(&self) -> &[Blend] {
+        &self.blends
     }
 
     #[inline]
-    fn last(&self) -> &DrawQuad {
-        self.cmd.last().unwrap_or(&self.cmd_first)
+    pub fn clear(&mut self) {
+        self.cmd_first = DrawQuad {
+            pipeline: self.pipeline,
+            ..DrawQuad::default()
+        };
+        self.cmd.clear();
+        self.vtx.clear();
     }
 
     #[inline]
@@ -53,16 +160,39 @@ impl QuadBatch {
         std::iter::once(&self.cmd_first).chain(&self.cmd)
     }
 
+    /// Blend mode and vertices of every draw, four vertices per quad in draw
+    /// order.
+    pub fn runs(&self) -> impl Iterator<Item = (&Blend, &[V])> {
+        self.commands().filter(|cmd| cmd.end != 0).map(move |cmd| {
+            let start = cmd.base as usize;
+            let end = start + cmd.end as usize / 6 * 4;
+            (&self.blends[cmd.pipeline as usize], &self.vtx[start..end])
+        })
+    }
+
+    /// Adds a quad split into two triangles, `[0, 1, 2]` and `[0, 2, 3]`.
+    ///
+    /// Pipelines cull clockwise triangles, see [`Quads::add_sprite`] for the order.
     #[inline]
-    fn vertex_buffer(&self, device: &wgpu::Device) -> wgpu::Buffer {
-        device.create_buffer_with_data(crate::cast_slice(&self.vtx), wgpu::BufferUsage::VERTEX)
+    pub fn add_quad(&mut self, quad: [V; 4]) {
+        self.add_quad_blend(quad, self.pipeline);
     }
 
+    /// Adds a quad with the blend mode at index `blend`, without changing the
+    /// blend mode of the following quads.
     #[inline]
-    fn add_quad(&mut self, quad: [Vertex; 4]) {
-        if self.last().end >= Self::MAX_INDEX {
-            let base = self.vtx.len() as i32;
-            self.cmd.push(DrawQuad { end: 0, base });
+    pub fn add_quad_blend(&mut self, quad: [V; 4], blend: u32) {
+        assert!((blend as usize) < self.blends.len(), "unknown blend index");
+        let (pipeline, base) = (blend, self.vtx.len() as i32);
+        let last = self.last_mut();
+        if last.end == 0 {
+            last.pipeline = pipeline;
+        } else if last.end >= Self::MAX_INDEX || last.pipeline != pipeline {
+            self.cmd.push(DrawQuad {
+                end: 0,
+                base,
+                pipeline,
+            });
         }
 
         let cmd = self.last_mut();
@@ -73,9 +203,132 @@ impl QuadBatch {
     }
 }
 
+impl Quads<Vertex> {
+    /// Adds an axis aligned quad showing the whole texture.
+    pub fn add_sprite(&mut self, min: [f32; 2], max: [f32; 2]) {
+        self.add_sprite_blend(min, max, self.pipeline)
+    }
+
+    /// [`Quads::add_sprite`] with the blend mode at index `blend`.
+    pub fn add_sprite_blend(
+        &mut self,
+        [min_x, min_y]: [f32; 2],
+        [max_x, max_y]: [f32; 2],
+        blend: u32,
+    ) {
+        self.add_quad_blend(
+            [
+                Vertex::new(max_x, max_y, 1.0, 1.0), // 11
+                Vertex::new(max_x, min_y, 1.0, 0.0), // 10
+                Vertex::new(min_x, min_y, 0.0, 0.0), // 00
+                Vertex::new(min_x, max_y, 0.0, 1.0), // 01
+            ],
+            blend,
+        )
+    }
+}
+
+struct QuadBatch<V> {
+    quads: Quads<V>,
+    vtx_buffer: RingBuffer,
+    idx: wgpu::Buffer,
+    /// Used by [`QuadBatch::draw`], a [`Pass`] binds its own.
+    projection: Projection,
+
+    /// Commands and vertex offset of the last [`Draw::prepare`].
+    prepared: Vec<DrawQuad>,
+    prepared_offset: wgpu::BufferAddress,
+}
+
+impl<V: Clone> QuadBatch<V> {
+    fn new(device: &wgpu::Device, layout: &Layout, blend: Blend) -> Self {
+        let idx: Vec<u16> = quad_indices16().collect();
+        let usage = wgpu::BufferUsage::VERTEX;
+        let capacity = (std::mem::size_of::<V>() * 4 * 1024) as wgpu::BufferAddress;
+        Self {
+            quads: Quads::new(blend),
+            vtx_buffer: RingBuffer::new(device, &layout.staging, "quad vertices", usage, capacity),
+            idx: device.create_buffer_with_data(crate::cast_slice(&idx), wgpu::BufferUsage::INDEX),
+            projection: Projection::new(device, layout),
+
+            prepared: Vec::new(),
+            prepared_offset: 0,
+        }
+    }
+
+    /// Stages pending vertices and returns their offset in the vertex buffer.
+    #[inline]
+    fn upload(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+    ) -> wgpu::BufferAddress {
+        let data = crate::cast_slice(&self.quads.vtx);
+        self.vtx_buffer.write(encoder, device, data)
+    }
+
+    fn prepare(&mut self, encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device) {
+        self.prepared.clear();
+        if self.quads.is_empty() {
+            return;
+        }
+
+        self.prepared_offset = self.upload(encoder, device);
+        self.prepared.push(self.quads.cmd_first);
+        self.prepared.extend_from_slice(&self.quads.cmd);
+        self.quads.clear();
+    }
+
+    fn record<'r>(&'r self, pass: &mut Pass<'r>, pipelines: &'r BlendPipelines) {
+        if self.prepared.is_empty() {
+            return;
+        }
+        pipelines.check_target(pass.target());
+
+        pass.set_vertex_buffer(0, self.vtx_buffer.buffer(), self.prepared_offset);
+        pass.set_index_buffer(&self.idx);
+        for cmd in &self.prepared {
+            pass.set_pipeline(pipelines.get(cmd.pipeline));
+            pass.draw_indexed(0..cmd.end, cmd.base, 0..1);
+        }
+    }
+
+    fn draw(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        target: &Target,
+        pipelines: &BlendPipelines,
+        bind_group: &wgpu::BindGroup,
+        instance: Option<&wgpu::Buffer>,
+    ) {
+        let offset = self.upload(encoder, device);
+        let proj_bind_group = self.projection.update(encoder, device, target);
+
+        let mut rpass = target.rpass(encoder);
+
+        rpass.set_vertex_buffer(0, self.vtx_buffer.buffer(), offset, 0);
+        if let Some(instance) = instance {
+            rpass.set_vertex_buffer(1, instance, 0, 0);
+        }
+        rpass.set_index_buffer(&self.idx, 0, 0);
+        rpass.set_bind_group(0, proj_bind_group, &[]);
+        rpass.set_bind_group(1, bind_group, &[]);
+
+        let mut current = None;
+        for cmd in self.quads.commands() {
+            if current != Some(cmd.pipeline) {
+                current = Some(cmd.pipeline);
+                rpass.set_pipeline(pipelines.get(cmd.pipeline));
+            }
+            rpass.draw_indexed(0..cmd.end, cmd.base, 0..1);
+        }
+    }
+}
+
 pub struct Batch {
-    quad: QuadBatch,
-    pipeline: wgpu::RenderPipeline,
+    quad: QuadBatch<Vertex>,
+    pipelines: BlendPipelines,
     bind_group: ImageBindGroup,
 }
 
@@ -84,73 +337,288 @@ impl Batch {
         device: &wgpu::Device,
         layout: &Layout,
         format: wgpu::TextureFormat,
-        blend: crate::blend::Blend,
+        blend: Blend,
         bind_group: ImageBindGroup,
     ) -> Self {
-        let color_state = wgpu::ColorStateDescriptor {
-            format,
-            alpha_blend: blend.alpha,
-            color_blend: blend.color,
-            write_mask: wgpu::ColorWrite::ALL,
-        };
-
-        let shader = Shader::new(device);
-        let pipeline = layout.create_pipeline(device, &shader, color_state);
+        let key = PipelineKey::new(ShaderId::Sprite, blend.clone(), format);
 
         Self {
-            quad: QuadBatch::new(device),
-            pipeline,
+            quad: QuadBatch::new(device, layout, blend),
+            pipelines: BlendPipelines::new(device, layout, key),
             bind_group,
         }
     }
 
+    /// Index of `blend` for the `add_*_blend` methods, see [`Quads::blend_index`].
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn blend_index(&mut self, device: &wgpu::Device, layout: &Layout, blend: Blend) -> u32 {
+        let index = self.quad.quads.blend_index(blend);
+        let blends = self.quad.quads.blends();
+        self.pipelines.sync(device, layout, blends);
+        index
+    }
+
+    /// Blend mode of the sprites added from now on.
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn set_blend(&mut self, device: &wgpu::Device, layout: &Layout, blend: Blend) {
+        self.quad.quads.set_blend(blend);
+        let blends = self.quad.quads.blends();
+        self.pipelines.sync(device, layout, blends);
+    }
+
+    /// Switches to pipelines for targets with `sample_count` samples.
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, layout: &Layout, sample_count: u32) {
+        let blends = self.quad.quads.blends();
+        self.pipelines
+            .set_sample_count(device, layout, blends, sample_count);
+    }
+
+    pub fn blend(&self) -> &Blend {
+        self.quad.quads.blend()
+    }
+
+    /// Quads added since the last flush.
+    pub fn quads(&self) -> &Quads<Vertex> {
+        &self.quad.quads
+    }
+
     pub fn add_quad(&mut self, quad: [Vertex; 4]) {
-        self.quad.add_quad(quad);
+        self.quad.quads.add_quad(quad);
+    }
+
+    /// Adds a quad with the blend mode at index `blend`, see [`Batch::blend_index`].
+    pub fn add_quad_blend(&mut self, quad: [Vertex; 4], blend: u32) {
+        self.quad.quads.add_quad_blend(quad, blend);
     }
 
-    pub fn add_sprite(&mut self, [min_x, min_y]: [f32; 2], [max_x, max_y]: [f32; 2]) {
-        self.quad.add_quad([
-            Vertex::new(max_x, max_y, 1.0, 1.0), // 11
-            Vertex::new(max_x, min_y, 1.0, 0.0), // 10
-            Vertex::new(min_x, min_y, 0.0, 0.0), // 00
-            Vertex::new(min_x, max_y, 0.0, 1.0), // 01
-        ])
+    pub fn add_sprite(&mut self, min: [f32; 2], max: [f32; 2]) {
+        self.quad.quads.add_sprite(min, max)
+    }
+
+    /// Adds a sprite with the blend mode at index `blend`, see [`Batch::blend_index`].
+    pub fn add_sprite_blend(&mut self, min: [f32; 2], max: [f32; 2], blend: u32) {
+        self.quad.quads.add_sprite_blend(min, max, blend)
     }
 
     pub fn clear(&mut self) {
-        self.quad.clear();
+        self.quad.quads.clear();
     }
 
     pub fn flush(
         &mut self,
         encoder: &mut wgpu::CommandEncoder,
         device: &wgpu::Device,
-        layout: &Layout,
         target: &Target,
     ) {
-        if self.quad.vtx.is_empty() {
+        if self.quad.quads.is_empty() {
             self.clear();
             return;
         }
 
-        let vtx = self.quad.vertex_buffer(device);
+        self.pipelines.check_target(target);
+        let (pipelines, bind_group) = (&self.pipelines, &*self.bind_group);
+        self.quad
+            .draw(encoder, device, target, pipelines, bind_group, None);
 
-        let proj_bind_group = target.projection(device, layout);
+        self.clear();
+    }
+}
+
+impl Draw for Batch {
+    fn prepare(&mut self, encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device) {
+        self.quad.prepare(encoder, device);
+    }
 
-        {
-            let mut rpass = target.rpass(encoder);
+    fn draw<'r>(&'r self, pass: &mut Pass<'r>) {
+        pass.set_bind_group(1, &self.bind_group);
+        self.quad.record(pass, &self.pipelines);
+    }
+}
 
-            rpass.set_pipeline(&self.pipeline);
-            rpass.set_vertex_buffer(0, &vtx, 0, 0);
-            rpass.set_index_buffer(&self.quad.idx, 0, 0);
-            rpass.set_bind_group(0, &proj_bind_group, &[]);
-            rpass.set_bind_group(1, &self.bind_group, &[]);
+/// [`Batch`] with [`CompactVertex`]es, per-vertex colors and up to
+/// [`MAX_SLOTS`](crate::layout::MAX_SLOTS) textures.
+pub struct CompactBatch {
+    quad: QuadBatch<CompactVertex>,
+    pipelines: BlendPipelines,
+    bind_group: wgpu::BindGroup,
 
-            for cmd in self.quad.commands() {
-                rpass.draw_indexed(0..cmd.end, cmd.base, 0..1);
-            }
+    origin: [f32; 2],
+    /// Per-instance origin, rewritten when it differs from the uploaded one.
+    origin_buffer: wgpu::Buffer,
+    uploaded_origin: Option<[f32; 2]>,
+}
+
+impl CompactBatch {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &Layout,
+        format: wgpu::TextureFormat,
+        blend: Blend,
+        images: &[&Image],
+        sampler: &wgpu::Sampler,
+    ) -> Self {
+        let key = PipelineKey::new(ShaderId::Compact, blend.clone(), format);
+
+        Self {
+            quad: QuadBatch::new(device, layout, blend),
+            pipelines: BlendPipelines::new(device, layout, key),
+            bind_group: layout.bind_slots(device, images, sampler),
+
+            origin: [0.0, 0.0],
+            origin_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("compact origin"),
+                size: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+            }),
+            uploaded_origin: None,
+        }
+    }
+
+    /// Logical position vertices are stored relative to.
+    ///
+    /// Quads must stay within [`CompactVertex::MAX_OFFSET`] pixels of it, it
+    /// can only be moved while the batch is empty.
+    pub fn set_origin(&mut self, origin: [f32; 2]) {
+        debug_assert!(
+            self.quad.quads.is_empty(),
+            "the origin of a batch with quads can't be moved"
+        );
+        self.origin = origin;
+    }
+
+    pub fn origin(&self) -> [f32; 2] {
+        self.origin
+    }
+
+    fn upload_origin(&mut self, encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device) {
+        if self.uploaded_origin != Some(self.origin) {
+            let data = crate::cast_slice(&self.origin);
+            write_buffer(encoder, device, &self.origin_buffer, 0, data);
+            self.uploaded_origin = Some(self.origin);
         }
+    }
+
+    /// Replaces the textures bound to the slots.
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn set_images(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &Layout,
+        images: &[&Image],
+        sampler: &wgpu::Sampler,
+    ) {
+        self.bind_group = layout.bind_slots(device, images, sampler);
+    }
+
+    /// Index of `blend` for the `add_*_blend` methods, see [`Quads::blend_index`].
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn blend_index(&mut self, device: &wgpu::Device, layout: &Layout, blend: Blend) -> u32 {
+        let index = self.quad.quads.blend_index(blend);
+        let blends = self.quad.quads.blends();
+        self.pipelines.sync(device, layout, blends);
+        index
+    }
+
+    /// Blend mode of the sprites added from now on.
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn set_blend(&mut self, device: &wgpu::Device, layout: &Layout, blend: Blend) {
+        self.quad.quads.set_blend(blend);
+        let blends = self.quad.quads.blends();
+        self.pipelines.sync(device, layout, blends);
+    }
+
+    /// Switches to pipelines for targets with `sample_count` samples.
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, layout: &Layout, sample_count: u32) {
+        let blends = self.quad.quads.blends();
+        self.pipelines
+            .set_sample_count(device, layout, blends, sample_count);
+    }
+
+    pub fn blend(&self) -> &Blend {
+        self.quad.quads.blend()
+    }
+
+    /// Quad of vertices created with the [`origin`](CompactBatch::origin) of the batch.
+    pub fn add_quad(&mut self, quad: [CompactVertex; 4]) {
+        self.quad.quads.add_quad(quad);
+    }
+
+    /// [`CompactBatch::add_quad`] with the blend mode at index `blend`, see
+    /// [`CompactBatch::blend_index`].
+    pub fn add_quad_blend(&mut self, quad: [CompactVertex; 4], blend: u32) {
+        self.quad.quads.add_quad_blend(quad, blend);
+    }
+
+    pub fn add_sprite(&mut self, min: [f32; 2], max: [f32; 2], color: [u8; 4], texture_id: u8) {
+        let blend = self.quad.quads.pipeline;
+        self.add_sprite_blend(min, max, color, texture_id, blend)
+    }
+
+    /// Adds a sprite with the blend mode at index `blend`, see [`CompactBatch::blend_index`].
+    pub fn add_sprite_blend(
+        &mut self,
+        [min_x, min_y]: [f32; 2],
+        [max_x, max_y]: [f32; 2],
+        color: [u8; 4],
+        texture_id: u8,
+        blend: u32,
+    ) {
+        let origin = self.origin;
+        let vertex =
+            |x, y, u, v| CompactVertex::new(origin, x, y, pack_uv(u, v), color, texture_id);
+        let quad = [
+            vertex(max_x, max_y, 1.0, 1.0), // 11
+            vertex(max_x, min_y, 1.0, 0.0), // 10
+            vertex(min_x, min_y, 0.0, 0.0), // 00
+            vertex(min_x, max_y, 0.0, 1.0), // 01
+        ];
+        self.quad.quads.add_quad_blend(quad, blend)
+    }
+
+    pub fn clear(&mut self) {
+        self.quad.quads.clear();
+    }
+
+    pub fn flush(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        target: &Target,
+    ) {
+        if self.quad.quads.is_empty() {
+            self.clear();
+            return;
+        }
+
+        self.pipelines.check_target(target);
+        self.upload_origin(encoder, device);
+        let (pipelines, bind_group) = (&self.pipelines, &self.bind_group);
+        let origin = Some(&self.origin_buffer);
+        self.quad
+            .draw(encoder, device, target, pipelines, bind_group, origin);
 
         self.clear();
     }
 }
+
+impl Draw for CompactBatch {
+    fn prepare(&mut self, encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device) {
+        self.upload_origin(encoder, device);
+        self.quad.prepare(encoder, device);
+    }
+
+    fn draw<'r>(&'r self, pass: &mut Pass<'r>) {
+        pass.set_bind_group(1, &self.bind_group);
+        pass.set_vertex_buffer(1, &self.origin_buffer, 0);
+        self.quad.record(pass, &self.pipelines);
+    }
+}
@@ -0,0 +1,288 @@
+use super::BlendPipelines;
+use crate::{
+    blend::Blend,
+    image::Image,
+    layout::{Layout, PipelineKey, ShaderId, MAX_SLOTS},
+    math::{Frame, Matrix},
+    renderer::{Draw, Pass},
+    target::{Projection, Target},
+    utils::RingBuffer,
+};
+
+/// Per-sprite data for [`InstanceBatch`].
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Instance {
+    /// Maps the unit quad into logical pixels, see [`Instance::new`].
+    pub transform: [f32; 6],
+    /// Texture coordinates of the top-left and bottom-right corners.
+    pub frame: [f32; 4],
+    /// Premultiplied color multiplied with the texture.
+    pub tint: [u8; 4],
+    /// Texture slot, in `0..MAX_SLOTS`.
+    pub slot: u32,
+}
+
+impl Instance {
+    pub const fn format() -> [wgpu::VertexAttributeDescriptor; 6] {
+        wgpu::vertex_attr_array![
+            1 => Float2,
+            2 => Float2,
+            3 => Float2,
+            4 => Float4,
+            5 => Uchar4Norm,
+            6 => Uint
+        ]
+    }
+
+    /// Sprite of `width` by `height` logical pixels with the top-left corner at
+    /// the origin of `matrix`, showing the whole texture.
+    pub fn new(matrix: &Matrix, width: f32, height: f32, slot: u32) -> Self {
+        let Matrix { a, b, c, d, tx, ty } = *matrix;
+        Self {
+            transform: [a * width, b * width, c * height, d * height, tx, ty],
+            frame: [0.0, 0.0, 1.0, 1.0],
+            tint: [0xFF; 4],
+            slot,
+        }
+    }
+
+    /// Shows the `frame` part of a texture of `[tw, th]` pixels.
+    #[must_use]
+    pub fn with_frame(self, frame: Frame, [tw, th]: [f32; 2]) -> Self {
+        let [x0, y0] = frame.min();
+        let [x1, y1] = frame.max();
+        let frame = [x0 / tw, y0 / th, x1 / tw, y1 / th];
+        Self { frame, ..self }
+    }
+
+    #[must_use]
+    pub fn with_tint(self, tint: [u8; 4]) -> Self {
+        Self { tint, ..self }
+    }
+}
+
+/// Sprite renderer drawing one instance per sprite against a shared unit quad.
+///
+/// Uploads 48 bytes per sprite instead of four [`Vertex`](crate::layout::Vertex)es
+/// of [`Batch`](super::Batch).
+pub struct InstanceBatch {
+    instances: Vec<Instance>,
+    /// End of each run of instances sharing a pipeline, and its index.
+    runs: Vec<(u32, u32)>,
+    buffer: RingBuffer,
+    /// Offset and runs of the instances uploaded by [`Draw::prepare`].
+    prepared: (wgpu::BufferAddress, Vec<(u32, u32)>),
+    quad_vtx: wgpu::Buffer,
+    quad_idx: wgpu::Buffer,
+
+    bind_group: wgpu::BindGroup,
+    /// Blend modes in order of first use, indexed by the runs.
+    blends: Vec<Blend>,
+    pipelines: BlendPipelines,
+    /// Blend index for the following instances.
+    pipeline: u32,
+    projection: Projection,
+}
+
+impl InstanceBatch {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &Layout,
+        format: wgpu::TextureFormat,
+        blend: Blend,
+        images: &[&Image],
+        sampler: &wgpu::Sampler,
+    ) -> Self {
+        let key = PipelineKey::new(ShaderId::Instanced, blend.clone(), format);
+
+        let corners: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+        let quad_vtx = crate::cast_slice(&corners);
+        let quad_vtx = device.create_buffer_with_data(quad_vtx, wgpu::BufferUsage::VERTEX);
+        let quad_idx = crate::cast_slice(&indices);
+        let quad_idx = device.create_buffer_with_data(quad_idx, wgpu::BufferUsage::INDEX);
+
+        let usage = wgpu::BufferUsage::VERTEX;
+        let capacity = (std::mem::size_of::<Instance>() * 1024) as wgpu::BufferAddress;
+        let buffer = RingBuffer::new(device, &layout.staging, "sprite instances", usage, capacity);
+
+        let bind_group = layout.bind_slots(device, images, sampler);
+
+        Self {
+            instances: Vec::new(),
+            runs: Vec::new(),
+            buffer,
+            prepared: (0, Vec::new()),
+            quad_vtx,
+            quad_idx,
+
+            bind_group,
+            blends: vec![blend],
+            pipelines: BlendPipelines::new(device, layout, key),
+            pipeline: 0,
+            projection: Projection::new(device, layout),
+        }
+    }
+
+    /// Replaces the textures bound to the slots, up to [`MAX_SLOTS`].
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn set_images(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &Layout,
+        images: &[&Image],
+        sampler: &wgpu::Sampler,
+    ) {
+        self.bind_group = layout.bind_slots(device, images, sampler);
+    }
+
+    /// Index of `blend` for the `add_*_blend` methods, blend modes are
+    /// numbered in order of first use.
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn blend_index(&mut self, device: &wgpu::Device, layout: &Layout, blend: Blend) -> u32 {
+        let index = super::blend_index(&mut self.blends, blend);
+        self.pipelines.sync(device, layout, &self.blends);
+        index
+    }
+
+    /// Blend mode of the instances added from now on.
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn set_blend(&mut self, device: &wgpu::Device, layout: &Layout, blend: Blend) {
+        self.pipeline = self.blend_index(device, layout, blend);
+    }
+
+    /// Switches to pipelines for targets with `sample_count` samples.
+    ///
+    /// `layout` must be the one the batch was created with.
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, layout: &Layout, sample_count: u32) {
+        self.pipelines
+            .set_sample_count(device, layout, &self.blends, sample_count);
+    }
+
+    pub fn blend(&self) -> &Blend {
+        &self.blends[self.pipeline as usize]
+    }
+
+    pub fn add(&mut self, instance: Instance) {
+        self.add_blend(instance, self.pipeline);
+    }
+
+    /// Adds an instance with the blend mode at index `blend`, see
+    /// [`InstanceBatch::blend_index`].
+    pub fn add_blend(&mut self, instance: Instance, blend: u32) {
+        debug_assert!((instance.slot as usize) < MAX_SLOTS);
+        assert!((blend as usize) < self.blends.len(), "unknown blend index");
+        self.instances.push(instance);
+
+        let end = self.instances.len() as u32;
+        match self.runs.last_mut() {
+            Some((last, pipeline)) if *pipeline == blend => *last = end,
+            _ => self.runs.push((end, blend)),
+        }
+    }
+
+    pub fn add_sprite(&mut self, min: [f32; 2], max: [f32; 2], slot: u32) {
+        self.add_sprite_blend(min, max, slot, self.pipeline);
+    }
+
+    /// Adds a sprite with the blend mode at index `blend`, see
+    /// [`InstanceBatch::blend_index`].
+    pub fn add_sprite_blend(
+        &mut self,
+        [min_x, min_y]: [f32; 2],
+        [max_x, max_y]: [f32; 2],
+        slot: u32,
+        blend: u32,
+    ) {
+        let matrix = Matrix::new(1.0, 0.0, 0.0, 1.0, min_x, min_y);
+        let instance = Instance::new(&matrix, max_x - min_x, max_y - min_y, slot);
+        self.add_blend(instance, blend);
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.runs.clear();
+    }
+
+    pub fn flush(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        target: &Target,
+    ) {
+        if self.instances.is_empty() {
+            return;
+        }
+
+        self.pipelines.check_target(target);
+        let offset = self
+            .buffer
+            .write(encoder, device, crate::cast_slice(&self.instances));
+        let proj_bind_group = self.projection.update(encoder, device, target);
+
+        {
+            let mut rpass = target.rpass(encoder);
+
+            rpass.set_vertex_buffer(0, &self.quad_vtx, 0, 0);
+            rpass.set_vertex_buffer(1, self.buffer.buffer(), offset, 0);
+            rpass.set_index_buffer(&self.quad_idx, 0, 0);
+            rpass.set_bind_group(0, proj_bind_group, &[]);
+            rpass.set_bind_group(1, &self.bind_group, &[]);
+
+            let mut start = 0;
+            for &(end, pipeline) in &self.runs {
+                rpass.set_pipeline(self.pipelines.get(pipeline));
+                rpass.draw_indexed(0..6, 0, start..end);
+                start = end;
+            }
+        }
+
+        self.clear();
+    }
+}
+
+impl Draw for InstanceBatch {
+    fn prepare(&mut self, encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device) {
+        self.prepared.1.clear();
+        if self.instances.is_empty() {
+            return;
+        }
+
+        let data = crate::cast_slice(&self.instances);
+        self.prepared.0 = self.buffer.write(encoder, device, data);
+        self.prepared.1.extend_from_slice(&self.runs);
+        self.clear();
+    }
+
+    fn draw<'r>(&'r self, pass: &mut Pass<'r>) {
+        let (offset, runs) = &self.prepared;
+        if runs.is_empty() {
+            return;
+        }
+
+        self.pipelines.check_target(pass.target());
+        pass.set_bind_group(1, &self.bind_group);
+        pass.set_vertex_buffer(0, &self.quad_vtx, 0);
+        pass.set_vertex_buffer(1, self.buffer.buffer(), *offset);
+        pass.set_index_buffer(&self.quad_idx);
+
+        let mut start = 0;
+        for &(end, pipeline) in runs {
+            pass.set_pipeline(self.pipelines.get(pipeline));
+            pass.draw_indexed(0..6, 0, start..end);
+            start = end;
+        }
+    }
+}
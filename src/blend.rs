@@ -1,6 +1,12 @@
+use std::convert::TryFrom;
 use wgpu::BlendFactor::{DstAlpha, One, SrcAlpha, Zero};
 use wgpu::BlendFactor::{DstColor, OneMinusSrcColor};
 use wgpu::BlendFactor::{OneMinusDstAlpha, OneMinusSrcAlpha};
+use wgpu::BlendOperation::{Add, Max, Min, ReverseSubtract, Subtract};
+
+pub mod advanced;
+
+pub use self::advanced::AdvancedBlend;
 
 pub const REPLACE: Blend = Blend {
     alpha: wgpu::BlendDescriptor::REPLACE,
@@ -32,6 +38,140 @@ pub const NPM_NORMAL: Blend = Blend::add_sep(SrcAlpha, OneMinusSrcAlpha, One, On
 pub const NPM_ADD: Blend = Blend::add_sep(SrcAlpha, One, One, One);
 pub const NPM_SCREEN: Blend = Blend::add_sep(SrcAlpha, OneMinusSrcColor, One, OneMinusSrcAlpha);
 
+/// `dst - src`, alpha is composited as `PMA_SRC_OVER`.
+pub const PMA_SUBTRACT: Blend =
+    Blend::op_sep(ReverseSubtract, One, One, Add, One, OneMinusSrcAlpha);
+/// `min(src, dst)`, exact for opaque sources only.
+pub const PMA_DARKEN: Blend = Blend::op_sep(Min, One, One, Add, One, OneMinusSrcAlpha);
+/// `max(src, dst)`, exact for opaque sources only.
+pub const PMA_LIGHTEN: Blend = Blend::op_sep(Max, One, One, Add, One, OneMinusSrcAlpha);
+
+pub const NPM_SUBTRACT: Blend =
+    Blend::op_sep(ReverseSubtract, SrcAlpha, One, Add, One, OneMinusSrcAlpha);
+/// `Min` and `Max` ignore the factors, so these match the PMA variants.
+pub const NPM_DARKEN: Blend = PMA_DARKEN;
+pub const NPM_LIGHTEN: Blend = PMA_LIGHTEN;
+
+/// Named blend mode, serialized in `snake_case`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    Replace,
+    Normal,
+    Add,
+    Multiply,
+    Screen,
+    Subtract,
+    Darken,
+    Lighten,
+
+    Src,
+    SrcAtop,
+    SrcOver,
+    SrcIn,
+    SrcOut,
+    Dst,
+    DstAtop,
+    DstOver,
+    DstIn,
+    DstOut,
+    Xor,
+    Clear,
+
+    /// Modes below need [`AdvancedBlend`].
+    Overlay,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        BlendMode::Normal
+    }
+}
+
+impl BlendMode {
+    /// Whether the mode can't be expressed with fixed-function blending.
+    pub fn is_advanced(self) -> bool {
+        match self {
+            BlendMode::Overlay
+            | BlendMode::ColorDodge
+            | BlendMode::ColorBurn
+            | BlendMode::HardLight
+            | BlendMode::SoftLight
+            | BlendMode::Difference
+            | BlendMode::Exclusion
+            | BlendMode::Hue
+            | BlendMode::Saturation
+            | BlendMode::Color
+            | BlendMode::Luminosity => true,
+            _ => false,
+        }
+    }
+
+    /// Blend for premultiplied alpha, `None` for advanced modes, which need
+    /// [`AdvancedBlend`].
+    pub fn pma(self) -> Option<Blend> {
+        Some(match self {
+            BlendMode::Replace => REPLACE,
+            BlendMode::Normal => PMA_NORMAL,
+            BlendMode::Add => PMA_ADD,
+            BlendMode::Multiply => PMA_MUL,
+            BlendMode::Screen => PMA_SCREEN,
+            BlendMode::Subtract => PMA_SUBTRACT,
+            BlendMode::Darken => PMA_DARKEN,
+            BlendMode::Lighten => PMA_LIGHTEN,
+
+            BlendMode::Src => PMA_SRC,
+            BlendMode::SrcAtop => PMA_SRC_ATOP,
+            BlendMode::SrcOver => PMA_SRC_OVER,
+            BlendMode::SrcIn => PMA_SRC_IN,
+            BlendMode::SrcOut => PMA_SRC_OUT,
+            BlendMode::Dst => PMA_DST,
+            BlendMode::DstAtop => PMA_DST_ATOP,
+            BlendMode::DstOver => PMA_DST_OVER,
+            BlendMode::DstIn => PMA_DST_IN,
+            BlendMode::DstOut => PMA_DST_OUT,
+            BlendMode::Xor => PMA_XOR,
+            BlendMode::Clear => PMA_CLEAR,
+
+            _ => return None,
+        })
+    }
+
+    /// Blend for non-premultiplied alpha, `None` if there is no fixed-function
+    /// equivalent.
+    pub fn npm(self) -> Option<Blend> {
+        Some(match self {
+            BlendMode::Replace => REPLACE,
+            BlendMode::Normal => NPM_NORMAL,
+            BlendMode::Add => NPM_ADD,
+            BlendMode::Screen => NPM_SCREEN,
+            BlendMode::Subtract => NPM_SUBTRACT,
+            BlendMode::Darken => NPM_DARKEN,
+            BlendMode::Lighten => NPM_LIGHTEN,
+            _ => return None,
+        })
+    }
+}
+
+/// Premultiplied alpha blend of a mode, fails with the mode if it's advanced.
+impl TryFrom<BlendMode> for Blend {
+    type Error = BlendMode;
+
+    fn try_from(mode: BlendMode) -> Result<Self, BlendMode> {
+        mode.pma().ok_or(mode)
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Blend {
     pub alpha: wgpu::BlendDescriptor,
@@ -39,28 +179,77 @@ pub struct Blend {
 }
 
 impl Blend {
-    const fn add(src: wgpu::BlendFactor, dst: wgpu::BlendFactor) -> Self {
-        Self::add_sep(src, dst, src, dst)
+    /// Separate color and alpha blending.
+    pub const fn sep(color: wgpu::BlendDescriptor, alpha: wgpu::BlendDescriptor) -> Self {
+        Self { alpha, color }
+    }
+
+    /// The same `operation` and factors for color and alpha.
+    pub const fn op(
+        operation: wgpu::BlendOperation,
+        src_factor: wgpu::BlendFactor,
+        dst_factor: wgpu::BlendFactor,
+    ) -> Self {
+        Self::op_sep(
+            operation, src_factor, dst_factor, operation, src_factor, dst_factor,
+        )
+    }
+
+    /// `src * src_factor + dst * dst_factor`
+    pub const fn add(src: wgpu::BlendFactor, dst: wgpu::BlendFactor) -> Self {
+        Self::op(Add, src, dst)
     }
 
-    const fn add_sep(
+    /// `src * src_factor - dst * dst_factor`
+    pub const fn subtract(src: wgpu::BlendFactor, dst: wgpu::BlendFactor) -> Self {
+        Self::op(Subtract, src, dst)
+    }
+
+    /// `dst * dst_factor - src * src_factor`
+    pub const fn reverse_subtract(src: wgpu::BlendFactor, dst: wgpu::BlendFactor) -> Self {
+        Self::op(ReverseSubtract, src, dst)
+    }
+
+    /// `min(src, dst)`, factors are ignored.
+    pub const fn min() -> Self {
+        Self::op(Min, One, One)
+    }
+
+    /// `max(src, dst)`, factors are ignored.
+    pub const fn max() -> Self {
+        Self::op(Max, One, One)
+    }
+
+    /// Separate operations and factors for color and alpha.
+    pub const fn op_sep(
+        color_op: wgpu::BlendOperation,
         color_src: wgpu::BlendFactor,
         color_dst: wgpu::BlendFactor,
+        alpha_op: wgpu::BlendOperation,
         alpha_src: wgpu::BlendFactor,
         alpha_dst: wgpu::BlendFactor,
     ) -> Self {
-        Self {
-            alpha: wgpu::BlendDescriptor {
-                src_factor: alpha_src,
-                dst_factor: alpha_dst,
-                operation: wgpu::BlendOperation::Add,
-            },
-            color: wgpu::BlendDescriptor {
+        Self::sep(
+            wgpu::BlendDescriptor {
                 src_factor: color_src,
                 dst_factor: color_dst,
-                operation: wgpu::BlendOperation::Add,
+                operation: color_op,
             },
-        }
+            wgpu::BlendDescriptor {
+                src_factor: alpha_src,
+                dst_factor: alpha_dst,
+                operation: alpha_op,
+            },
+        )
+    }
+
+    pub const fn add_sep(
+        color_src: wgpu::BlendFactor,
+        color_dst: wgpu::BlendFactor,
+        alpha_src: wgpu::BlendFactor,
+        alpha_dst: wgpu::BlendFactor,
+    ) -> Self {
+        Self::op_sep(Add, color_src, color_dst, Add, alpha_src, alpha_dst)
     }
 
     pub const fn into_color_state(self, format: wgpu::TextureFormat) -> wgpu::ColorStateDescriptor {
@@ -72,3 +261,39 @@ impl Blend {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_names() {
+        let mode: BlendMode = serde_json::from_str("\"src_atop\"").unwrap();
+        assert_eq!(mode, BlendMode::SrcAtop);
+        assert_eq!(
+            serde_json::to_string(&BlendMode::Darken).unwrap(),
+            "\"darken\""
+        );
+        assert_eq!(Blend::try_from(BlendMode::Lighten), Ok(PMA_LIGHTEN));
+        assert_eq!(Blend::try_from(BlendMode::Overlay), Err(BlendMode::Overlay));
+        assert_eq!(BlendMode::Multiply.npm(), None);
+        assert!(BlendMode::SoftLight.is_advanced());
+        assert!(!BlendMode::Clear.is_advanced());
+    }
+
+    #[test]
+    fn only_advanced_modes_lack_a_pma_blend() {
+        use BlendMode::*;
+
+        #[rustfmt::skip]
+        let modes = [
+            Replace, Normal, Add, Multiply, Screen, Subtract, Darken, Lighten,
+            Src, SrcAtop, SrcOver, SrcIn, SrcOut, Dst, DstAtop, DstOver, DstIn, DstOut, Xor, Clear,
+            Overlay, ColorDodge, ColorBurn, HardLight, SoftLight, Difference, Exclusion,
+            Hue, Saturation, Color, Luminosity,
+        ];
+        for &mode in &modes {
+            assert_eq!(mode.pma().is_none(), mode.is_advanced(), "{:?}", mode);
+        }
+    }
+}
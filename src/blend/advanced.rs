@@ -0,0 +1,501 @@
+use super::{Blend, BlendMode};
+use crate::{
+    cast_slice,
+    layout::{Layout, PipelineKey, ShaderId, Vertex},
+    math::Frame,
+    target::{Projection, RenderTarget},
+    utils::{write_buffer, RingBuffer},
+};
+use std::sync::Arc;
+
+#[repr(C)]
+struct Params {
+    backdrop: [f32; 4],
+    mode: [i32; 4],
+}
+
+/// How [`AdvancedBlend`] draws a mode.
+enum Method {
+    /// Index of the W3C blend function in `advanced.frag`.
+    Shader(i32),
+    FixedFunction(Blend),
+}
+
+impl Method {
+    fn of(mode: BlendMode) -> Self {
+        use Method::{FixedFunction, Shader};
+
+        match mode {
+            BlendMode::Normal => Shader(0),
+            BlendMode::Multiply => Shader(1),
+            BlendMode::Screen => Shader(2),
+            BlendMode::Overlay => Shader(3),
+            BlendMode::Darken => Shader(4),
+            BlendMode::Lighten => Shader(5),
+            BlendMode::ColorDodge => Shader(6),
+            BlendMode::ColorBurn => Shader(7),
+            BlendMode::HardLight => Shader(8),
+            BlendMode::SoftLight => Shader(9),
+            BlendMode::Difference => Shader(10),
+            BlendMode::Exclusion => Shader(11),
+            BlendMode::Hue => Shader(12),
+            BlendMode::Saturation => Shader(13),
+            BlendMode::Color => Shader(14),
+            BlendMode::Luminosity => Shader(15),
+
+            BlendMode::Replace => FixedFunction(super::REPLACE),
+            BlendMode::Add => FixedFunction(super::PMA_ADD),
+            BlendMode::Subtract => FixedFunction(super::PMA_SUBTRACT),
+            BlendMode::Src => FixedFunction(super::PMA_SRC),
+            BlendMode::SrcAtop => FixedFunction(super::PMA_SRC_ATOP),
+            BlendMode::SrcOver => FixedFunction(super::PMA_SRC_OVER),
+            BlendMode::SrcIn => FixedFunction(super::PMA_SRC_IN),
+            BlendMode::SrcOut => FixedFunction(super::PMA_SRC_OUT),
+            BlendMode::Dst => FixedFunction(super::PMA_DST),
+            BlendMode::DstAtop => FixedFunction(super::PMA_DST_ATOP),
+            BlendMode::DstOver => FixedFunction(super::PMA_DST_OVER),
+            BlendMode::DstIn => FixedFunction(super::PMA_DST_IN),
+            BlendMode::DstOut => FixedFunction(super::PMA_DST_OUT),
+            BlendMode::Xor => FixedFunction(super::PMA_XOR),
+            BlendMode::Clear => FixedFunction(super::PMA_CLEAR),
+        }
+    }
+}
+
+// https://www.w3.org/TR/compositing-1/#blending, like `advanced.frag`
+
+type Rgb = [f32; 3];
+
+fn map(c: Rgb, f: impl Fn(f32) -> f32) -> Rgb {
+    [f(c[0]), f(c[1]), f(c[2])]
+}
+
+fn zip(a: Rgb, b: Rgb, f: impl Fn(f32, f32) -> f32) -> Rgb {
+    [f(a[0], b[0]), f(a[1], b[1]), f(a[2], b[2])]
+}
+
+fn color_dodge(cb: f32, cs: f32) -> f32 {
+    if cb == 0.0 {
+        0.0
+    } else if cs == 1.0 {
+        1.0
+    } else {
+        (cb / (1.0 - cs)).min(1.0)
+    }
+}
+
+fn color_burn(cb: f32, cs: f32) -> f32 {
+    if cb == 1.0 {
+        1.0
+    } else if cs == 0.0 {
+        0.0
+    } else {
+        1.0 - ((1.0 - cb) / cs).min(1.0)
+    }
+}
+
+fn hard_light(cb: f32, cs: f32) -> f32 {
+    if cs <= 0.5 {
+        cb * 2.0 * cs
+    } else {
+        let s = 2.0 * cs - 1.0;
+        cb + s - cb * s
+    }
+}
+
+fn soft_light(cb: f32, cs: f32) -> f32 {
+    if cs <= 0.5 {
+        cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+    } else {
+        let d = if cb <= 0.25 {
+            ((16.0 * cb - 12.0) * cb + 4.0) * cb
+        } else {
+            cb.sqrt()
+        };
+        cb + (2.0 * cs - 1.0) * (d - cb)
+    }
+}
+
+fn lum([r, g, b]: Rgb) -> f32 {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+
+fn clip_color(c: Rgb) -> Rgb {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    let mut c = c;
+    if n < 0.0 {
+        c = map(c, |c| l + (c - l) * l / (l - n));
+    }
+    if x > 1.0 {
+        c = map(c, |c| l + (c - l) * (1.0 - l) / (x - l));
+    }
+    c
+}
+
+fn set_lum(c: Rgb, l: f32) -> Rgb {
+    let d = l - lum(c);
+    clip_color(map(c, |c| c + d))
+}
+
+fn sat(c: Rgb) -> f32 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+fn set_sat(c: Rgb, s: f32) -> Rgb {
+    let c_min = c[0].min(c[1]).min(c[2]);
+    let c_max = c[0].max(c[1]).max(c[2]);
+    if c_max > c_min {
+        map(c, |c| (c - c_min) * s / (c_max - c_min))
+    } else {
+        [0.0; 3]
+    }
+}
+
+/// Blend function `shader_mode` of `advanced.frag` on straight colors.
+fn mix(shader_mode: i32, cb: Rgb, cs: Rgb) -> Rgb {
+    match shader_mode {
+        1 => zip(cb, cs, |b, s| b * s),
+        2 => zip(cb, cs, |b, s| b + s - b * s),
+        3 => zip(cs, cb, hard_light),
+        4 => zip(cb, cs, f32::min),
+        5 => zip(cb, cs, f32::max),
+        6 => zip(cb, cs, color_dodge),
+        7 => zip(cb, cs, color_burn),
+        8 => zip(cb, cs, hard_light),
+        9 => zip(cb, cs, soft_light),
+        10 => zip(cb, cs, |b, s| (b - s).abs()),
+        11 => zip(cb, cs, |b, s| b + s - 2.0 * b * s),
+        12 => set_lum(set_sat(cs, sat(cb)), lum(cb)),
+        13 => set_lum(set_sat(cb, sat(cs)), lum(cb)),
+        14 => set_lum(cs, lum(cb)),
+        15 => set_lum(cb, lum(cs)),
+        _ => cs,
+    }
+}
+
+/// Composites premultiplied textures with any [`BlendMode`], including the
+/// separable and non-separable modes of the W3C compositing spec.
+///
+/// The backdrop under the drawn area is copied into an offscreen target first,
+/// so the destination has to be a single sampled [`RenderTarget`]. Modes without a W3C blend
+/// function (e.g. [`BlendMode::Add`]) are drawn with fixed-function blending.
+pub struct AdvancedBlend {
+    format: wgpu::TextureFormat,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    params: wgpu::Buffer,
+    params_bind_group: wgpu::BindGroup,
+
+    sampler: wgpu::Sampler,
+    backdrop: Option<RenderTarget>,
+
+    quad_vtx: RingBuffer,
+    quad_idx: wgpu::Buffer,
+    projection: Projection,
+}
+
+impl AdvancedBlend {
+    pub fn new(device: &wgpu::Device, layout: &Layout, format: wgpu::TextureFormat) -> Self {
+        let key = PipelineKey::new(ShaderId::Advanced, super::REPLACE, format);
+        let pipeline = layout.cached_pipeline(device, key);
+
+        let size = std::mem::size_of::<Params>() as wgpu::BufferAddress;
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("advanced blend params"),
+            size,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let params_bind_group = layout.filter.bind_uniforms(device, &params, size);
+
+        let idx: [u16; 6] = [0, 1, 2, 0, 2, 3];
+        let quad_idx = device.create_buffer_with_data(cast_slice(&idx), wgpu::BufferUsage::INDEX);
+        let usage = wgpu::BufferUsage::VERTEX;
+        let size = std::mem::size_of::<[Vertex; 4]>() as wgpu::BufferAddress;
+        let quad_vtx = RingBuffer::new(
+            device,
+            &layout.staging,
+            "advanced blend quad",
+            usage,
+            size * 16,
+        );
+
+        Self {
+            format,
+            pipeline,
+            params,
+            params_bind_group,
+
+            sampler: crate::nearest_sampler(device),
+            backdrop: None,
+
+            quad_vtx,
+            quad_idx,
+            projection: Projection::new(device, layout),
+        }
+    }
+
+    /// Draws `source` stretched over the logical `frame` of `dst`.
+    ///
+    /// `source` is a bind group of `layout.image`, e.g. [`RenderTarget::bind_group`].
+    /// `layout` must be the one the blender was created with.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        layout: &Layout,
+        source: &wgpu::BindGroup,
+        frame: Frame,
+        mode: BlendMode,
+        dst: &RenderTarget,
+        scale: f32,
+    ) {
+        assert_eq!(
+            dst.sample_count, 1,
+            "advanced blending needs a single sampled target"
+        );
+
+        // physical area of `dst` under the quad
+        let [min_x, min_y] = frame.min();
+        let [max_x, max_y] = frame.max();
+        let clamp = |v: f32, max: u32| v.max(0.0).min(max as f32) as u32;
+        let x0 = clamp((min_x * scale).floor(), dst.width);
+        let y0 = clamp((min_y * scale).floor(), dst.height);
+        let x1 = clamp((max_x * scale).ceil(), dst.width);
+        let y1 = clamp((max_y * scale).ceil(), dst.height);
+        if x0 >= x1 || y0 >= y1 {
+            return;
+        }
+
+        let vtx = [
+            Vertex::new(max_x, max_y, 1.0, 1.0), // 11
+            Vertex::new(max_x, min_y, 1.0, 0.0), // 10
+            Vertex::new(min_x, min_y, 0.0, 0.0), // 00
+            Vertex::new(min_x, max_y, 0.0, 1.0), // 01
+        ];
+        let offset = self.quad_vtx.write(encoder, device, cast_slice(&vtx));
+
+        let shader_mode = match Method::of(mode) {
+            Method::Shader(shader_mode) => shader_mode,
+            Method::FixedFunction(blend) => {
+                let key = PipelineKey::new(ShaderId::Sprite, blend, self.format);
+                let pipeline = layout.cached_pipeline(device, key);
+                let proj_bind_group = self.projection.update(encoder, device, &dst.target(scale));
+
+                let mut rpass = dst.load_pass(encoder);
+                rpass.set_pipeline(&pipeline);
+                rpass.set_vertex_buffer(0, self.quad_vtx.buffer(), offset, 0);
+                rpass.set_index_buffer(&self.quad_idx, 0, 0);
+                rpass.set_bind_group(0, proj_bind_group, &[]);
+                rpass.set_bind_group(1, source, &[]);
+                rpass.draw_indexed(0..6, 0, 0..1);
+                return;
+            }
+        };
+
+        let (width, height) = (x1 - x0, y1 - y0);
+        self.reserve_backdrop(device, layout, width, height);
+
+        let Self {
+            pipeline,
+            params,
+            params_bind_group,
+            backdrop,
+            quad_vtx,
+            quad_idx,
+            projection,
+            ..
+        } = self;
+        let backdrop = backdrop.as_ref().unwrap();
+
+        let data = [Params {
+            backdrop: [
+                x0 as f32,
+                y0 as f32,
+                (backdrop.width as f32).recip(),
+                (backdrop.height as f32).recip(),
+            ],
+            mode: [shader_mode, 0, 0, 0],
+        }];
+        write_buffer(encoder, device, params, 0, cast_slice(&data));
+
+        encoder.copy_texture_to_texture(
+            wgpu::TextureCopyView {
+                texture: &dst.texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d { x: x0, y: y0, z: 0 },
+            },
+            wgpu::TextureCopyView {
+                texture: &backdrop.texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d { x: 0, y: 0, z: 0 },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth: 1,
+            },
+        );
+
+        let proj_bind_group = projection.update(encoder, device, &dst.target(scale));
+
+        let mut rpass = dst.load_pass(encoder);
+        rpass.set_pipeline(&pipeline);
+        rpass.set_vertex_buffer(0, quad_vtx.buffer(), offset, 0);
+        rpass.set_index_buffer(quad_idx, 0, 0);
+        rpass.set_bind_group(0, proj_bind_group, &[]);
+        rpass.set_bind_group(1, source, &[]);
+        rpass.set_bind_group(2, &backdrop.bind_group, &[]);
+        rpass.set_bind_group(3, params_bind_group, &[]);
+        rpass.draw_indexed(0..6, 0, 0..1);
+    }
+
+    /// CPU equivalent of [`AdvancedBlend::draw`] for one premultiplied source
+    /// color over a premultiplied backdrop.
+    pub fn reference(mode: BlendMode, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+        let shader_mode = match Method::of(mode) {
+            Method::Shader(shader_mode) => shader_mode,
+            Method::FixedFunction(blend) => return crate::soft::blend(&blend, src, dst),
+        };
+
+        let unpremul = |c: [f32; 4]| {
+            let k = if c[3] > 0.0 { c[3].recip() } else { 0.0 };
+            [c[0] * k, c[1] * k, c[2] * k]
+        };
+        let (cs, cb) = (unpremul(src), unpremul(dst));
+        let (a_s, a_b) = (src[3], dst[3]);
+
+        let mixed = map(mix(shader_mode, cb, cs), |c| {
+            a_s * a_b * c.max(0.0).min(1.0)
+        });
+        let color = |i: usize| src[i] * (1.0 - a_b) + dst[i] * (1.0 - a_s) + mixed[i];
+        [color(0), color(1), color(2), a_s + a_b * (1.0 - a_s)]
+    }
+
+    /// Offscreen copy of the backdrop, grown to at least `width` by `height`.
+    fn reserve_backdrop(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &Layout,
+        width: u32,
+        height: u32,
+    ) {
+        let (old_width, old_height) = self
+            .backdrop
+            .as_ref()
+            .map_or((0, 0), |t| (t.width, t.height));
+
+        if width > old_width || height > old_height {
+            let (width, height) = (width.max(old_width), height.max(old_height));
+            let (sampler, format) = (&self.sampler, self.format);
+            let target = RenderTarget::new(device, layout, sampler, format, width, height, 1);
+            self.backdrop = Some(target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opaque(mode: BlendMode, cs: Rgb, cb: Rgb) -> Rgb {
+        let [r, g, b, a] =
+            AdvancedBlend::reference(mode, [cs[0], cs[1], cs[2], 1.0], [cb[0], cb[1], cb[2], 1.0]);
+        assert_eq!(a, 1.0);
+        [r, g, b]
+    }
+
+    fn assert_rgb(actual: Rgb, expected: Rgb) {
+        let close = actual
+            .iter()
+            .zip(&expected)
+            .all(|(a, e)| (a - e).abs() < 1e-5);
+        assert!(close, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn separable_modes() {
+        let (cs, cb) = ([0.5, 0.75, 0.0], [0.5, 0.25, 1.0]);
+        assert_rgb(opaque(BlendMode::Normal, cs, cb), cs);
+        assert_rgb(opaque(BlendMode::Multiply, cs, cb), [0.25, 0.1875, 0.0]);
+        assert_rgb(opaque(BlendMode::Screen, cs, cb), [0.75, 0.8125, 1.0]);
+        // hard light with the layers swapped
+        assert_rgb(opaque(BlendMode::Overlay, cs, cb), [0.5, 0.375, 1.0]);
+        assert_rgb(opaque(BlendMode::HardLight, cs, cb), [0.5, 0.625, 0.0]);
+        assert_rgb(opaque(BlendMode::Darken, cs, cb), [0.5, 0.25, 0.0]);
+        assert_rgb(opaque(BlendMode::Lighten, cs, cb), [0.5, 0.75, 1.0]);
+        assert_rgb(opaque(BlendMode::Difference, cs, cb), [0.0, 0.5, 1.0]);
+        assert_rgb(opaque(BlendMode::Exclusion, cs, cb), [0.5, 0.625, 1.0]);
+    }
+
+    #[test]
+    fn dodge_and_burn_edge_cases() {
+        // black backdrop stays black, a white source saturates
+        assert_rgb(
+            opaque(BlendMode::ColorDodge, [1.0, 0.5, 0.5], [0.0, 0.25, 0.75]),
+            [0.0, 0.5, 1.0],
+        );
+        // white backdrop stays white, a black source clears
+        assert_rgb(
+            opaque(BlendMode::ColorBurn, [0.0, 0.5, 0.5], [1.0, 0.75, 0.25]),
+            [1.0, 0.5, 0.0],
+        );
+    }
+
+    #[test]
+    fn soft_light() {
+        let cs = [0.25, 0.75, 0.75];
+        let cb = [0.5, 0.16, 0.64];
+        let d = ((16.0 * 0.16 - 12.0) * 0.16 + 4.0) * 0.16;
+        let expected = [0.375, 0.16 + 0.5 * (d - 0.16), 0.64 + 0.5 * (0.8 - 0.64)];
+        assert_rgb(opaque(BlendMode::SoftLight, cs, cb), expected);
+    }
+
+    #[test]
+    fn non_separable_modes() {
+        let (red, gray) = ([1.0, 0.0, 0.0], [0.5, 0.5, 0.5]);
+        // red at the luminosity of gray is clipped back into range
+        let clipped = 0.5 - 0.3 * 0.5 / 0.7;
+        assert_rgb(opaque(BlendMode::Color, red, gray), [1.0, clipped, clipped]);
+        // gray has no saturation to give red a hue
+        assert_rgb(opaque(BlendMode::Hue, red, gray), gray);
+
+        let pink = [0.8, 0.4, 0.4];
+        let lum = 0.3 * 0.8 + 0.59 * 0.4 + 0.11 * 0.4;
+        assert_rgb(opaque(BlendMode::Saturation, gray, pink), [lum; 3]);
+        let dark = [0.2; 3];
+        assert_rgb(
+            opaque(BlendMode::Luminosity, dark, pink),
+            [0.48, 0.08, 0.08],
+        );
+    }
+
+    #[test]
+    fn compositing() {
+        let src = [0.25, 0.0, 0.0, 0.5];
+        let dst = [0.0, 0.0, 0.5, 0.5];
+        let transparent = [0.0; 4];
+
+        for &mode in &[BlendMode::Multiply, BlendMode::Overlay, BlendMode::Hue] {
+            assert_eq!(AdvancedBlend::reference(mode, src, transparent), src);
+            assert_eq!(AdvancedBlend::reference(mode, transparent, dst), dst);
+        }
+        let normal = AdvancedBlend::reference(BlendMode::Normal, src, dst);
+        assert_eq!(normal, crate::filter::reference::over(src, dst));
+
+        // multiply of straight (0.5, 0, 0) and (0, 0, 1) is black
+        let multiply = AdvancedBlend::reference(BlendMode::Multiply, src, dst);
+        assert_eq!(multiply, [0.125, 0.0, 0.25, 0.75]);
+    }
+
+    #[test]
+    fn fixed_function_modes() {
+        let src = [0.25, 0.5, 0.0, 0.5];
+        let dst = [0.5, 0.5, 0.5, 1.0];
+        let add = AdvancedBlend::reference(BlendMode::Add, src, dst);
+        assert_eq!(add, crate::soft::blend(&crate::blend::PMA_ADD, src, dst));
+    }
+}
@@ -0,0 +1,48 @@
+/// Adapter, device and queue.
+pub struct Context {
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl Context {
+    /// Context without a surface for offscreen rendering into
+    /// [`RenderTarget`](crate::target::RenderTarget)s, e.g. in tools and tests.
+    ///
+    /// Returns `None` if there is no suitable adapter.
+    pub fn headless() -> Option<Self> {
+        futures::executor::block_on(Self::request(
+            wgpu::PowerPreference::Default,
+            wgpu::BackendBit::PRIMARY,
+            &wgpu::DeviceDescriptor::default(),
+            None,
+        ))
+    }
+
+    /// Requests a device from an adapter able to present to `compatible_surface`,
+    /// if any.
+    pub async fn request(
+        power_preference: wgpu::PowerPreference,
+        backends: wgpu::BackendBit,
+        desc: &wgpu::DeviceDescriptor,
+        compatible_surface: Option<&wgpu::Surface>,
+    ) -> Option<Self> {
+        let options = wgpu::RequestAdapterOptions {
+            power_preference,
+            compatible_surface,
+        };
+
+        let adapter = wgpu::Adapter::request(&options, backends).await?;
+        let (device, queue) = adapter.request_device(desc).await;
+
+        Some(Self {
+            adapter,
+            device,
+            queue,
+        })
+    }
+
+    pub fn submit(&self, encoder: wgpu::CommandEncoder) {
+        self.queue.submit(&[encoder.finish()]);
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+
+/// Errors of loading assets and setting up rendering.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Json(serde_json::Error),
+    /// Malformed or unsupported file.
+    Format(String),
+    /// No adapter is compatible with the requested options.
+    NoAdapter,
+    /// Acquiring the next swap chain texture timed out.
+    Timeout,
+    /// Mapping a buffer for reading failed.
+    BufferMap,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::Image(err) => write!(f, "image error: {}", err),
+            Error::Json(err) => write!(f, "json error: {}", err),
+            Error::Format(message) => write!(f, "{}", message),
+            Error::NoAdapter => write!(f, "no suitable adapter found"),
+            Error::Timeout => write!(f, "timeout when acquiring next swap chain texture"),
+            Error::BufferMap => write!(f, "failed to map buffer"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Image(err) => Some(err),
+            Error::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Self {
+        Error::Image(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<wgpu::TimeOut> for Error {
+    fn from(_: wgpu::TimeOut) -> Self {
+        Error::Timeout
+    }
+}
+
+impl From<wgpu::BufferAsyncErr> for Error {
+    fn from(_: wgpu::BufferAsyncErr) -> Self {
+        Error::BufferMap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn source_chain() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "sheet.json");
+        let err = Error::from(io);
+        assert_eq!(err.to_string(), "io error: sheet.json");
+        assert_eq!(err.source().unwrap().to_string(), "sheet.json");
+        assert!(Error::NoAdapter.source().is_none());
+    }
+}
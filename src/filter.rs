@@ -0,0 +1,268 @@
+use crate::{
+    blend::{self, Blend},
+    target::RenderTarget,
+};
+use std::sync::Arc;
+
+pub mod blur;
+pub mod color_matrix;
+pub mod displacement;
+pub mod lut;
+pub mod reference;
+pub mod retro;
+pub mod shadow;
+pub mod system;
+
+pub use self::{
+    blur::BlurFilter,
+    color_matrix::{ColorMatrix, ColorMatrixFilter},
+    displacement::DisplacementFilter,
+    lut::{Lut, LutFilter},
+    retro::{CrtFilter, PixelateFilter},
+    shadow::{DropShadowFilter, GlowFilter},
+    system::{FilterState, FilterSystem},
+};
+
+/// A post-processing step applied to a rendered subtree.
+pub trait Filter {
+    /// Extra space around the filtered bounds, in logical pixels.
+    fn padding(&self) -> f32 {
+        0.0
+    }
+
+    /// Blend mode used to composite the result of the last filter back.
+    fn blend(&self) -> Blend {
+        blend::PMA_NORMAL
+    }
+
+    /// Renders `input` into `output`.
+    ///
+    /// Both targets have the same size and `output` must be cleared by the filter.
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    );
+}
+
+/// Layout shared by all filter pipelines, see [`Layout::filter`](crate::layout::Layout::filter).
+///
+/// - set 0: `FilterGlobals` uniform (input size, output frame)
+/// - set 1: input texture and sampler
+/// - set 2: filter uniforms
+/// - set 3: additional texture (e.g. displacement map or 3D lookup table)
+pub struct FilterLayout {
+    pub globals: wgpu::BindGroupLayout,
+    pub uniforms: wgpu::BindGroupLayout,
+    /// 3D texture and sampler of a [`LutFilter`].
+    pub lut_texture: wgpu::BindGroupLayout,
+
+    pub copy: wgpu::PipelineLayout,
+    pub pipeline: wgpu::PipelineLayout,
+    pub map: wgpu::PipelineLayout,
+    pub lut: wgpu::PipelineLayout,
+}
+
+impl FilterLayout {
+    /// `image` is the texture and sampler layout of the input, [`Layout::image`](crate::layout::Layout::image).
+    pub fn new(device: &wgpu::Device, image: &wgpu::BindGroupLayout) -> Self {
+        let globals = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("filter globals"),
+            bindings: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            }],
+        });
+
+        let uniforms = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("filter uniforms"),
+            bindings: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+            }],
+        });
+
+        let lut_texture = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("lut"),
+            bindings: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::SampledTexture {
+                        dimension: wgpu::TextureViewDimension::D3,
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                },
+            ],
+        });
+
+        let copy = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&globals, image],
+        });
+        let pipeline = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&globals, image, &uniforms],
+        });
+        let map = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&globals, image, &uniforms, image],
+        });
+        let lut = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&globals, image, &uniforms, &lut_texture],
+        });
+
+        Self {
+            globals,
+            uniforms,
+            lut_texture,
+
+            copy,
+            pipeline,
+            map,
+            lut,
+        }
+    }
+
+    pub fn bind_uniforms(
+        &self,
+        device: &wgpu::Device,
+        buffer: &wgpu::Buffer,
+        size: wgpu::BufferAddress,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("filter uniforms bind group"),
+            layout: &self.uniforms,
+            bindings: &[wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer,
+                    range: 0..size,
+                },
+            }],
+        })
+    }
+}
+
+/// Fragment shader of a filter pass, see [`ShaderId::Filter`](crate::layout::ShaderId::Filter).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FilterShader {
+    /// Copy of the input, blended into the output.
+    Copy,
+    Blur,
+    Displacement,
+    Lut,
+    /// Tint pass of [`DropShadowFilter`] and [`GlowFilter`].
+    Shadow,
+    /// SPIR-V of a [`ShaderFilter`], with a single uniform block at set 2.
+    Custom(&'static [u8]),
+}
+
+impl FilterShader {
+    pub(crate) fn spirv(self) -> &'static [u8] {
+        match self {
+            FilterShader::Copy => include_bytes!("shaders/compiled/copy.frag.spv"),
+            FilterShader::Blur => include_bytes!("shaders/compiled/blur.frag.spv"),
+            FilterShader::Displacement => include_bytes!("shaders/compiled/displacement.frag.spv"),
+            FilterShader::Lut => include_bytes!("shaders/compiled/lut.frag.spv"),
+            FilterShader::Shadow => include_bytes!("shaders/compiled/shadow.frag.spv"),
+            FilterShader::Custom(spirv) => spirv,
+        }
+    }
+
+    pub(crate) fn pipeline_layout(self, layout: &FilterLayout) -> &wgpu::PipelineLayout {
+        match self {
+            FilterShader::Copy => &layout.copy,
+            FilterShader::Blur | FilterShader::Shadow | FilterShader::Custom(_) => &layout.pipeline,
+            FilterShader::Displacement => &layout.map,
+            FilterShader::Lut => &layout.lut,
+        }
+    }
+}
+
+/// Filter with a custom fragment shader and a single uniform block at set 2.
+pub struct ShaderFilter {
+    pub pipeline: Arc<wgpu::RenderPipeline>,
+    pub uniforms: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub padding: f32,
+    pub blend: Blend,
+}
+
+impl ShaderFilter {
+    /// The pipeline is shared by all filters of `system` with the same `spirv`.
+    pub fn new<T>(
+        device: &wgpu::Device,
+        system: &FilterSystem,
+        spirv: &'static [u8],
+        uniforms: &[T],
+    ) -> Self {
+        let pipeline = system.pipeline(device, FilterShader::Custom(spirv));
+        let layout = &system.layout.filter;
+
+        let data = crate::cast_slice(uniforms);
+        let usage = wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST;
+        let buffer = device.create_buffer_with_data(data, usage);
+        let bind_group = layout.bind_uniforms(device, &buffer, data.len() as wgpu::BufferAddress);
+
+        Self {
+            pipeline,
+            uniforms: buffer,
+            bind_group,
+            padding: 0.0,
+            blend: blend::PMA_NORMAL,
+        }
+    }
+
+    /// Replaces the uniform block. `uniforms` must have the same size as on creation.
+    pub fn set_uniforms<T>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        uniforms: &[T],
+    ) {
+        crate::utils::write_buffer(
+            encoder,
+            device,
+            &self.uniforms,
+            0,
+            crate::cast_slice(uniforms),
+        );
+    }
+}
+
+impl Filter for ShaderFilter {
+    fn padding(&self) -> f32 {
+        self.padding
+    }
+
+    fn blend(&self) -> Blend {
+        self.blend.clone()
+    }
+
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        system.apply_filter(
+            encoder,
+            device,
+            &self.pipeline,
+            &[&self.bind_group],
+            input,
+            output,
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use super::{reference::Pixels, Filter, FilterShader, FilterSystem};
+use crate::{blend, target::RenderTarget};
+use std::sync::Arc;
+
+/// Largest supported kernel size.
+pub const MAX_KERNEL_SIZE: usize = 15;
+
+/// Normalized half of a gaussian kernel, starting from the center tap.
+///
+/// `size` is clamped to odd values in `3..=MAX_KERNEL_SIZE`.
+pub fn gaussian_kernel(size: usize) -> Vec<f32> {
+    let half = kernel_half(size);
+    let sigma = half as f32 * 0.5 + 1.0;
+
+    let mut weights: Vec<f32> = (0..=half)
+        .map(|i| (-((i * i) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+
+    let sum = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+    for w in &mut weights {
+        *w /= sum;
+    }
+    weights
+}
+
+fn kernel_half(size: usize) -> usize {
+    size.max(3).min(MAX_KERNEL_SIZE) / 2
+}
+
+/// One directional blur pass over `input`; `[dx, dy]` is the tap spacing in pixels.
+pub fn blur_pass(input: &Pixels, weights: &[f32], [dx, dy]: [f32; 2]) -> Pixels {
+    let mut output = Pixels::new(input.width, input.height);
+    for y in 0..input.height {
+        for x in 0..input.width {
+            let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+
+            let center = input.sample(cx, cy);
+            let mut color = [0.0; 4];
+            for c in 0..4 {
+                color[c] = center[c] * weights[0];
+            }
+
+            for (i, &weight) in weights.iter().enumerate().skip(1) {
+                let (ox, oy) = (dx * i as f32, dy * i as f32);
+                let a = input.sample(cx + ox, cy + oy);
+                let b = input.sample(cx - ox, cy - oy);
+                for c in 0..4 {
+                    color[c] += (a[c] + b[c]) * weight;
+                }
+            }
+
+            output.set(x, y, color);
+        }
+    }
+    output
+}
+
+/// Two-pass separable gaussian blur.
+pub struct BlurFilter {
+    /// Blur radius in logical pixels.
+    pub strength: f32,
+    /// Number of horizontal + vertical pass pairs.
+    pub quality: u32,
+    /// Number of taps per pass, odd in `3..=15`.
+    pub kernel_size: usize,
+
+    pipeline: Arc<wgpu::RenderPipeline>,
+    horizontal: (wgpu::Buffer, wgpu::BindGroup),
+    vertical: (wgpu::Buffer, wgpu::BindGroup),
+}
+
+impl BlurFilter {
+    const UNIFORMS_SIZE: wgpu::BufferAddress = 4 * 12;
+
+    pub fn new(device: &wgpu::Device, system: &FilterSystem, strength: f32) -> Self {
+        let layout = &system.layout.filter;
+
+        let pipeline = system.pipeline(device, FilterShader::Blur);
+
+        let uniforms = || {
+            let usage = wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST;
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("blur uniforms"),
+                size: Self::UNIFORMS_SIZE,
+                usage,
+            });
+            let bind_group = layout.bind_uniforms(device, &buffer, Self::UNIFORMS_SIZE);
+            (buffer, bind_group)
+        };
+
+        Self {
+            strength,
+            quality: 4,
+            kernel_size: 5,
+
+            pipeline,
+            horizontal: uniforms(),
+            vertical: uniforms(),
+        }
+    }
+
+    /// Tap spacing in physical pixels for one pass.
+    fn spacing(&self, scale: f32) -> f32 {
+        self.strength * scale / self.quality.max(1) as f32
+    }
+
+    /// CPU equivalent of [`Filter::apply`] at given resolution.
+    pub fn reference(&self, input: &Pixels, scale: f32) -> Pixels {
+        if self.strength == 0.0 {
+            return input.clone();
+        }
+
+        let weights = gaussian_kernel(self.kernel_size);
+        let spacing = self.spacing(scale);
+
+        let mut pixels = input.clone();
+        for _ in 0..self.quality.max(1) {
+            pixels = blur_pass(&pixels, &weights, [spacing, 0.0]);
+            pixels = blur_pass(&pixels, &weights, [0.0, spacing]);
+        }
+        pixels
+    }
+
+    fn write_uniforms(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        buffer: &wgpu::Buffer,
+        [dx, dy]: [f32; 2],
+    ) {
+        let kernel = gaussian_kernel(self.kernel_size);
+        let mut weights = [0.0; 8];
+        weights[..kernel.len()].copy_from_slice(&kernel);
+
+        let half = (kernel.len() - 1) as f32;
+        let [w0, w1, w2, w3, w4, w5, w6, w7] = weights;
+        let data = [dx, dy, half, 0.0, w0, w1, w2, w3, w4, w5, w6, w7];
+
+        crate::utils::write_buffer(encoder, device, buffer, 0, crate::cast_slice(&data));
+    }
+}
+
+impl Filter for BlurFilter {
+    fn padding(&self) -> f32 {
+        self.strength.abs() * 2.0
+    }
+
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        if self.strength == 0.0 {
+            system.copy(encoder, device, input, output, blend::REPLACE, true);
+            return;
+        }
+
+        let spacing = self.spacing(system.scale());
+        let (width, height) = (input.width as f32, input.height as f32);
+
+        let (h_buffer, h_bind_group) = &self.horizontal;
+        let (v_buffer, v_bind_group) = &self.vertical;
+        self.write_uniforms(encoder, device, h_buffer, [spacing / width, 0.0]);
+        self.write_uniforms(encoder, device, v_buffer, [0.0, spacing / height]);
+
+        let pipeline = &self.pipeline;
+        let tmp = system.acquire(device, input.width, input.height);
+
+        system.apply_filter(encoder, device, pipeline, &[h_bind_group], input, &tmp);
+        system.apply_filter(encoder, device, pipeline, &[v_bind_group], &tmp, output);
+        for _ in 1..self.quality {
+            system.apply_filter(encoder, device, pipeline, &[h_bind_group], output, &tmp);
+            system.apply_filter(encoder, device, pipeline, &[v_bind_group], &tmp, output);
+        }
+
+        system.release(tmp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_is_normalized() {
+        for size in (3..=MAX_KERNEL_SIZE).step_by(2) {
+            let weights = gaussian_kernel(size);
+            let sum = weights[0] + 2.0 * weights[1..].iter().sum::<f32>();
+            assert_eq!(weights.len(), size / 2 + 1);
+            assert!((sum - 1.0).abs() < 1e-5);
+            assert!(weights.windows(2).all(|w| w[0] >= w[1]));
+        }
+    }
+
+    #[test]
+    fn blur_keeps_solid_color() {
+        let mut pixels = Pixels::new(8, 8);
+        for c in &mut pixels.data {
+            *c = [0.25, 0.5, 0.75, 1.0];
+        }
+
+        let weights = gaussian_kernel(9);
+        let blurred = blur_pass(&pixels, &weights, [1.5, 0.0]);
+        assert!(blurred.max_difference(&pixels) < 1e-5);
+    }
+
+    #[test]
+    fn blur_spreads_single_texel() {
+        let mut pixels = Pixels::new(9, 1);
+        pixels.set(4, 0, [1.0; 4]);
+
+        let weights = gaussian_kernel(5);
+        let blurred = blur_pass(&pixels, &weights, [1.0, 0.0]);
+
+        assert!((blurred.get(4, 0)[3] - weights[0]).abs() < 1e-6);
+        assert!((blurred.get(3, 0)[3] - weights[1]).abs() < 1e-6);
+        assert!((blurred.get(6, 0)[3] - weights[2]).abs() < 1e-6);
+        assert_eq!(blurred.get(0, 0)[3], 0.0);
+    }
+}
@@ -0,0 +1,385 @@
+use super::{reference::Pixels, Filter, FilterSystem, ShaderFilter};
+use crate::{image::ImageSource, target::RenderTarget};
+
+/// 5x4 row-major color matrix, the last column holds offsets.
+///
+/// `r' = m[0] * r + m[1] * g + m[2] * b + m[3] * a + m[4]`, and so on for each row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorMatrix(pub [f32; 20]);
+
+impl Default for ColorMatrix {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl ColorMatrix {
+    #[rustfmt::skip]
+    pub const IDENTITY: Self = Self([
+        1.0, 0.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.0, 1.0, 0.0,
+    ]);
+
+    #[rustfmt::skip]
+    pub const NEGATIVE: Self = Self([
+        -1.0, 0.0, 0.0, 1.0, 0.0,
+        0.0, -1.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, -1.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0, 0.0,
+    ]);
+
+    #[rustfmt::skip]
+    pub const SEPIA: Self = Self([
+        0.393, 0.769, 0.189, 0.0, 0.0,
+        0.349, 0.686, 0.168, 0.0, 0.0,
+        0.272, 0.534, 0.131, 0.0, 0.0,
+        0.0, 0.0, 0.0, 1.0, 0.0,
+    ]);
+
+    /// Scales RGB channels by `b`.
+    #[rustfmt::skip]
+    pub fn brightness(b: f32) -> Self {
+        Self([
+            b, 0.0, 0.0, 0.0, 0.0,
+            0.0, b, 0.0, 0.0, 0.0,
+            0.0, 0.0, b, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Multiplies RGB channels by `[r, g, b]`.
+    #[rustfmt::skip]
+    pub fn tint([r, g, b]: [f32; 3]) -> Self {
+        Self([
+            r, 0.0, 0.0, 0.0, 0.0,
+            0.0, g, 0.0, 0.0, 0.0,
+            0.0, 0.0, b, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Sets each RGB channel to the sum of channels scaled by `scale`.
+    #[rustfmt::skip]
+    pub fn grayscale(scale: f32) -> Self {
+        let s = scale;
+        Self([
+            s, s, s, 0.0, 0.0,
+            s, s, s, 0.0, 0.0,
+            s, s, s, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Rotates hue by `rotation` degrees.
+    #[rustfmt::skip]
+    pub fn hue_rotate(rotation: f32) -> Self {
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        let w = 1.0 / 3.0;
+        let sqrt_w = f32::sqrt(w);
+
+        let a00 = cos + (1.0 - cos) * w;
+        let a01 = w * (1.0 - cos) - sqrt_w * sin;
+        let a02 = w * (1.0 - cos) + sqrt_w * sin;
+
+        let a10 = w * (1.0 - cos) + sqrt_w * sin;
+        let a11 = cos + w * (1.0 - cos);
+        let a12 = w * (1.0 - cos) - sqrt_w * sin;
+
+        let a20 = w * (1.0 - cos) - sqrt_w * sin;
+        let a21 = w * (1.0 - cos) + sqrt_w * sin;
+        let a22 = cos + w * (1.0 - cos);
+
+        Self([
+            a00, a01, a02, 0.0, 0.0,
+            a10, a11, a12, 0.0, 0.0,
+            a20, a21, a22, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Increases (positive) or decreases (negative) contrast around mid-gray.
+    #[rustfmt::skip]
+    pub fn contrast(amount: f32) -> Self {
+        let v = amount + 1.0;
+        let o = -0.5 * (v - 1.0);
+        Self([
+            v, 0.0, 0.0, 0.0, o,
+            0.0, v, 0.0, 0.0, o,
+            0.0, 0.0, v, 0.0, o,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    /// Increases (positive) or decreases (negative) saturation.
+    #[rustfmt::skip]
+    pub fn saturate(amount: f32) -> Self {
+        let x = amount * 2.0 / 3.0 + 1.0;
+        let y = (x - 1.0) * -0.5;
+        Self([
+            x, y, y, 0.0, 0.0,
+            y, x, y, 0.0, 0.0,
+            y, y, x, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ])
+    }
+
+    pub fn desaturate() -> Self {
+        Self::saturate(-1.0)
+    }
+
+    /// Product of two matrices, as PixiJS's `ColorMatrixFilter` composes them.
+    ///
+    /// The result applies `rhs` first and `self` second.
+    pub fn multiply(&self, rhs: &Self) -> Self {
+        let (a, b) = (&self.0, &rhs.0);
+        let mut out = [0.0; 20];
+        for row in 0..4 {
+            let r = row * 5;
+            for col in 0..5 {
+                out[r + col] = a[r] * b[col]
+                    + a[r + 1] * b[col + 5]
+                    + a[r + 2] * b[col + 10]
+                    + a[r + 3] * b[col + 15];
+            }
+            out[r + 4] += a[r + 4];
+        }
+        Self(out)
+    }
+
+    /// Composition that applies `self` first and `next` second.
+    pub fn then(&self, next: &Self) -> Self {
+        next.multiply(self)
+    }
+
+    /// Transforms a straight (not premultiplied) color.
+    pub fn transform(&self, [r, g, b, a]: [f32; 4]) -> [f32; 4] {
+        let m = &self.0;
+        let row = |i: usize| m[i] * r + m[i + 1] * g + m[i + 2] * b + m[i + 3] * a + m[i + 4];
+        [row(0), row(5), row(10), row(15)]
+    }
+
+    /// Transforms a premultiplied color, blending with the original by `alpha`.
+    ///
+    /// Matches the output of [`ColorMatrixFilter`] before it is stored.
+    pub fn apply_premultiplied(&self, color: [f32; 4], alpha: f32) -> [f32; 4] {
+        if alpha == 0.0 {
+            return color;
+        }
+
+        let [r, g, b, a] = color;
+        let [r, g, b] = if a > 0.0 {
+            [r / a, g / a, b / a]
+        } else {
+            [r, g, b]
+        };
+
+        let [tr, tg, tb, ta] = self.transform([r, g, b, a]);
+        let mix = |x: f32, y: f32| (x + (y - x) * alpha) * ta;
+        [mix(r, tr), mix(g, tg), mix(b, tb), ta]
+    }
+
+    /// Transforms straight alpha RGBA8 or BGRA8 texels in place.
+    pub fn apply_source(&self, source: &mut ImageSource) {
+        use wgpu::TextureFormat::*;
+
+        let bgra = match source.format {
+            Rgba8Unorm | Rgba8UnormSrgb => false,
+            Bgra8Unorm | Bgra8UnormSrgb => true,
+            format => panic!("unsupported format: {:?}", format),
+        };
+
+        let float = |c: u8| c as f32 / 255.0;
+        let unorm = |c: f32| (crate::math::clamp01(c) * 255.0).round() as u8;
+
+        for texel in source.texels.chunks_exact_mut(4) {
+            let (r, b) = if bgra { (2, 0) } else { (0, 2) };
+            let color = [
+                float(texel[r]),
+                float(texel[1]),
+                float(texel[b]),
+                float(texel[3]),
+            ];
+            let [cr, cg, cb, ca] = self.transform(color);
+            texel[r] = unorm(cr);
+            texel[1] = unorm(cg);
+            texel[b] = unorm(cb);
+            texel[3] = unorm(ca);
+        }
+    }
+
+    /// Column-major `mat4`, `vec4` offset and alpha as laid out in the shader.
+    fn uniforms(&self, alpha: f32) -> [f32; 24] {
+        let m = &self.0;
+        let mut data = [0.0; 24];
+        for col in 0..4 {
+            for row in 0..4 {
+                data[col * 4 + row] = m[row * 5 + col];
+            }
+        }
+        data[16..20].copy_from_slice(&[m[4], m[9], m[14], m[19]]);
+        data[20] = alpha;
+        data
+    }
+}
+
+/// Applies a [`ColorMatrix`] to each pixel.
+pub struct ColorMatrixFilter {
+    pub matrix: ColorMatrix,
+    /// Opacity of the effect, `0.0` leaves the input unchanged.
+    pub alpha: f32,
+
+    shader: ShaderFilter,
+}
+
+impl ColorMatrixFilter {
+    pub fn new(device: &wgpu::Device, system: &FilterSystem) -> Self {
+        let matrix = ColorMatrix::IDENTITY;
+        let alpha = 1.0;
+
+        let spirv = include_bytes!("../shaders/compiled/color_matrix.frag.spv");
+        let shader = ShaderFilter::new(device, system, spirv, &matrix.uniforms(alpha));
+
+        Self {
+            matrix,
+            alpha,
+            shader,
+        }
+    }
+
+    fn load(&mut self, matrix: ColorMatrix, multiply: bool) {
+        self.matrix = if multiply {
+            self.matrix.multiply(&matrix)
+        } else {
+            matrix
+        };
+    }
+
+    pub fn reset(&mut self) {
+        self.matrix = ColorMatrix::IDENTITY;
+    }
+
+    pub fn brightness(&mut self, b: f32, multiply: bool) {
+        self.load(ColorMatrix::brightness(b), multiply);
+    }
+
+    pub fn tint(&mut self, color: [f32; 3], multiply: bool) {
+        self.load(ColorMatrix::tint(color), multiply);
+    }
+
+    pub fn grayscale(&mut self, scale: f32, multiply: bool) {
+        self.load(ColorMatrix::grayscale(scale), multiply);
+    }
+
+    pub fn hue_rotate(&mut self, rotation: f32, multiply: bool) {
+        self.load(ColorMatrix::hue_rotate(rotation), multiply);
+    }
+
+    pub fn contrast(&mut self, amount: f32, multiply: bool) {
+        self.load(ColorMatrix::contrast(amount), multiply);
+    }
+
+    pub fn saturate(&mut self, amount: f32, multiply: bool) {
+        self.load(ColorMatrix::saturate(amount), multiply);
+    }
+
+    pub fn desaturate(&mut self) {
+        self.load(ColorMatrix::desaturate(), true);
+    }
+
+    pub fn sepia(&mut self, multiply: bool) {
+        self.load(ColorMatrix::SEPIA, multiply);
+    }
+
+    pub fn negative(&mut self, multiply: bool) {
+        self.load(ColorMatrix::NEGATIVE, multiply);
+    }
+
+    /// CPU equivalent of [`Filter::apply`].
+    pub fn reference(&self, input: &Pixels) -> Pixels {
+        input.map(|c| self.matrix.apply_premultiplied(c, self.alpha))
+    }
+}
+
+impl Filter for ColorMatrixFilter {
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        let uniforms = self.matrix.uniforms(self.alpha);
+        self.shader.set_uniforms(encoder, device, &uniforms);
+        self.shader.apply(encoder, device, system, input, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: [f32; 4], b: [f32; 4]) {
+        for i in 0..4 {
+            assert!((a[i] - b[i]).abs() < 1e-4, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn identity() {
+        let color = [0.1, 0.2, 0.3, 0.4];
+        assert_near(ColorMatrix::IDENTITY.transform(color), color);
+        assert_near(ColorMatrix::hue_rotate(0.0).transform(color), color);
+        assert_near(ColorMatrix::hue_rotate(360.0).transform(color), color);
+        assert_near(ColorMatrix::saturate(0.0).transform(color), color);
+        assert_near(ColorMatrix::contrast(0.0).transform(color), color);
+    }
+
+    #[test]
+    fn composition() {
+        let a = ColorMatrix::brightness(0.5);
+        let b = ColorMatrix::brightness(0.5).multiply(&a);
+        assert_eq!(b, ColorMatrix::brightness(0.25));
+
+        let twice = ColorMatrix::NEGATIVE.multiply(&ColorMatrix::NEGATIVE);
+        assert_near(twice.transform([0.1, 0.2, 0.3, 1.0]), [0.1, 0.2, 0.3, 1.0]);
+
+        let color = [0.2, 0.4, 0.6, 1.0];
+        let (m1, m2) = (ColorMatrix::contrast(0.3), ColorMatrix::hue_rotate(45.0));
+        let composed = m1.then(&m2).transform(color);
+        assert_near(composed, m2.transform(m1.transform(color)));
+    }
+
+    #[test]
+    fn presets() {
+        let [r, g, b, a] = ColorMatrix::grayscale(1.0 / 3.0).transform([0.3, 0.6, 0.9, 1.0]);
+        assert_near([r, g, b, a], [0.6, 0.6, 0.6, 1.0]);
+
+        let negative = ColorMatrix::NEGATIVE.transform([0.25, 0.5, 1.0, 1.0]);
+        assert_near(negative, [0.75, 0.5, 0.0, 1.0]);
+
+        let desaturated = ColorMatrix::desaturate().transform([1.0, 0.0, 0.0, 1.0]);
+        assert_near(desaturated, [1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0, 1.0]);
+    }
+
+    #[test]
+    fn premultiplied() {
+        let m = ColorMatrix::grayscale(1.0 / 3.0);
+        let color = [0.1, 0.2, 0.3, 0.5];
+        assert_near(m.apply_premultiplied(color, 1.0), [0.2, 0.2, 0.2, 0.5]);
+        assert_near(m.apply_premultiplied(color, 0.5), [0.15, 0.2, 0.25, 0.5]);
+        assert_near(m.apply_premultiplied(color, 0.0), color);
+    }
+
+    #[test]
+    fn image_source() {
+        let texels = vec![255, 0, 0, 255, 0, 128, 255, 255];
+        let format = wgpu::TextureFormat::Bgra8Unorm;
+        let mut source = ImageSource::new(format, texels, 2, 1);
+
+        ColorMatrix::NEGATIVE.apply_source(&mut source);
+        assert_eq!(source.texels, [0, 255, 255, 255, 255, 127, 0, 255]);
+    }
+}
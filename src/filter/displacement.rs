@@ -0,0 +1,182 @@
+use super::{reference::Pixels, Filter, FilterShader, FilterSystem};
+use crate::{
+    image::ImageBindGroup,
+    math::{clamp01, Frame, Matrix},
+    target::RenderTarget,
+};
+use std::sync::Arc;
+
+/// Offsets input texels by the red and green channels of a map sprite.
+///
+/// `0.5` in the map means no displacement.
+pub struct DisplacementFilter {
+    /// Transform of the map sprite in logical coordinates.
+    pub transform: Matrix,
+    /// Maximum displacement in logical pixels.
+    pub scale: [f32; 2],
+    /// Map texture, bound with the [`FilterSystem::layout`].
+    pub map: ImageBindGroup,
+    /// Size of the map texture in logical pixels.
+    pub map_size: [f32; 2],
+
+    pipeline: Arc<wgpu::RenderPipeline>,
+    uniforms: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl DisplacementFilter {
+    const UNIFORMS_SIZE: wgpu::BufferAddress = 4 * 16;
+
+    pub fn new(
+        device: &wgpu::Device,
+        system: &FilterSystem,
+        map: ImageBindGroup,
+        map_size: [f32; 2],
+    ) -> Self {
+        let layout = &system.layout.filter;
+
+        let pipeline = system.pipeline(device, FilterShader::Displacement);
+
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("displacement uniforms"),
+            size: Self::UNIFORMS_SIZE,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let bind_group = layout.bind_uniforms(device, &uniforms, Self::UNIFORMS_SIZE);
+
+        Self {
+            transform: Matrix::IDENTITY,
+            scale: [20.0, 20.0],
+            map,
+            map_size,
+
+            pipeline,
+            uniforms,
+            bind_group,
+        }
+    }
+
+    /// Maps texture coordinates of a subtree covering `frame` to map texture
+    /// coordinates.
+    fn map_matrix(transform: Matrix, [w, h]: [f32; 2], frame: Frame) -> Matrix {
+        Matrix::new(frame.w, 0.0, 0.0, frame.h, frame.x, frame.y)
+            .append(transform.invert())
+            .scale(w.recip(), h.recip())
+    }
+
+    /// Maximum displacement in texture coordinates of `input`.
+    fn offset_scale(&self, scale: f32, width: u32, height: u32) -> [f32; 2] {
+        let [x, y] = self.scale;
+        [x * scale / width as f32, y * scale / height as f32]
+    }
+
+    /// CPU equivalent of [`Filter::apply`] for a subtree covering `frame` at
+    /// given resolution, `map` holds the texels of the map texture.
+    ///
+    /// The map is sampled with clamp-to-edge addressing.
+    pub fn reference(&self, input: &Pixels, map: &Pixels, frame: Frame, scale: f32) -> Pixels {
+        let matrix = Self::map_matrix(self.transform, self.map_size, frame);
+        let offset_scale = self.offset_scale(scale, input.width, input.height);
+        Self::displace(input, map, matrix, offset_scale)
+    }
+
+    fn displace(input: &Pixels, map: &Pixels, matrix: Matrix, [sx, sy]: [f32; 2]) -> Pixels {
+        let (width, height) = (input.width as f32, input.height as f32);
+        let mut output = Pixels::new(input.width, input.height);
+        for y in 0..input.height {
+            for x in 0..input.width {
+                let (u, v) = ((x as f32 + 0.5) / width, (y as f32 + 0.5) / height);
+                let [mu, mv] = matrix.apply(u, v);
+                let [r, g, _, _] = map.sample_uv(mu, mv);
+
+                let u = clamp01(u + (r - 0.5) * sx);
+                let v = clamp01(v + (g - 0.5) * sy);
+                output.set(x, y, input.sample_uv(u, v));
+            }
+        }
+        output
+    }
+}
+
+impl Filter for DisplacementFilter {
+    fn padding(&self) -> f32 {
+        let [x, y] = self.scale;
+        x.abs().max(y.abs())
+    }
+
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        let m = Self::map_matrix(self.transform, self.map_size, system.frame());
+        let [sx, sy] = self.offset_scale(system.scale(), input.width, input.height);
+
+        #[rustfmt::skip]
+        let data = [
+            m.a, m.b, 0.0, 0.0,
+            m.c, m.d, 0.0, 0.0,
+            m.tx, m.ty, 1.0, 0.0,
+            sx, sy, 0.0, 0.0,
+        ];
+        crate::utils::write_buffer(encoder, device, &self.uniforms, 0, crate::cast_slice(&data));
+
+        let bind_groups = [&self.bind_group, &*self.map];
+        system.apply_filter(encoder, device, &self.pipeline, &bind_groups, input, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near([x, y]: [f32; 2], expected: [f32; 2]) {
+        assert!(
+            (x - expected[0]).abs() < 1e-5 && (y - expected[1]).abs() < 1e-5,
+            "{:?} != {:?}",
+            [x, y],
+            expected
+        );
+    }
+
+    #[test]
+    fn map_matrix() {
+        // map sprite covering the filtered frame exactly
+        let frame = Frame::new(10.0, 20.0, 40.0, 30.0);
+        let transform = Matrix::IDENTITY.translate(10.0, 20.0);
+        let m = DisplacementFilter::map_matrix(transform, [40.0, 30.0], frame);
+        assert_near(m.apply(0.0, 0.0), [0.0, 0.0]);
+        assert_near(m.apply(1.0, 1.0), [1.0, 1.0]);
+
+        // frame over the right half of a map scaled by 2
+        let frame = Frame::new(20.0, 0.0, 20.0, 40.0);
+        let transform = Matrix::new(2.0, 0.0, 0.0, 2.0, 0.0, 0.0);
+        let m = DisplacementFilter::map_matrix(transform, [20.0, 20.0], frame);
+        assert_near(m.apply(0.0, 0.0), [0.5, 0.0]);
+        assert_near(m.apply(1.0, 0.5), [1.0, 0.5]);
+    }
+
+    #[test]
+    fn displace() {
+        let mut input = Pixels::new(4, 1);
+        for x in 0..4 {
+            input.set(x, 0, [x as f32 / 3.0; 4]);
+        }
+
+        let mut map = Pixels::new(1, 1);
+        map.set(0, 0, [0.5, 0.5, 0.0, 1.0]);
+        let output = DisplacementFilter::displace(&input, &map, Matrix::IDENTITY, [0.5, 0.5]);
+        assert!(output.max_difference(&input) < 1e-5);
+
+        // full red moves by a quarter of the width, one texel, clamped at the edge
+        map.set(0, 0, [1.0, 0.5, 0.0, 1.0]);
+        let output = DisplacementFilter::displace(&input, &map, Matrix::IDENTITY, [0.5, 0.5]);
+        let expected = [1.0 / 3.0, 2.0 / 3.0, 1.0, 1.0];
+        for (x, &value) in expected.iter().enumerate() {
+            assert!((output.get(x as i32, 0)[0] - value).abs() < 1e-5);
+        }
+    }
+}
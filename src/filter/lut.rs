@@ -0,0 +1,463 @@
+use super::{reference::Pixels, Filter, FilterShader, FilterSystem};
+use crate::{
+    image::{f32_to_f16, ImageSource},
+    target::RenderTarget,
+    utils::{align, COPY_ROW_ALIGNMENT},
+    Error,
+};
+use std::{fs, path::Path, sync::Arc};
+
+/// 3D color lookup table.
+///
+/// Entries are stored with red changing fastest, then green, then blue.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut {
+    pub size: usize,
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    pub data: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    /// Table that maps each color to itself, `size` must be at least 2.
+    pub fn identity(size: usize) -> Result<Self, Error> {
+        if size < 2 {
+            let message = format!("LUT size must be at least 2, found {}", size);
+            return Err(Error::Format(message));
+        }
+
+        let max = (size - 1) as f32;
+        let mut data = Vec::with_capacity(size * size * size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push([r as f32 / max, g as f32 / max, b as f32 / max]);
+                }
+            }
+        }
+
+        Ok(Self {
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            data,
+        })
+    }
+
+    /// Loads a `.cube` file.
+    pub fn open_cube(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let text = fs::read_to_string(path)?;
+        Self::parse_cube(&text)
+    }
+
+    /// Loads a strip image, `size * size` pixels wide and `size` pixels high.
+    pub fn open_strip(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_strip(&ImageSource::linear(path)?)
+    }
+
+    pub fn parse_cube(text: &str) -> Result<Self, Error> {
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut data = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let number = index + 1;
+            let parse = |word: Option<&str>| -> Result<f32, Error> {
+                let word = word.ok_or_else(|| parse_error(number, "missing value"))?;
+                word.parse()
+                    .map_err(|_| parse_error(number, &format!("invalid number `{}`", word)))
+            };
+
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("TITLE") => (),
+                Some("LUT_3D_SIZE") => {
+                    let value = parse(words.next())?;
+                    if value < 2.0 || value.fract() != 0.0 {
+                        let message = format!("invalid size {}", value);
+                        return Err(parse_error(number, &message));
+                    }
+                    size = Some(value as usize);
+                }
+                Some("LUT_1D_SIZE") => {
+                    return Err(parse_error(number, "1D tables are not supported"));
+                }
+                Some("DOMAIN_MIN") => {
+                    domain_min = [
+                        parse(words.next())?,
+                        parse(words.next())?,
+                        parse(words.next())?,
+                    ]
+                }
+                Some("DOMAIN_MAX") => {
+                    domain_max = [
+                        parse(words.next())?,
+                        parse(words.next())?,
+                        parse(words.next())?,
+                    ]
+                }
+                Some("LUT_3D_INPUT_RANGE") => {
+                    let (min, max) = (parse(words.next())?, parse(words.next())?);
+                    domain_min = [min; 3];
+                    domain_max = [max; 3];
+                }
+                first => data.push([parse(first)?, parse(words.next())?, parse(words.next())?]),
+            }
+        }
+
+        let size = size.ok_or_else(|| Error::Format("missing LUT_3D_SIZE".into()))?;
+        if data.len() != size * size * size {
+            let message = format!("expected {} entries, found {}", size.pow(3), data.len());
+            return Err(Error::Format(message));
+        }
+
+        Ok(Self {
+            size,
+            domain_min,
+            domain_max,
+            data,
+        })
+    }
+
+    /// Reads a horizontal strip of blue slices, red along x and green along y.
+    pub fn from_strip(source: &ImageSource) -> Result<Self, Error> {
+        let pixels =
+            Pixels::from_source(source).map_err(|err| Error::Format(format!("strip: {}", err)))?;
+
+        let size = source.height as usize;
+        if size < 2 || source.width as usize != size * size {
+            let message = format!(
+                "strip must be {0}x{1} pixels, found {2}x{1}",
+                size * size,
+                size,
+                source.width
+            );
+            return Err(Error::Format(message));
+        }
+
+        let mut data = Vec::with_capacity(size * size * size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let [r, g, b, _] = pixels.get((b * size + r) as i32, g as i32);
+                    data.push([r, g, b]);
+                }
+            }
+        }
+
+        Ok(Self {
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            data,
+        })
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.data[r + (g + b * self.size) * self.size]
+    }
+
+    /// Trilinear lookup of a straight (not premultiplied) color.
+    pub fn sample(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let max = (self.size - 1) as f32;
+
+        let mut index = [0; 3];
+        let mut fract = [0.0; 3];
+        for i in 0..3 {
+            let range = self.domain_max[i] - self.domain_min[i];
+            let x = ((rgb[i] - self.domain_min[i]) / range).max(0.0).min(1.0) * max;
+            let floor = x.floor().min(max - 1.0);
+            index[i] = floor as usize;
+            fract[i] = x - floor;
+        }
+
+        let [r, g, b] = index;
+        let [fr, fg, fb] = fract;
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+            [
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ]
+        };
+
+        let c00 = lerp(self.entry(r, g, b), self.entry(r + 1, g, b), fr);
+        let c10 = lerp(self.entry(r, g + 1, b), self.entry(r + 1, g + 1, b), fr);
+        let c01 = lerp(self.entry(r, g, b + 1), self.entry(r + 1, g, b + 1), fr);
+        let c11 = lerp(
+            self.entry(r, g + 1, b + 1),
+            self.entry(r + 1, g + 1, b + 1),
+            fr,
+        );
+
+        lerp(lerp(c00, c10, fg), lerp(c01, c11, fg), fb)
+    }
+
+    /// `Rgba16Float` texels with rows padded to `bytes_per_row`.
+    fn texels(&self, bytes_per_row: usize) -> Vec<u8> {
+        let mut texels = vec![0; bytes_per_row * self.size * self.size];
+        for (row, entries) in self.data.chunks_exact(self.size).enumerate() {
+            let start = row * bytes_per_row;
+            for (i, &[r, g, b]) in entries.iter().enumerate() {
+                let texel = &mut texels[start + i * 8..start + i * 8 + 8];
+                for (c, value) in [r, g, b, 1.0].iter().enumerate() {
+                    texel[2 * c..2 * c + 2].copy_from_slice(&f32_to_f16(*value).to_le_bytes());
+                }
+            }
+        }
+        texels
+    }
+}
+
+fn parse_error(line: usize, message: &str) -> Error {
+    Error::Format(format!("line {}: {}", line, message))
+}
+
+/// Color grading with a 3D lookup table.
+pub struct LutFilter {
+    /// Blend between the original (`0.0`) and graded (`1.0`) colors.
+    pub intensity: f32,
+
+    lut: Lut,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    uniforms: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    lut_bind_group: wgpu::BindGroup,
+}
+
+impl LutFilter {
+    const UNIFORMS_SIZE: wgpu::BufferAddress = 4 * 12;
+
+    /// Uploads `lut` as a `Rgba16Float` 3D texture.
+    pub fn new(
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &FilterSystem,
+        lut: Lut,
+    ) -> Self {
+        let size = lut.size as u32;
+        let bytes_per_row = align(size as wgpu::BufferAddress * 8, COPY_ROW_ALIGNMENT);
+        let texels = lut.texels(bytes_per_row as usize);
+        let texels = device.create_buffer_with_data(&texels, wgpu::BufferUsage::COPY_SRC);
+
+        let extent = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth: size,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("lut"),
+            size: extent,
+            array_layer_count: 1,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+
+        let src = wgpu::BufferCopyView {
+            buffer: &texels,
+            offset: 0,
+            bytes_per_row: bytes_per_row as u32,
+            rows_per_image: size,
+        };
+        let dst = wgpu::TextureCopyView {
+            texture: &texture,
+            mip_level: 0,
+            array_layer: 0,
+            origin: wgpu::Origin3d::ZERO,
+        };
+        encoder.copy_buffer_to_texture(src, dst, extent);
+
+        let view = texture.create_default_view();
+        let lut_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("lut bind group"),
+            layout: &system.layout.filter.lut_texture,
+            bindings: &[
+                wgpu::Binding {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::Binding {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&system.sampler),
+                },
+            ],
+        });
+
+        let layout = &system.layout.filter;
+        let pipeline = system.pipeline(device, FilterShader::Lut);
+
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("lut uniforms"),
+            size: Self::UNIFORMS_SIZE,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let bind_group = layout.bind_uniforms(device, &uniforms, Self::UNIFORMS_SIZE);
+
+        Self {
+            intensity: 1.0,
+
+            lut,
+            pipeline,
+            uniforms,
+            bind_group,
+            lut_bind_group,
+        }
+    }
+
+    pub fn lut(&self) -> &Lut {
+        &self.lut
+    }
+
+    /// CPU equivalent of [`Filter::apply`], without quantizing the table to half floats.
+    pub fn reference(&self, input: &Pixels) -> Pixels {
+        input.map(|[r, g, b, a]| {
+            let rgb = if a > 0.0 {
+                [r / a, g / a, b / a]
+            } else {
+                [r, g, b]
+            };
+            let graded = self.lut.sample(rgb);
+            let mix = |i: usize| (rgb[i] + (graded[i] - rgb[i]) * self.intensity) * a;
+            [mix(0), mix(1), mix(2), a]
+        })
+    }
+}
+
+impl Filter for LutFilter {
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        let Lut {
+            size,
+            domain_min: [r0, g0, b0],
+            domain_max: [r1, g1, b1],
+            ..
+        } = self.lut;
+
+        #[rustfmt::skip]
+        let data = [
+            r0, g0, b0, 0.0,
+            (r1 - r0).recip(), (g1 - g0).recip(), (b1 - b0).recip(), 0.0,
+            self.intensity, size as f32, 0.0, 0.0,
+        ];
+        crate::utils::write_buffer(encoder, device, &self.uniforms, 0, crate::cast_slice(&data));
+
+        let bind_groups = [&self.bind_group, &self.lut_bind_group];
+        system.apply_filter(encoder, device, &self.pipeline, &bind_groups, input, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE: &str = "
+        # comment
+        TITLE \"identity\"
+        LUT_3D_SIZE 2
+        DOMAIN_MIN 0.0 0.0 0.0
+        DOMAIN_MAX 1.0 1.0 1.0
+
+        0.0 0.0 0.0
+        1.0 0.0 0.0
+        0.0 1.0 0.0
+        1.0 1.0 0.0
+        0.0 0.0 1.0
+        1.0 0.0 1.0
+        0.0 1.0 1.0
+        1.0 1.0 1.0
+    ";
+
+    #[test]
+    fn parse_cube() {
+        let lut = Lut::parse_cube(CUBE).unwrap();
+        assert_eq!(lut, Lut::identity(2).unwrap());
+
+        let truncated = CUBE.replace("1.0 1.0 1.0\n", "");
+        assert!(Lut::parse_cube(&truncated).is_err());
+        let err = Lut::parse_cube("LUT_3D_SIZE 2\n0.0 zero 0.0").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: invalid number `zero`");
+    }
+
+    #[test]
+    fn strip() {
+        let size = 4;
+        let mut texels = Vec::new();
+        for g in 0..size {
+            for b in 0..size {
+                for r in 0..size {
+                    let c = |x: u32| (x * 255 / (size - 1)) as u8;
+                    texels.extend_from_slice(&[c(r), c(g), c(b), 255]);
+                }
+            }
+        }
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let source = ImageSource::new(format, texels, size * size, size);
+        let lut = Lut::from_strip(&source).unwrap();
+        let identity = Lut::identity(4).unwrap();
+        assert_eq!(lut.size, 4);
+        for (a, b) in lut.data.iter().zip(&identity.data) {
+            assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-5));
+        }
+
+        let source = ImageSource::new(format, vec![0; 4 * 4 * 4], 4, 4);
+        assert!(Lut::from_strip(&source).is_err());
+
+        let format = wgpu::TextureFormat::R8Unorm;
+        let source = ImageSource::new(format, vec![0; 4 * 2], 4, 2);
+        assert!(matches!(Lut::from_strip(&source), Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn identity_too_small() {
+        assert!(matches!(Lut::identity(1), Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn half_float_texels() {
+        let texels = Lut::identity(2).unwrap().texels(256);
+        assert_eq!(texels.len(), 256 * 2 * 2);
+
+        // 1.0 is 0x3C00, entry (1, 0, 0) is red
+        assert_eq!(&texels[8..16], &[0, 0x3C, 0, 0, 0, 0, 0, 0x3C]);
+        assert!(texels[16..256].iter().all(|&b| b == 0));
+        assert_eq!(
+            &texels[256 + 8..256 + 16],
+            &[0, 0x3C, 0, 0x3C, 0, 0, 0, 0x3C]
+        );
+    }
+
+    #[test]
+    fn trilinear() {
+        let lut = Lut::identity(5).unwrap();
+        for &rgb in &[[0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [0.1, 0.55, 0.9]] {
+            let out = lut.sample(rgb);
+            for i in 0..3 {
+                assert!((out[i] - rgb[i]).abs() < 1e-5);
+            }
+        }
+
+        let mut inverted = Lut::identity(2).unwrap();
+        for c in &mut inverted.data {
+            *c = [1.0 - c[0], 1.0 - c[1], 1.0 - c[2]];
+        }
+        let [r, g, b] = inverted.sample([0.25, 0.5, 0.75]);
+        assert!((r - 0.75).abs() < 1e-5 && (g - 0.5).abs() < 1e-5 && (b - 0.25).abs() < 1e-5);
+    }
+}
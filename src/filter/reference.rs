@@ -0,0 +1,147 @@
+//! CPU reference implementations of filters.
+//!
+//! Filters work on stored texel values, so results match GPU output for
+//! non-sRGB render target formats.
+
+use crate::{image::ImageSource, Error};
+
+/// Floating point RGBA image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pixels {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<[f32; 4]>,
+}
+
+impl Pixels {
+    pub fn new(width: u32, height: u32) -> Self {
+        let data = vec![[0.0; 4]; (width * height) as usize];
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Converts 8-bit RGBA or BGRA texels, fails for other formats.
+    pub fn from_source(source: &ImageSource) -> Result<Self, Error> {
+        let bgra = is_bgra(source.format)?;
+
+        let data = source
+            .texels
+            .chunks_exact(4)
+            .map(|c| {
+                let [r, g, b, a] = [c[0], c[1], c[2], c[3]];
+                let [r, g, b] = if bgra { [b, g, r] } else { [r, g, b] };
+                [float(r), float(g), float(b), float(a)]
+            })
+            .collect();
+
+        Ok(Self {
+            width: source.width,
+            height: source.height,
+            data,
+        })
+    }
+
+    /// Converts to 8-bit RGBA or BGRA texels, fails for other formats.
+    pub fn to_source(&self, format: wgpu::TextureFormat) -> Result<ImageSource, Error> {
+        let bgra = is_bgra(format)?;
+
+        let mut texels = Vec::with_capacity(self.data.len() * 4);
+        for &[r, g, b, a] in &self.data {
+            let [r, g, b] = if bgra { [b, g, r] } else { [r, g, b] };
+            texels.extend_from_slice(&[unorm8(r), unorm8(g), unorm8(b), unorm8(a)]);
+        }
+
+        Ok(ImageSource::new(format, texels, self.width, self.height))
+    }
+
+    /// Texel at `(x, y)` with clamp-to-edge addressing.
+    pub fn get(&self, x: i32, y: i32) -> [f32; 4] {
+        if self.data.is_empty() {
+            return [0.0; 4];
+        }
+        let x = x.max(0).min(self.width as i32 - 1) as usize;
+        let y = y.max(0).min(self.height as i32 - 1) as usize;
+        self.data[x + y * self.width as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: [f32; 4]) {
+        self.data[(x + y * self.width) as usize] = color;
+    }
+
+    /// Bilinear sample at pixel coordinates; texel centers are at `n + 0.5`.
+    pub fn sample(&self, x: f32, y: f32) -> [f32; 4] {
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let c00 = self.get(x0, y0);
+        let c10 = self.get(x0 + 1, y0);
+        let c01 = self.get(x0, y0 + 1);
+        let c11 = self.get(x0 + 1, y0 + 1);
+
+        let mut out = [0.0; 4];
+        for i in 0..4 {
+            let top = c00[i] + (c10[i] - c00[i]) * fx;
+            let bottom = c01[i] + (c11[i] - c01[i]) * fx;
+            out[i] = top + (bottom - top) * fy;
+        }
+        out
+    }
+
+    /// Bilinear sample at normalized texture coordinates.
+    pub fn sample_uv(&self, u: f32, v: f32) -> [f32; 4] {
+        self.sample(u * self.width as f32, v * self.height as f32)
+    }
+
+    /// Largest per-channel difference between two images of the same size.
+    pub fn max_difference(&self, other: &Self) -> f32 {
+        assert_eq!((self.width, self.height), (other.width, other.height));
+        self.data
+            .iter()
+            .zip(&other.data)
+            .flat_map(|(a, b)| (0..4).map(move |i| (a[i] - b[i]).abs()))
+            .fold(0.0, f32::max)
+    }
+
+    pub fn map(&self, f: impl Fn([f32; 4]) -> [f32; 4]) -> Self {
+        Self {
+            width: self.width,
+            height: self.height,
+            data: self.data.iter().map(|&c| f(c)).collect(),
+        }
+    }
+}
+
+/// Whether 8-bit texels of `format` have red and blue swapped.
+fn is_bgra(format: wgpu::TextureFormat) -> Result<bool, Error> {
+    use wgpu::TextureFormat::*;
+
+    match format {
+        Rgba8Unorm | Rgba8UnormSrgb => Ok(false),
+        Bgra8Unorm | Bgra8UnormSrgb => Ok(true),
+        format => Err(Error::Format(format!("unsupported format {:?}", format))),
+    }
+}
+
+fn float(c: u8) -> f32 {
+    c as f32 / 255.0
+}
+
+fn unorm8(c: f32) -> u8 {
+    (crate::math::clamp01(c) * 255.0).round() as u8
+}
+
+/// Source-over compositing of premultiplied colors.
+pub fn over(src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+    let k = 1.0 - src[3];
+    [
+        src[0] + dst[0] * k,
+        src[1] + dst[1] * k,
+        src[2] + dst[2] * k,
+        src[3] + dst[3] * k,
+    ]
+}
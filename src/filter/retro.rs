@@ -0,0 +1,300 @@
+use super::{reference::Pixels, Filter, FilterSystem, ShaderFilter};
+use crate::{math::Frame, target::RenderTarget};
+
+/// Snaps texels to blocks of `size` logical pixels.
+pub struct PixelateFilter {
+    pub size: [f32; 2],
+
+    shader: ShaderFilter,
+}
+
+impl PixelateFilter {
+    pub fn new(device: &wgpu::Device, system: &FilterSystem, size: [f32; 2]) -> Self {
+        let spirv = include_bytes!("../shaders/compiled/pixelate.frag.spv");
+        let shader = ShaderFilter::new(device, system, spirv, &Self::uniforms(size));
+        Self { size, shader }
+    }
+
+    fn uniforms([x, y]: [f32; 2]) -> [f32; 4] {
+        [x.max(1.0), y.max(1.0), 0.0, 0.0]
+    }
+
+    /// CPU equivalent of [`Filter::apply`] for a subtree covering `frame`.
+    ///
+    /// Blocks are sampled at their top-left corner, so the linear sampler of
+    /// the system averages the texels around it.
+    pub fn reference(&self, input: &Pixels, frame: Frame) -> Pixels {
+        Self::pixelate(input, frame, self.size)
+    }
+
+    fn pixelate(input: &Pixels, frame: Frame, size: [f32; 2]) -> Pixels {
+        let [sx, sy, _, _] = Self::uniforms(size);
+        let (width, height) = (input.width as f32, input.height as f32);
+
+        let mut output = Pixels::new(input.width, input.height);
+        for y in 0..input.height {
+            for x in 0..input.width {
+                // snap in logical coordinates, like the shader
+                let lx = frame.x + (x as f32 + 0.5) / width * frame.w;
+                let ly = frame.y + (y as f32 + 0.5) / height * frame.h;
+                let u = ((lx / sx).floor() * sx - frame.x) / frame.w;
+                let v = ((ly / sy).floor() * sy - frame.y) / frame.h;
+                output.set(x, y, input.sample_uv(u, v));
+            }
+        }
+        output
+    }
+}
+
+impl Filter for PixelateFilter {
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        let uniforms = Self::uniforms(self.size);
+        self.shader.set_uniforms(encoder, device, &uniforms);
+        self.shader.apply(encoder, device, system, input, output);
+    }
+}
+
+/// Old monitor look: scanlines, noise and vignette.
+pub struct CrtFilter {
+    /// Bend of the scanlines.
+    pub curvature: f32,
+    /// Scanline width, `0.0` disables scanlines.
+    pub line_width: f32,
+    pub line_contrast: f32,
+    pub vertical_line: bool,
+
+    /// Noise opacity, `0.0` disables noise.
+    pub noise: f32,
+    /// Size of noise grains in physical pixels.
+    pub noise_size: f32,
+    pub seed: f32,
+    /// Animates scanlines, usually elapsed seconds.
+    pub time: f32,
+
+    /// Radius of the vignette, `0.0` disables vignette.
+    pub vignetting: f32,
+    pub vignetting_alpha: f32,
+    pub vignetting_blur: f32,
+
+    shader: ShaderFilter,
+}
+
+impl CrtFilter {
+    pub fn new(device: &wgpu::Device, system: &FilterSystem) -> Self {
+        let spirv = include_bytes!("../shaders/compiled/crt.frag.spv");
+        let shader = ShaderFilter::new(device, system, spirv, &[0.0f32; 12]);
+
+        Self {
+            curvature: 1.0,
+            line_width: 1.0,
+            line_contrast: 0.25,
+            vertical_line: false,
+
+            noise: 0.0,
+            noise_size: 1.0,
+            seed: 0.0,
+            time: 0.0,
+
+            vignetting: 0.3,
+            vignetting_alpha: 1.0,
+            vignetting_blur: 0.3,
+
+            shader,
+        }
+    }
+
+    fn uniforms(&self) -> [f32; 12] {
+        let vertical_line = if self.vertical_line { 1.0 } else { 0.0 };
+        [
+            self.curvature,
+            self.line_width,
+            self.line_contrast,
+            vertical_line,
+            self.noise,
+            self.noise_size,
+            self.seed,
+            self.time,
+            self.vignetting,
+            self.vignetting_alpha,
+            self.vignetting_blur,
+            0.0,
+        ]
+    }
+
+    /// CPU equivalent of [`Filter::apply`].
+    ///
+    /// Noise uses the same hash as the shader, but GPUs evaluate it with less
+    /// precision, so only results without noise match.
+    pub fn reference(&self, input: &Pixels) -> Pixels {
+        Self::crt(input, self.uniforms())
+    }
+
+    fn crt(input: &Pixels, uniforms: [f32; 12]) -> Pixels {
+        use std::f32::consts::SQRT_2;
+
+        #[rustfmt::skip]
+        let [
+            curvature, line_width, line_contrast, vertical_line,
+            noise, noise_size, seed, time,
+            vignetting, vignetting_alpha, vignetting_blur, _,
+        ] = uniforms;
+
+        let rand = |[x, y]: [f32; 2]| ((x * 12.9898 + y * 78.233).sin() * 43_758.547).fract();
+        let scale = |rgb: &mut [f32; 3], k: f32| rgb.iter_mut().for_each(|c| *c *= k);
+        let (width, height) = (input.width as f32, input.height as f32);
+
+        let mut output = Pixels::new(input.width, input.height);
+        for py in 0..input.height {
+            for px in 0..input.width {
+                let (u, v) = ((px as f32 + 0.5) / width, (py as f32 + 0.5) / height);
+                let dir = [u - 0.5, v - 0.5];
+
+                let [r, g, b, a] = input.get(px as i32, py as i32);
+                let mut rgb = [r, g, b];
+
+                if noise > 0.0 && noise_size > 0.0 {
+                    let cell = |x: f32| (x / noise_size).floor() * noise_size * seed;
+                    let grain = (rand([cell(u * width), cell(v * height)]) - 0.5) * noise;
+                    rgb.iter_mut().for_each(|c| *c += grain);
+                }
+
+                if line_width > 0.0 {
+                    let c = if curvature > 0.0 { curvature } else { 1.0 };
+                    let k = if curvature > 0.0 {
+                        let length = (dir[0].powi(4) + dir[1].powi(4)).sqrt();
+                        length * 0.25 * c * c + 0.935 * c
+                    } else {
+                        1.0
+                    };
+                    let vertical = vertical_line > 0.5;
+
+                    let mut line = if vertical {
+                        dir[0] * k * width
+                    } else {
+                        dir[1] * k * height
+                    };
+                    line *= (2.0 / line_width).min(1.0) / c;
+                    scale(
+                        &mut rgb,
+                        1.0 + (line * 1.2 - time).cos() * 0.5 * line_contrast,
+                    );
+
+                    let segment = if vertical {
+                        (dir[0] + 0.5) * width
+                    } else {
+                        (dir[1] + 0.5) * height
+                    };
+                    scale(&mut rgb, 0.99 + segment.rem_euclid(4.0).ceil() * 0.015);
+                }
+
+                if vignetting > 0.0 {
+                    let outer = SQRT_2 - vignetting * SQRT_2;
+                    let length = (dir[0] * dir[0] + dir[1] * dir[1]).sqrt();
+                    let darker = (outer - length * SQRT_2) / (0.00001 + vignetting_blur * SQRT_2);
+                    let darker = darker.max(0.0).min(1.0);
+                    scale(&mut rgb, darker + (1.0 - darker) * (1.0 - vignetting_alpha));
+                }
+
+                output.set(px, py, [rgb[0], rgb[1], rgb[2], a]);
+            }
+        }
+        output
+    }
+}
+
+impl Filter for CrtFilter {
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        self.shader.set_uniforms(encoder, device, &self.uniforms());
+        self.shader.apply(encoder, device, system, input, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32) -> Pixels {
+        let mut pixels = Pixels::new(width, 1);
+        for x in 0..width {
+            pixels.set(x, 0, [x as f32 / (width - 1) as f32; 4]);
+        }
+        pixels
+    }
+
+    #[test]
+    fn pixelate_snaps_to_blocks() {
+        let input = gradient(8);
+        let red = |pixels: &Pixels, x: i32| pixels.get(x, 0)[0];
+
+        let frame = Frame::new(0.0, 0.0, 8.0, 1.0);
+        let output = PixelateFilter::pixelate(&input, frame, [2.0, 1.0]);
+        for x in (0..8).step_by(2) {
+            assert_eq!(red(&output, x), red(&output, x + 1));
+        }
+        assert!(red(&output, 1) < red(&output, 2));
+        // corner of the second block, between texels 1 and 2
+        let expected = (red(&input, 1) + red(&input, 2)) / 2.0;
+        assert!((red(&output, 2) - expected).abs() < 1e-5);
+
+        // blocks stay in place in logical coordinates when the frame moves
+        let frame = Frame::new(1.0, 0.0, 8.0, 1.0);
+        let output = PixelateFilter::pixelate(&input, frame, [2.0, 1.0]);
+        assert_eq!(red(&output, 1), red(&output, 2));
+        assert!(red(&output, 0) < red(&output, 1));
+    }
+
+    /// Uniforms of [`CrtFilter`] with everything disabled.
+    const CRT_OFF: [f32; 12] = [1.0, 0.0, 0.25, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.3, 0.0];
+
+    fn solid(width: u32, height: u32) -> Pixels {
+        let mut pixels = Pixels::new(width, height);
+        for c in &mut pixels.data {
+            *c = [0.5, 0.5, 0.5, 0.5];
+        }
+        pixels
+    }
+
+    #[test]
+    fn crt_disabled_keeps_input() {
+        let input = gradient(8);
+        let output = CrtFilter::crt(&input, CRT_OFF);
+        assert!(output.max_difference(&input) < 1e-6);
+    }
+
+    #[test]
+    fn crt_vignetting() {
+        let mut uniforms = CRT_OFF;
+        uniforms[8] = 0.5;
+
+        let input = solid(9, 9);
+        let output = CrtFilter::crt(&input, uniforms);
+        assert_eq!(output.get(4, 4), input.get(4, 4));
+        assert_eq!(output.get(0, 0), [0.0, 0.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn crt_scanlines() {
+        let mut uniforms = CRT_OFF;
+        uniforms[1] = 1.0;
+
+        let input = solid(1, 8);
+        let output = CrtFilter::crt(&input, uniforms);
+        let rows: Vec<_> = (0..8).map(|y| output.get(0, y)).collect();
+        assert!(rows.iter().all(|c| c[3] == 0.5 && c[0] == c[1]));
+        assert!(rows.windows(2).any(|w| w[0][0] != w[1][0]));
+    }
+}
@@ -0,0 +1,237 @@
+use super::{
+    blur::BlurFilter,
+    reference::{self, Pixels},
+    Filter, FilterShader, FilterSystem,
+};
+use crate::{blend, target::RenderTarget};
+use std::sync::Arc;
+
+/// Fills the alpha of the input with a solid color, optionally offset.
+struct TintPass {
+    pipeline: Arc<wgpu::RenderPipeline>,
+    uniforms: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl TintPass {
+    const UNIFORMS_SIZE: wgpu::BufferAddress = 4 * 8;
+
+    fn new(device: &wgpu::Device, system: &FilterSystem) -> Self {
+        let layout = &system.layout.filter;
+
+        let pipeline = system.pipeline(device, FilterShader::Shadow);
+
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tint uniforms"),
+            size: Self::UNIFORMS_SIZE,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let bind_group = layout.bind_uniforms(device, &uniforms, Self::UNIFORMS_SIZE);
+
+        Self {
+            pipeline,
+            uniforms,
+            bind_group,
+        }
+    }
+
+    fn write_uniforms(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &FilterSystem,
+        input: &RenderTarget,
+        [x, y]: [f32; 2],
+        [r, g, b, a]: [f32; 4],
+    ) {
+        let scale = system.scale();
+        let x = x * scale / input.width as f32;
+        let y = y * scale / input.height as f32;
+
+        let data = [x, y, 0.0, 0.0, r, g, b, a];
+        crate::utils::write_buffer(encoder, device, &self.uniforms, 0, crate::cast_slice(&data));
+    }
+
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        let (pipeline, bind_group) = (&self.pipeline, &self.bind_group);
+        system.apply_filter(encoder, device, pipeline, &[bind_group], input, output);
+    }
+
+    fn reference(input: &Pixels, scale: f32, [x, y]: [f32; 2], [r, g, b, a]: [f32; 4]) -> Pixels {
+        let mut output = Pixels::new(input.width, input.height);
+        for py in 0..input.height {
+            for px in 0..input.width {
+                let sx = px as f32 + 0.5 - x * scale;
+                let sy = py as f32 + 0.5 - y * scale;
+                let alpha = input.sample(sx, sy)[3];
+                let [r, g, b, a] = [r * a * alpha, g * a * alpha, b * a * alpha, a * alpha];
+                output.set(px, py, [r.min(1.0), g.min(1.0), b.min(1.0), a.min(1.0)]);
+            }
+        }
+        output
+    }
+}
+
+/// Blurred, offset silhouette drawn behind the input.
+pub struct DropShadowFilter {
+    /// Shadow offset in logical pixels.
+    pub offset: [f32; 2],
+    pub color: [f32; 3],
+    pub alpha: f32,
+    /// Draw only the shadow, without the input on top.
+    pub shadow_only: bool,
+    pub blur: BlurFilter,
+
+    tint: TintPass,
+}
+
+impl DropShadowFilter {
+    pub fn new(device: &wgpu::Device, system: &FilterSystem) -> Self {
+        let mut blur = BlurFilter::new(device, system, 2.0);
+        blur.quality = 3;
+
+        Self {
+            offset: [4.0, 4.0],
+            color: [0.0, 0.0, 0.0],
+            alpha: 0.5,
+            shadow_only: false,
+            blur,
+
+            tint: TintPass::new(device, system),
+        }
+    }
+
+    fn tint_color(&self) -> [f32; 4] {
+        let [r, g, b] = self.color;
+        [r, g, b, self.alpha]
+    }
+
+    /// CPU equivalent of [`Filter::apply`] at given resolution.
+    pub fn reference(&self, input: &Pixels, scale: f32) -> Pixels {
+        let shadow = TintPass::reference(input, scale, self.offset, self.tint_color());
+        let shadow = self.blur.reference(&shadow, scale);
+        if self.shadow_only {
+            return shadow;
+        }
+
+        let mut output = shadow;
+        for (dst, &src) in output.data.iter_mut().zip(&input.data) {
+            *dst = reference::over(src, *dst);
+        }
+        output
+    }
+}
+
+impl Filter for DropShadowFilter {
+    fn padding(&self) -> f32 {
+        let [x, y] = self.offset;
+        x.abs().max(y.abs()) + self.blur.padding()
+    }
+
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        let tmp = system.acquire(device, input.width, input.height);
+
+        let (offset, color) = (self.offset, self.tint_color());
+        self.tint
+            .write_uniforms(encoder, device, system, input, offset, color);
+        self.tint.apply(encoder, device, system, input, &tmp);
+        self.blur.apply(encoder, device, system, &tmp, output);
+
+        if !self.shadow_only {
+            system.copy(encoder, device, input, output, blend::PMA_NORMAL, false);
+        }
+
+        system.release(tmp);
+    }
+}
+
+/// Blurred colored halo around the input.
+pub struct GlowFilter {
+    pub color: [f32; 3],
+    /// Multiplier for the halo opacity.
+    pub strength: f32,
+    /// Draw only the glow, without the input on top.
+    pub knockout: bool,
+    pub blur: BlurFilter,
+
+    tint: TintPass,
+}
+
+impl GlowFilter {
+    pub fn new(device: &wgpu::Device, system: &FilterSystem, distance: f32) -> Self {
+        let mut blur = BlurFilter::new(device, system, distance);
+        blur.kernel_size = 9;
+
+        Self {
+            color: [1.0, 1.0, 1.0],
+            strength: 2.0,
+            knockout: false,
+            blur,
+
+            tint: TintPass::new(device, system),
+        }
+    }
+
+    fn tint_color(&self) -> [f32; 4] {
+        let [r, g, b] = self.color;
+        [r, g, b, self.strength]
+    }
+
+    /// CPU equivalent of [`Filter::apply`] at given resolution.
+    pub fn reference(&self, input: &Pixels, scale: f32) -> Pixels {
+        let glow = TintPass::reference(input, scale, [0.0, 0.0], self.tint_color());
+        let glow = self.blur.reference(&glow, scale);
+        if self.knockout {
+            return glow;
+        }
+
+        let mut output = glow;
+        for (dst, &src) in output.data.iter_mut().zip(&input.data) {
+            *dst = reference::over(src, *dst);
+        }
+        output
+    }
+}
+
+impl Filter for GlowFilter {
+    fn padding(&self) -> f32 {
+        self.blur.padding()
+    }
+
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        system: &mut FilterSystem,
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        let tmp = system.acquire(device, input.width, input.height);
+
+        let color = self.tint_color();
+        self.tint
+            .write_uniforms(encoder, device, system, input, [0.0, 0.0], color);
+        self.tint.apply(encoder, device, system, input, &tmp);
+        self.blur.apply(encoder, device, system, &tmp, output);
+
+        if !self.knockout {
+            system.copy(encoder, device, input, output, blend::PMA_NORMAL, false);
+        }
+
+        system.release(tmp);
+    }
+}
@@ -0,0 +1,338 @@
+use super::{Filter, FilterShader};
+use crate::{
+    blend::{self, Blend},
+    cast_slice,
+    layout::{Layout, PipelineKey, ShaderId, Vertex},
+    math::{Bounds, Frame, Point},
+    target::{Projection, RenderTarget, Target},
+    utils::RingBuffer,
+};
+use std::sync::Arc;
+
+/// Subtree being rendered into an offscreen target between
+/// [`FilterSystem::push`] and [`FilterSystem::pop`].
+pub struct FilterState<'a> {
+    filters: &'a [&'a dyn Filter],
+    frame: Frame,
+    scale: f32,
+    target: RenderTarget,
+}
+
+impl<'a> FilterState<'a> {
+    /// Target to render the filtered subtree into.
+    ///
+    /// Uses the same logical coordinates as the destination target. It's single
+    /// sampled whatever the destination is, so the subtree must be drawn with
+    /// pipelines for 1 sample.
+    pub fn target(&self) -> Target {
+        Target {
+            origin: [self.frame.x, self.frame.y],
+            ..self.target.target(self.scale)
+        }
+    }
+
+    /// Logical area covered by the filtered subtree, including padding.
+    pub fn frame(&self) -> Frame {
+        self.frame
+    }
+}
+
+pub struct FilterSystem {
+    /// Shared with the batches drawing filtered subtrees, filter pipelines
+    /// are cached in it.
+    pub layout: Arc<Layout>,
+    pub format: wgpu::TextureFormat,
+    pub sampler: wgpu::Sampler,
+
+    frame: Frame,
+    scale: f32,
+    pool: Vec<RenderTarget>,
+    /// Physical size of the last destination, the pool is dropped when it changes.
+    dst_size: (u32, u32),
+
+    quad_vtx: RingBuffer,
+    quad_idx: wgpu::Buffer,
+    projection: Projection,
+}
+
+impl FilterSystem {
+    /// Targets kept for reuse, the oldest ones are dropped first.
+    const MAX_POOLED: usize = 16;
+
+    /// `layout` is also used for the pipelines and pooled targets of the
+    /// system, batches drawing filtered subtrees should share it.
+    pub fn new(device: &wgpu::Device, layout: Arc<Layout>, format: wgpu::TextureFormat) -> Self {
+        let idx: [u16; 6] = [0, 1, 2, 0, 2, 3];
+        let quad_idx = device.create_buffer_with_data(cast_slice(&idx), wgpu::BufferUsage::INDEX);
+        let usage = wgpu::BufferUsage::VERTEX;
+        let size = std::mem::size_of::<[Vertex; 4]>() as wgpu::BufferAddress;
+        let quad_vtx = RingBuffer::new(device, &layout.staging, "composite quad", usage, size * 16);
+        let projection = Projection::new(device, &layout);
+
+        Self {
+            format,
+            sampler: crate::linear_sampler(device),
+
+            frame: Frame::EMPTY,
+            scale: 1.0,
+            pool: Vec::new(),
+            dst_size: (0, 0),
+
+            quad_vtx,
+            quad_idx,
+            projection,
+
+            layout,
+        }
+    }
+
+    /// Starts rendering a subtree with `bounds` into a pooled target.
+    ///
+    /// Returns `None` if there is nothing to filter.
+    pub fn push<'a>(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        filters: &'a [&'a dyn Filter],
+        mut bounds: Bounds,
+        dst: &Target,
+    ) -> Option<FilterState<'a>> {
+        if (dst.width, dst.height) != self.dst_size {
+            // targets sized for the old destination are unlikely to fit again
+            self.dst_size = (dst.width, dst.height);
+            self.clear_pool();
+        }
+        if filters.is_empty() || bounds.is_empty() {
+            return None;
+        }
+
+        let padding = filters.iter().map(|f| f.padding()).fold(0.0, f32::max);
+        bounds.pad(Point {
+            x: padding,
+            y: padding,
+        });
+
+        let scale = dst.scale;
+        let [x, y] = dst.origin;
+        let screen = Frame::new(x, y, dst.width as f32 / scale, dst.height as f32 / scale);
+        let frame = bounds.to_frame().fit(screen);
+
+        // snap to physical pixels
+        let x0 = (frame.x * scale).floor();
+        let y0 = (frame.y * scale).floor();
+        let x1 = ((frame.x + frame.w) * scale).ceil();
+        let y1 = ((frame.y + frame.h) * scale).ceil();
+
+        let (width, height) = ((x1 - x0) as u32, (y1 - y0) as u32);
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let frame = Frame::new(x0 / scale, y0 / scale, (x1 - x0) / scale, (y1 - y0) / scale);
+        let target = self.acquire(device, width, height);
+        target.clear(encoder);
+
+        Some(FilterState {
+            filters,
+            frame,
+            scale,
+            target,
+        })
+    }
+
+    /// Applies filters of `state` and composites the result into `dst`.
+    pub fn pop(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        state: FilterState,
+        dst: &Target,
+    ) {
+        let FilterState {
+            filters,
+            frame,
+            scale,
+            target: mut input,
+        } = state;
+
+        self.frame = frame;
+        self.scale = scale;
+
+        let mut output = self.acquire(device, input.width, input.height);
+        for filter in filters {
+            filter.apply(encoder, device, self, &input, &output);
+            std::mem::swap(&mut input, &mut output);
+        }
+
+        let blend = filters.last().map_or(blend::PMA_NORMAL, |f| f.blend());
+        self.composite(encoder, device, &input, frame, blend, dst);
+
+        self.release(input);
+        self.release(output);
+    }
+
+    /// Logical area of the subtree being filtered.
+    pub fn frame(&self) -> Frame {
+        self.frame
+    }
+
+    /// Ratio of physical to logical pixels of the subtree being filtered.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Takes a single sampled target with given physical size from the pool.
+    pub fn acquire(&mut self, device: &wgpu::Device, width: u32, height: u32) -> RenderTarget {
+        let found = self
+            .pool
+            .iter()
+            .position(|t| t.width == width && t.height == height);
+
+        if let Some(index) = found {
+            self.pool.remove(index)
+        } else {
+            let (layout, sampler, format) = (&self.layout, &self.sampler, self.format);
+            RenderTarget::new(device, layout, sampler, format, width, height, 1)
+        }
+    }
+
+    /// Returns a target into the pool.
+    pub fn release(&mut self, target: RenderTarget) {
+        if self.pool.len() == Self::MAX_POOLED {
+            self.pool.remove(0);
+        }
+        self.pool.push(target);
+    }
+
+    /// Drops all pooled targets.
+    pub fn clear_pool(&mut self) {
+        self.pool.clear();
+    }
+
+    /// Cached pipeline of a filter pass writing into targets of the system.
+    pub fn pipeline(
+        &self,
+        device: &wgpu::Device,
+        shader: FilterShader,
+    ) -> Arc<wgpu::RenderPipeline> {
+        let key = PipelineKey::new(ShaderId::Filter(shader), blend::REPLACE, self.format);
+        self.layout.cached_pipeline(device, key)
+    }
+
+    /// Renders `input` into `output` with a filter pipeline.
+    ///
+    /// `bind_groups` are bound starting from set 2.
+    pub fn apply_filter(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        pipeline: &wgpu::RenderPipeline,
+        bind_groups: &[&wgpu::BindGroup],
+        input: &RenderTarget,
+        output: &RenderTarget,
+    ) {
+        let globals = self.globals(device, input);
+
+        let mut rpass = output.clear_pass(encoder);
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, &globals, &[]);
+        rpass.set_bind_group(1, &input.bind_group, &[]);
+        for (i, bind_group) in bind_groups.iter().enumerate() {
+            rpass.set_bind_group(2 + i as u32, bind_group, &[]);
+        }
+        rpass.draw(0..4, 0..1);
+    }
+
+    /// Copies `input` into `output` with given blend mode.
+    pub fn copy(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        input: &RenderTarget,
+        output: &RenderTarget,
+        blend: Blend,
+        clear: bool,
+    ) {
+        let globals = self.globals(device, input);
+
+        let shader = ShaderId::Filter(FilterShader::Copy);
+        let key = PipelineKey::new(shader, blend, self.format);
+        let pipeline = self.layout.cached_pipeline(device, key);
+
+        let mut rpass = if clear {
+            output.clear_pass(encoder)
+        } else {
+            output.load_pass(encoder)
+        };
+        rpass.set_pipeline(&pipeline);
+        rpass.set_bind_group(0, &globals, &[]);
+        rpass.set_bind_group(1, &input.bind_group, &[]);
+        rpass.draw(0..4, 0..1);
+    }
+
+    fn composite(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        input: &RenderTarget,
+        frame: Frame,
+        blend: Blend,
+        dst: &Target,
+    ) {
+        let [min_x, min_y] = frame.min();
+        let [max_x, max_y] = frame.max();
+        let vtx = [
+            Vertex::new(max_x, max_y, 1.0, 1.0), // 11
+            Vertex::new(max_x, min_y, 1.0, 0.0), // 10
+            Vertex::new(min_x, min_y, 0.0, 0.0), // 00
+            Vertex::new(min_x, max_y, 0.0, 1.0), // 01
+        ];
+        let offset = self.quad_vtx.write(encoder, device, cast_slice(&vtx));
+
+        let key = PipelineKey {
+            sample_count: dst.sample_count,
+            ..PipelineKey::new(ShaderId::Sprite, blend, self.format)
+        };
+        let pipeline = self.layout.cached_pipeline(device, key);
+
+        let Self {
+            quad_vtx,
+            quad_idx,
+            projection,
+            ..
+        } = self;
+
+        let proj_bind_group = projection.update(encoder, device, dst);
+
+        let mut rpass = dst.rpass(encoder);
+        rpass.set_pipeline(&pipeline);
+        rpass.set_vertex_buffer(0, quad_vtx.buffer(), offset, 0);
+        rpass.set_index_buffer(quad_idx, 0, 0);
+        rpass.set_bind_group(0, proj_bind_group, &[]);
+        rpass.set_bind_group(1, &input.bind_group, &[]);
+        rpass.draw_indexed(0..6, 0, 0..1);
+    }
+
+    fn globals(&self, device: &wgpu::Device, input: &RenderTarget) -> wgpu::BindGroup {
+        let (width, height) = (input.width as f32, input.height as f32);
+        let frame = self.frame;
+        let data = [
+            [width, height, width.recip(), height.recip()],
+            [frame.x, frame.y, frame.w, frame.h],
+        ];
+
+        let buffer = device.create_buffer_with_data(cast_slice(&data), wgpu::BufferUsage::UNIFORM);
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("filter globals bind group"),
+            layout: &self.layout.filter.globals,
+            bindings: &[wgpu::Binding {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer {
+                    buffer: &buffer,
+                    range: 0..4 * 8,
+                },
+            }],
+        })
+    }
+}
@@ -1,6 +1,18 @@
-use image::DynamicImage::*;
+pub mod compressed;
+pub mod mipmap;
+
+pub use self::{compressed::Compressed, mipmap::Mipmaps};
+use crate::{
+    utils::{align, COPY_ROW_ALIGNMENT},
+    Error,
+};
 pub use image::ImageResult;
-use std::{path::Path, sync::Arc};
+use image::{DynamicImage, DynamicImage::*, GenericImageView};
+use std::{
+    io::{BufRead, Seek},
+    path::Path,
+    sync::Arc,
+};
 
 fn premul(texels: &mut [u8]) {
     fn mul(c: &mut u8, a: &u8) {
@@ -16,6 +28,22 @@ fn premul(texels: &mut [u8]) {
     }
 }
 
+fn unpremul(texels: &mut [u8]) {
+    fn div(c: &mut u8, a: u8) {
+        *c = (*c as f32 * 255.0 / a as f32).round().min(255.0) as u8;
+    }
+
+    for c in texels.chunks_exact_mut(4) {
+        if let [x, y, z, alpha] = c {
+            if *alpha != 0 {
+                div(x, *alpha);
+                div(y, *alpha);
+                div(z, *alpha);
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ImageBindGroup(pub(crate) Arc<wgpu::BindGroup>);
 
@@ -43,53 +71,280 @@ impl ImageSource {
         }
     }
 
+    /// Multiplies color by alpha.
+    ///
+    /// Only 8-bit RGBA or BGRA, `Rg8Unorm` and `Rgba16Float` texels change,
+    /// other formats are left as is.
     pub fn premul(mut self) -> Self {
-        premul(&mut self.texels);
+        use wgpu::TextureFormat::*;
+
+        match self.format {
+            Rgba8Unorm | Rgba8UnormSrgb | Bgra8Unorm | Bgra8UnormSrgb => premul(&mut self.texels),
+            Rg8Unorm => {
+                for c in self.texels.chunks_exact_mut(2) {
+                    c[0] = ((c[0] as f32 * c[1] as f32) / 255.0) as u8;
+                }
+            }
+            Rgba16Float => map_half(&mut self.texels, |[r, g, b, a]| [r * a, g * a, b * a, a]),
+            _ => (),
+        }
         self
     }
 
-    pub fn linear(path: impl AsRef<Path>) -> ImageResult<Self> {
-        let source = image::open(path)?;
-
-        let (format, (width, height), texels) = match source {
-            ImageRgba8(m) => (
-                wgpu::TextureFormat::Rgba8Unorm,
-                m.dimensions(),
-                m.into_raw(),
-            ),
-            ImageBgra8(m) => (
-                wgpu::TextureFormat::Bgra8Unorm,
-                m.dimensions(),
-                m.into_raw(),
-            ),
-            _ => unimplemented!(),
+    /// Divides color by alpha, for the same formats as [`ImageSource::premul`].
+    pub fn unpremul(mut self) -> Self {
+        use wgpu::TextureFormat::*;
+
+        match self.format {
+            Rgba8Unorm | Rgba8UnormSrgb | Bgra8Unorm | Bgra8UnormSrgb => unpremul(&mut self.texels),
+            Rg8Unorm => {
+                for c in self.texels.chunks_exact_mut(2) {
+                    if c[1] != 0 {
+                        let gray = c[0] as f32 * 255.0 / c[1] as f32;
+                        c[0] = gray.round().min(255.0) as u8;
+                    }
+                }
+            }
+            Rgba16Float => map_half(&mut self.texels, |[r, g, b, a]| {
+                if a == 0.0 {
+                    [r, g, b, a]
+                } else {
+                    [r / a, g / a, b / a, a]
+                }
+            }),
+            _ => (),
+        }
+        self
+    }
+
+    /// Saves texels as PNG, color values are written as is.
+    ///
+    /// `R8Unorm` is saved as grayscale, `Rg8Unorm` as grayscale with alpha and
+    /// `Rgba16Float` clamped to 8 bits. Other formats than these and 8-bit
+    /// RGBA or BGRA fail with [`ImageError::Unsupported`](image::ImageError::Unsupported).
+    pub fn save_png(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        use image::{error::*, ColorType, ImageFormat};
+        use wgpu::TextureFormat::*;
+
+        let (texels, color) = match self.format {
+            Rgba8Unorm | Rgba8UnormSrgb => (self.texels.clone(), ColorType::Rgba8),
+            Bgra8Unorm | Bgra8UnormSrgb => {
+                let mut texels = self.texels.clone();
+                for c in texels.chunks_exact_mut(4) {
+                    c.swap(0, 2);
+                }
+                (texels, ColorType::Rgba8)
+            }
+            R8Unorm => (self.texels.clone(), ColorType::L8),
+            Rg8Unorm => (self.texels.clone(), ColorType::La8),
+            Rgba16Float => {
+                let texels = self
+                    .texels
+                    .chunks_exact(2)
+                    .map(|c| f16_to_f32(u16::from_le_bytes([c[0], c[1]])))
+                    .map(|c| (crate::math::clamp01(c) * 255.0).round() as u8)
+                    .collect();
+                (texels, ColorType::Rgba8)
+            }
+            format => {
+                let hint = ImageFormatHint::Exact(ImageFormat::Png);
+                let kind = UnsupportedErrorKind::GenericFeature(format!("{:?} texels", format));
+                let err = UnsupportedError::from_format_and_kind(hint, kind);
+                return Err(ImageError::Unsupported(err));
+            }
         };
 
-        Ok(Self::new(format, texels, width, height))
+        let format = ImageFormat::Png;
+        image::save_buffer_with_format(path, &texels, self.width, self.height, color, format)
     }
 
-    pub fn srgb_premul(path: impl AsRef<Path>) -> ImageResult<Self> {
+    pub fn linear(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::open(path, false, Channels::Rgba8)
+    }
+
+    pub fn srgb_premul(path: impl AsRef<Path>) -> Result<Self, Error> {
         Self::srgb(path).map(|m| m.premul())
     }
 
-    pub fn srgb(path: impl AsRef<Path>) -> ImageResult<Self> {
-        let source = image::open(path)?;
-
-        let (format, (width, height), texels) = match source {
-            ImageRgba8(m) => (
-                wgpu::TextureFormat::Rgba8UnormSrgb,
-                m.dimensions(),
-                m.into_raw(),
-            ),
-            ImageBgra8(m) => (
-                wgpu::TextureFormat::Bgra8UnormSrgb,
-                m.dimensions(),
-                m.into_raw(),
-            ),
-            _ => unimplemented!(),
+    pub fn srgb(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::open(path, true, Channels::Rgba8)
+    }
+
+    /// Loads an image file, see [`ImageSource::from_dynamic`].
+    pub fn open(path: impl AsRef<Path>, srgb: bool, channels: Channels) -> Result<Self, Error> {
+        Ok(Self::from_dynamic(image::open(path)?, srgb, channels))
+    }
+
+    /// Loads an encoded image, guessing the format from its contents.
+    pub fn from_memory(bytes: &[u8], srgb: bool, channels: Channels) -> Result<Self, Error> {
+        let image = image::load_from_memory(bytes)?;
+        Ok(Self::from_dynamic(image, srgb, channels))
+    }
+
+    /// Loads an encoded image, guessing the format from its contents.
+    pub fn from_reader(
+        reader: impl BufRead + Seek,
+        srgb: bool,
+        channels: Channels,
+    ) -> Result<Self, Error> {
+        let image = image::io::Reader::new(reader)
+            .with_guessed_format()?
+            .decode()?;
+        Ok(Self::from_dynamic(image, srgb, channels))
+    }
+
+    /// Converts a decoded image.
+    ///
+    /// `srgb` picks sRGB formats for 8-bit color. Images kept by
+    /// [`Channels::Native`] have no sRGB format, so with `srgb` their gray or
+    /// color values are converted to linear instead; 8-bit gray loses
+    /// precision in dark values this way.
+    pub fn from_dynamic(image: DynamicImage, srgb: bool, channels: Channels) -> Self {
+        use wgpu::TextureFormat::*;
+
+        let (width, height) = image.dimensions();
+        let (rgba, bgra) = if srgb {
+            (Rgba8UnormSrgb, Bgra8UnormSrgb)
+        } else {
+            (Rgba8Unorm, Bgra8Unorm)
+        };
+
+        let (format, texels) = match (image, channels) {
+            (ImageRgba8(m), _) => (rgba, m.into_raw()),
+            (ImageBgra8(m), _) => (bgra, m.into_raw()),
+
+            (ImageLuma8(m), Channels::Native) => {
+                let mut texels = m.into_raw();
+                if srgb {
+                    linearize(&mut texels, 1);
+                }
+                (R8Unorm, texels)
+            }
+            (ImageLumaA8(m), Channels::Native) => {
+                let mut texels = m.into_raw();
+                if srgb {
+                    linearize(&mut texels, 2);
+                }
+                (Rg8Unorm, texels)
+            }
+            (image @ ImageLuma16(_), Channels::Native)
+            | (image @ ImageLumaA16(_), Channels::Native)
+            | (image @ ImageRgb16(_), Channels::Native)
+            | (image @ ImageRgba16(_), Channels::Native) => {
+                let texels = image
+                    .to_rgba16()
+                    .into_raw()
+                    .chunks_exact(4)
+                    .flat_map(|c| {
+                        let color = |c: u16| {
+                            let c = c as f32 / 65535.0;
+                            if srgb {
+                                srgb_to_linear(c)
+                            } else {
+                                c
+                            }
+                        };
+                        let alpha = c[3] as f32 / 65535.0;
+                        let texel = [color(c[0]), color(c[1]), color(c[2]), alpha];
+                        let mut bytes = [0; 8];
+                        for (i, &c) in texel.iter().enumerate() {
+                            let half = f32_to_f16(c).to_le_bytes();
+                            bytes[2 * i..2 * i + 2].copy_from_slice(&half);
+                        }
+                        bytes.to_vec()
+                    })
+                    .collect();
+                (Rgba16Float, texels)
+            }
+
+            (image, _) => (rgba, image.to_rgba().into_raw()),
         };
 
-        Ok(Self::new(format, texels, width, height))
+        Self::new(format, texels, width, height)
+    }
+}
+
+/// How [`ImageSource`] loaders store images that aren't 8-bit RGBA or BGRA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Channels {
+    /// Expands everything to 8-bit RGBA.
+    Rgba8,
+    /// Keeps gray images as `R8Unorm`, gray with alpha as `Rg8Unorm` and
+    /// 16-bit images as `Rgba16Float`. RGB is always expanded to RGBA.
+    Native,
+}
+
+pub(crate) fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts the first channel of each `stride` bytes from sRGB to linear.
+fn linearize(texels: &mut [u8], stride: usize) {
+    for c in texels.iter_mut().step_by(stride) {
+        *c = (srgb_to_linear(*c as f32 / 255.0) * 255.0).round() as u8;
+    }
+}
+
+pub(crate) fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Rounds to the nearest half float, for values in `0.0..=1.0`.
+pub(crate) fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xFF) as i32 - 127 + 15;
+    let mantissa = bits & 0x007F_FFFF;
+
+    if exp <= 0 {
+        // subnormal or zero
+        if exp < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exp) as u32;
+        let half = (mantissa >> shift) + ((mantissa >> (shift - 1)) & 1);
+        sign | half as u16
+    } else if exp >= 0x1F {
+        // overflow to infinity
+        sign | 0x7C00
+    } else {
+        let half = ((exp as u32) << 10) | (mantissa >> 13);
+        // round to nearest, a carry into the exponent is still correct
+        sign | (half + ((mantissa >> 12) & 1)) as u16
+    }
+}
+
+/// Applies `f` to every `Rgba16Float` texel.
+fn map_half(texels: &mut [u8], f: impl Fn([f32; 4]) -> [f32; 4]) {
+    for texel in texels.chunks_exact_mut(8) {
+        let mut rgba = [0.0; 4];
+        for (i, c) in rgba.iter_mut().enumerate() {
+            *c = f16_to_f32(u16::from_le_bytes([texel[2 * i], texel[2 * i + 1]]));
+        }
+        for (i, &c) in f(rgba).iter().enumerate() {
+            texel[2 * i..2 * i + 2].copy_from_slice(&f32_to_f16(c).to_le_bytes());
+        }
+    }
+}
+
+pub(crate) fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((half >> 10) & 0x1F) as i32;
+    let mantissa = (half & 0x3FF) as f32;
+
+    sign * match exp {
+        0 => mantissa * 2f32.powi(-24),
+        0x1F => std::f32::INFINITY,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exp - 15),
     }
 }
 
@@ -98,6 +353,7 @@ pub struct Image {
     pub format: wgpu::TextureFormat,
     pub width: u32,
     pub height: u32,
+    pub mip_level_count: u32,
 }
 
 impl Image {
@@ -107,57 +363,197 @@ impl Image {
         device: &wgpu::Device,
         source: &ImageSource,
     ) -> Self {
-        let texels = device.create_buffer_with_data(&source.texels, wgpu::BufferUsage::COPY_SRC);
+        Self::with_mipmaps(label, encoder, device, source, Mipmaps::None)
+    }
 
-        let size = wgpu::Extent3d {
-            width: source.width,
-            height: source.height,
-            depth: 1,
-        };
+    /// Uploads `source` and the mip levels generated from it.
+    pub fn with_mipmaps<'a>(
+        label: impl Into<Option<&'a str>>,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        source: &ImageSource,
+        mipmaps: Mipmaps,
+    ) -> Self {
+        let levels = mipmaps.generate(source);
+        Self::with_levels(label, encoder, device, source, &levels)
+    }
+
+    /// Uploads `base` and prebuilt mip `levels`, each half the size of the previous one.
+    pub fn with_levels<'a>(
+        label: impl Into<Option<&'a str>>,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        base: &ImageSource,
+        levels: &[ImageSource],
+    ) -> Self {
+        let mip_level_count = levels.len() as u32 + 1;
 
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: label.into(),
-            size,
+            size: wgpu::Extent3d {
+                width: base.width,
+                height: base.height,
+                depth: 1,
+            },
             array_layer_count: 1,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: source.format,
+            format: base.format,
             usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
         });
 
-        let src = wgpu::BufferCopyView {
-            buffer: &texels,
-            offset: 0,
-            bytes_per_row: size.width * 4,
-            rows_per_image: 0,
-        };
-        let dst = wgpu::TextureCopyView {
-            texture: &texture,
-            mip_level: 0,
-            array_layer: 0,
-            origin: wgpu::Origin3d::ZERO,
-        };
-        encoder.copy_buffer_to_texture(src, dst, size);
+        let origin = wgpu::Origin3d::ZERO;
+        write_texels(encoder, device, &texture, 0, origin, base);
+        for (level, source) in levels.iter().enumerate() {
+            write_texels(encoder, device, &texture, level as u32 + 1, origin, source);
+        }
 
         Self {
             texture,
-            format: source.format,
-            width: source.width,
-            height: source.height,
+            format: base.format,
+            width: base.width,
+            height: base.height,
+            mip_level_count,
         }
     }
 
+    /// Decodes a block compressed image with its mip levels and uploads it
+    /// with premultiplied alpha.
+    ///
+    /// wgpu 0.5 has no block compressed formats, so the levels are decoded to
+    /// straight alpha RGBA8 on the CPU with [`Compressed::decode`] and
+    /// premultiplied before uploading.
+    pub fn compressed<'a>(
+        label: impl Into<Option<&'a str>>,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        compressed: &Compressed,
+    ) -> Self {
+        let levels: Vec<_> = compressed
+            .decode()
+            .into_iter()
+            .map(ImageSource::premul)
+            .collect();
+        Self::with_levels(label, encoder, device, &levels[0], &levels[1..])
+    }
+
+    /// Overwrites the base level at `x`, `y` with `source`.
+    ///
+    /// Mip levels aren't updated. Panics if the region is out of bounds or
+    /// the format differs.
+    pub fn write_region(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        x: u32,
+        y: u32,
+        source: &ImageSource,
+    ) {
+        assert_eq!(self.format, source.format, "format mismatch");
+        assert!(
+            x + source.width <= self.width && y + source.height <= self.height,
+            "region out of bounds"
+        );
+
+        let origin = wgpu::Origin3d { x, y, z: 0 };
+        write_texels(encoder, device, &self.texture, 0, origin, source);
+    }
+
     pub fn srgb_premul(
         encoder: &mut wgpu::CommandEncoder,
         device: &wgpu::Device,
         path: impl AsRef<Path>,
-    ) -> ImageResult<Self> {
+    ) -> Result<Self, Error> {
         let source = ImageSource::srgb_premul(path)?;
         Ok(Self::new(None, encoder, device, &source))
     }
 }
 
+/// Size of a texel of `format` in bytes.
+///
+/// Panics for depth formats without a fixed size.
+pub fn bytes_per_texel(format: wgpu::TextureFormat) -> u32 {
+    use wgpu::TextureFormat::*;
+
+    match format {
+        R8Unorm | R8Snorm | R8Uint | R8Sint => 1,
+
+        R16Uint | R16Sint | R16Float => 2,
+        Rg8Unorm | Rg8Snorm | Rg8Uint | Rg8Sint => 2,
+
+        R32Uint | R32Sint | R32Float => 4,
+        Rg16Uint | Rg16Sint | Rg16Float => 4,
+        Rgba8Unorm | Rgba8UnormSrgb | Rgba8Snorm | Rgba8Uint | Rgba8Sint => 4,
+        Bgra8Unorm | Bgra8UnormSrgb => 4,
+        Rgb10a2Unorm | Rg11b10Float => 4,
+        Depth32Float => 4,
+
+        Rg32Uint | Rg32Sint | Rg32Float => 8,
+        Rgba16Uint | Rgba16Sint | Rgba16Float => 8,
+
+        Rgba32Uint | Rgba32Sint | Rgba32Float => 16,
+
+        format => panic!("{:?} has no fixed texel size", format),
+    }
+}
+
+fn pad_rows(texels: &[u8], row: usize, padded_row: usize) -> Vec<u8> {
+    let mut padded = vec![0; texels.len() / row * padded_row];
+    for (dst, src) in padded
+        .chunks_exact_mut(padded_row)
+        .zip(texels.chunks_exact(row))
+    {
+        dst[..row].copy_from_slice(src);
+    }
+    padded
+}
+
+/// Copies `source` into `texture` at `origin`, padding rows to the copy alignment.
+fn write_texels(
+    encoder: &mut wgpu::CommandEncoder,
+    device: &wgpu::Device,
+    texture: &wgpu::Texture,
+    mip_level: u32,
+    origin: wgpu::Origin3d,
+    source: &ImageSource,
+) {
+    let row = (source.width * bytes_per_texel(source.format)) as wgpu::BufferAddress;
+    let padded_row = align(row, COPY_ROW_ALIGNMENT);
+    assert_eq!(
+        source.texels.len() as wgpu::BufferAddress,
+        row * source.height as wgpu::BufferAddress,
+        "texels don't match the size and format"
+    );
+
+    let usage = wgpu::BufferUsage::COPY_SRC;
+    let texels = if row == padded_row {
+        device.create_buffer_with_data(&source.texels, usage)
+    } else {
+        let padded = pad_rows(&source.texels, row as usize, padded_row as usize);
+        device.create_buffer_with_data(&padded, usage)
+    };
+
+    let src = wgpu::BufferCopyView {
+        buffer: &texels,
+        offset: 0,
+        bytes_per_row: padded_row as u32,
+        rows_per_image: 0,
+    };
+    let dst = wgpu::TextureCopyView {
+        texture,
+        mip_level,
+        array_layer: 0,
+        origin,
+    };
+    let size = wgpu::Extent3d {
+        width: source.width,
+        height: source.height,
+        depth: 1,
+    };
+    encoder.copy_buffer_to_texture(src, dst, size);
+}
+
 pub struct ImageLoader {
     encoder: wgpu::CommandEncoder,
 }
@@ -173,11 +569,136 @@ impl ImageLoader {
         &mut self,
         device: &wgpu::Device,
         path: impl AsRef<Path>,
-    ) -> ImageResult<Image> {
+    ) -> Result<Image, Error> {
         Image::srgb_premul(&mut self.encoder, device, path)
     }
 
+    /// Loads a premultiplied sRGB image with a mip chain.
+    pub fn srgb_premul_mipmapped(
+        &mut self,
+        device: &wgpu::Device,
+        path: impl AsRef<Path>,
+    ) -> Result<Image, Error> {
+        let source = ImageSource::srgb_premul(path)?;
+        Ok(self.upload(device, &source, Mipmaps::Premultiplied))
+    }
+
+    /// Loads a KTX2 or DDS file with its mip levels, decoded to RGBA8 on the
+    /// CPU with premultiplied alpha, see [`Image::compressed`].
+    pub fn compressed(
+        &mut self,
+        device: &wgpu::Device,
+        path: impl AsRef<Path>,
+    ) -> Result<Image, Error> {
+        let compressed = Compressed::open(path)?;
+        Ok(Image::compressed(
+            None,
+            &mut self.encoder,
+            device,
+            &compressed,
+        ))
+    }
+
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        source: &ImageSource,
+        mipmaps: Mipmaps,
+    ) -> Image {
+        Image::with_mipmaps(None, &mut self.encoder, device, source, mipmaps)
+    }
+
     pub fn finish(self) -> wgpu::CommandBuffer {
         self.encoder.finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayAlphaImage, GrayImage, ImageBuffer, Rgb, RgbImage};
+
+    #[test]
+    fn padded_rows() {
+        let padded = pad_rows(&[1, 2, 3, 4, 5, 6], 3, 4);
+        assert_eq!(padded, [1, 2, 3, 0, 4, 5, 6, 0]);
+        assert_eq!(bytes_per_texel(wgpu::TextureFormat::Rgba16Float), 8);
+    }
+
+    #[test]
+    fn half_floats() {
+        for &value in &[0.0, 1.0, 0.5, 0.25, 1.0 / 3.0, 0.001, 1e-6] {
+            let half = f32_to_f16(value);
+            assert!((f16_to_f32(half) - value).abs() <= value * 0.001 + 1e-7);
+        }
+        assert_eq!(f32_to_f16(1.0), 0x3C00);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+    }
+
+    #[test]
+    fn save_png_formats() {
+        let path = std::env::temp_dir().join("pixi_save_png.png");
+
+        let gray = ImageSource::new(wgpu::TextureFormat::R8Unorm, vec![10, 200], 2, 1);
+        gray.save_png(&path).unwrap();
+        assert_eq!(image::open(&path).unwrap().to_luma().into_raw(), [10, 200]);
+
+        let half = [0.0, 0.5, 1.0, 2.0].iter().map(|&c| f32_to_f16(c));
+        let texels = half.flat_map(|c| c.to_le_bytes().to_vec()).collect();
+        let float = ImageSource::new(wgpu::TextureFormat::Rgba16Float, texels, 1, 1);
+        float.save_png(&path).unwrap();
+        assert_eq!(
+            image::open(&path).unwrap().to_rgba().into_raw(),
+            [0, 128, 255, 255]
+        );
+
+        let gray_alpha = ImageSource::new(wgpu::TextureFormat::Rg8Unorm, vec![10, 200], 1, 1);
+        gray_alpha.save_png(&path).unwrap();
+        let saved = image::open(&path).unwrap();
+        assert_eq!(saved.color(), image::ColorType::La8);
+        assert_eq!(saved.to_luma_alpha().into_raw(), [10, 200]);
+
+        let depth = ImageSource::new(wgpu::TextureFormat::R32Float, vec![0; 4], 1, 1);
+        let err = depth.save_png(&path).unwrap_err();
+        assert!(matches!(err, image::ImageError::Unsupported(_)));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn expand_to_rgba() {
+        let gray = ImageLuma8(GrayImage::from_raw(2, 1, vec![10, 200]).unwrap());
+        let source = ImageSource::from_dynamic(gray, true, Channels::Rgba8);
+        assert_eq!(source.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(source.texels, [10, 10, 10, 255, 200, 200, 200, 255]);
+
+        let rgb = ImageRgb8(RgbImage::from_raw(1, 1, vec![1, 2, 3]).unwrap());
+        let source = ImageSource::from_dynamic(rgb, false, Channels::Native);
+        assert_eq!(source.format, wgpu::TextureFormat::Rgba8Unorm);
+        assert_eq!(source.texels, [1, 2, 3, 255]);
+    }
+
+    #[test]
+    fn native_channels() {
+        let gray = ImageLumaA8(GrayAlphaImage::from_raw(1, 1, vec![200, 128]).unwrap());
+        let source = ImageSource::from_dynamic(gray, false, Channels::Native).premul();
+        assert_eq!(source.format, wgpu::TextureFormat::Rg8Unorm);
+        assert_eq!(source.texels, [100, 128]);
+
+        // gray has no sRGB format, so it's linearized and alpha is kept
+        let gray = ImageLumaA8(GrayAlphaImage::from_raw(1, 1, vec![188, 188]).unwrap());
+        let source = ImageSource::from_dynamic(gray, true, Channels::Native);
+        assert_eq!(source.format, wgpu::TextureFormat::Rg8Unorm);
+        assert_eq!(source.texels, [128, 188]);
+
+        // only formats with known alpha are changed
+        let float = ImageSource::new(wgpu::TextureFormat::R32Float, vec![0, 0, 128, 63], 1, 1);
+        assert_eq!(float.premul().unpremul().texels, [0, 0, 128, 63]);
+
+        let deep: ImageBuffer<Rgb<u16>, _> =
+            ImageBuffer::from_raw(1, 1, vec![0, 65535, 0]).unwrap();
+        let source = ImageSource::from_dynamic(ImageRgb16(deep), false, Channels::Native);
+        assert_eq!(source.format, wgpu::TextureFormat::Rgba16Float);
+        assert_eq!(source.texels, [0, 0, 0, 0x3C, 0, 0, 0, 0x3C]);
+    }
+}
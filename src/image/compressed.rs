@@ -0,0 +1,700 @@
+//! CPU decoding of BC1, BC3 and BC7 images from KTX2 and DDS containers.
+//!
+//! wgpu 0.5 has no block compressed texture formats, so the payloads are only
+//! parsed here and decoded to RGBA8 on the CPU before uploading. Uploading
+//! them as BC textures where the adapter supports it needs a newer wgpu.
+
+use super::ImageSource;
+use crate::Error;
+use std::{convert::TryInto, path::Path};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockFormat {
+    /// DXT1, RGB with 1-bit alpha.
+    Bc1,
+    /// DXT5, RGB with interpolated alpha.
+    Bc3,
+    Bc7,
+}
+
+impl BlockFormat {
+    pub fn block_size(self) -> usize {
+        match self {
+            BlockFormat::Bc1 => 8,
+            BlockFormat::Bc3 | BlockFormat::Bc7 => 16,
+        }
+    }
+
+    /// Size of a `width` by `height` level in bytes.
+    pub fn level_size(self, width: u32, height: u32) -> usize {
+        let blocks = (width as usize + 3) / 4 * ((height as usize + 3) / 4);
+        blocks * self.block_size()
+    }
+}
+
+/// Block compressed image with its mip levels, largest first.
+#[derive(Clone, Debug)]
+pub struct Compressed {
+    pub format: BlockFormat,
+    pub srgb: bool,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+const DDS_MAGIC: &[u8] = b"DDS ";
+const KTX2_MAGIC: &[u8] = &[
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+fn format_error(message: impl Into<String>) -> Error {
+    Error::Format(message.into())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    let slice = bytes
+        .get(offset..offset + 4)
+        .ok_or_else(|| format_error("truncated header"))?;
+    Ok(u32::from_le_bytes(slice.try_into().unwrap()))
+}
+
+/// Fails unless a `width` by `height` image can have `level_count` levels,
+/// checked before anything is allocated for them.
+fn check_levels(width: u32, height: u32, level_count: u32) -> Result<(), Error> {
+    if width == 0 || height == 0 {
+        return Err(format_error("empty image"));
+    }
+    let max = 32 - width.max(height).leading_zeros();
+    if level_count == 0 || level_count > max {
+        return Err(format_error(format!(
+            "{} levels for a {}x{} image",
+            level_count, width, height
+        )));
+    }
+    Ok(())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Result<u64, Error> {
+    let slice = bytes
+        .get(offset..offset + 8)
+        .ok_or_else(|| format_error("truncated header"))?;
+    Ok(u64::from_le_bytes(slice.try_into().unwrap()))
+}
+
+impl Compressed {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_memory(&std::fs::read(path)?)
+    }
+
+    /// Parses a KTX2 or DDS file, detected by its magic number.
+    pub fn from_memory(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.starts_with(KTX2_MAGIC) {
+            Self::parse_ktx2(bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            Self::parse_dds(bytes)
+        } else {
+            Err(format_error("not a KTX2 or DDS file"))
+        }
+    }
+
+    pub fn parse_dds(bytes: &[u8]) -> Result<Self, Error> {
+        const MIPMAP_COUNT: u32 = 0x2_0000;
+
+        if !bytes.starts_with(DDS_MAGIC) || u32_at(bytes, 4)? != 124 {
+            return Err(format_error("not a DDS file"));
+        }
+
+        let flags = u32_at(bytes, 8)?;
+        let height = u32_at(bytes, 12)?;
+        let width = u32_at(bytes, 16)?;
+        let level_count = match u32_at(bytes, 28)? {
+            count if flags & MIPMAP_COUNT != 0 && count > 0 => count,
+            _ => 1,
+        };
+
+        let four_cc = bytes
+            .get(84..88)
+            .ok_or_else(|| format_error("truncated header"))?;
+        let (format, srgb, offset) = match four_cc {
+            b"DXT1" => (BlockFormat::Bc1, false, 128),
+            b"DXT5" => (BlockFormat::Bc3, false, 128),
+            b"DX10" => {
+                let format = match u32_at(bytes, 128)? {
+                    71 => (BlockFormat::Bc1, false),
+                    72 => (BlockFormat::Bc1, true),
+                    77 => (BlockFormat::Bc3, false),
+                    78 => (BlockFormat::Bc3, true),
+                    98 => (BlockFormat::Bc7, false),
+                    99 => (BlockFormat::Bc7, true),
+                    dxgi => return Err(format_error(format!("unsupported DXGI format {}", dxgi))),
+                };
+                (format.0, format.1, 148)
+            }
+            four_cc => {
+                let four_cc = String::from_utf8_lossy(four_cc);
+                return Err(format_error(format!(
+                    "unsupported DDS format {:?}",
+                    four_cc
+                )));
+            }
+        };
+
+        check_levels(width, height, level_count)?;
+        let mut levels = Vec::with_capacity(level_count as usize);
+        let mut offset: usize = offset;
+        for level in 0..level_count {
+            let size = format.level_size((width >> level).max(1), (height >> level).max(1));
+            let data = offset
+                .checked_add(size)
+                .and_then(|end| bytes.get(offset..end))
+                .ok_or_else(|| format_error("truncated DDS data"))?;
+            levels.push(data.to_vec());
+            offset += size;
+        }
+
+        Ok(Self {
+            format,
+            srgb,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    pub fn parse_ktx2(bytes: &[u8]) -> Result<Self, Error> {
+        if !bytes.starts_with(KTX2_MAGIC) {
+            return Err(format_error("not a KTX2 file"));
+        }
+
+        let (format, srgb) = match u32_at(bytes, 12)? {
+            131 | 133 => (BlockFormat::Bc1, false),
+            132 | 134 => (BlockFormat::Bc1, true),
+            137 => (BlockFormat::Bc3, false),
+            138 => (BlockFormat::Bc3, true),
+            145 => (BlockFormat::Bc7, false),
+            146 => (BlockFormat::Bc7, true),
+            vk => return Err(format_error(format!("unsupported Vulkan format {}", vk))),
+        };
+
+        let width = u32_at(bytes, 20)?;
+        let height = u32_at(bytes, 24)?;
+        let (depth, layers, faces) = (u32_at(bytes, 28)?, u32_at(bytes, 32)?, u32_at(bytes, 36)?);
+        if depth > 1 || layers > 1 || faces != 1 {
+            return Err(format_error("only 2D KTX2 textures are supported"));
+        }
+        if u32_at(bytes, 44)? != 0 {
+            return Err(format_error("supercompressed KTX2 is not supported"));
+        }
+        let level_count = u32_at(bytes, 40)?.max(1);
+        check_levels(width, height, level_count)?;
+
+        let mut levels = Vec::with_capacity(level_count as usize);
+        for level in 0..level_count {
+            let index = 80 + level as usize * 24;
+            let offset = u64_at(bytes, index)? as usize;
+            let length = u64_at(bytes, index + 8)? as usize;
+
+            let size = format.level_size((width >> level).max(1), (height >> level).max(1));
+            if length != size {
+                return Err(format_error(format!(
+                    "level {} has {} bytes",
+                    level, length
+                )));
+            }
+            let data = offset
+                .checked_add(size)
+                .and_then(|end| bytes.get(offset..end))
+                .ok_or_else(|| format_error("truncated KTX2 data"))?;
+            levels.push(data.to_vec());
+        }
+
+        Ok(Self {
+            format,
+            srgb,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    /// Decodes all levels to 8-bit RGBA with straight alpha.
+    pub fn decode(&self) -> Vec<ImageSource> {
+        let format = if self.srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+
+        let levels = self.levels.iter().enumerate();
+        levels
+            .map(|(level, data)| {
+                let width = (self.width >> level).max(1);
+                let height = (self.height >> level).max(1);
+                let texels = decode_blocks(self.format, data, width, height);
+                ImageSource::new(format, texels, width, height)
+            })
+            .collect()
+    }
+}
+
+fn decode_blocks(format: BlockFormat, data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = (width + 3) / 4;
+
+    let mut texels = vec![0; width * height * 4];
+    for (index, block) in data.chunks_exact(format.block_size()).enumerate() {
+        let mut out = [[0; 4]; 16];
+        match format {
+            BlockFormat::Bc1 => decode_bc1(block, &mut out, false),
+            BlockFormat::Bc3 => decode_bc3(block, &mut out),
+            BlockFormat::Bc7 => decode_bc7(block, &mut out),
+        }
+
+        let (bx, by) = (index % blocks_x * 4, index / blocks_x * 4);
+        for (i, texel) in out.iter().enumerate() {
+            let (x, y) = (bx + i % 4, by + i / 4);
+            if x < width && y < height {
+                let offset = (y * width + x) * 4;
+                texels[offset..offset + 4].copy_from_slice(texel);
+            }
+        }
+    }
+    texels
+}
+
+fn rgb565(c: u16) -> [u16; 3] {
+    let (r, g, b) = ((c >> 11) & 31, (c >> 5) & 63, c & 31);
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// BC3 color blocks always use four colors.
+fn decode_bc1(block: &[u8], out: &mut [[u8; 4]; 16], four_colors: bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+
+    let mix = |wa: u16, wb: u16, div: u16| {
+        let c = |i: usize| ((wa * a[i] + wb * b[i]) / div) as u8;
+        [c(0), c(1), c(2), 255]
+    };
+    let palette = if c0 > c1 || four_colors {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(2, 1, 3), mix(1, 2, 3)]
+    } else {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(1, 1, 2), [0, 0, 0, 0]]
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, texel) in out.iter_mut().enumerate() {
+        *texel = palette[(indices >> (2 * i) & 3) as usize];
+    }
+}
+
+fn decode_bc3(block: &[u8], out: &mut [[u8; 4]; 16]) {
+    decode_bc1(&block[8..], out, true);
+
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for (k, alpha) in palette.iter_mut().enumerate().skip(2) {
+            *alpha = ((8 - k as u32) * a0 + (k as u32 - 1) * a1) / 7;
+        }
+    } else {
+        for (k, alpha) in palette.iter_mut().enumerate().take(6).skip(2) {
+            *alpha = ((6 - k as u32) * a0 + (k as u32 - 1) * a1) / 5;
+        }
+    }
+
+    let mut bytes = [0; 8];
+    bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bytes);
+    for (i, texel) in out.iter_mut().enumerate() {
+        texel[3] = palette[(indices >> (3 * i) & 7) as usize] as u8;
+    }
+}
+
+/// Subsets, partition, rotation, index selection, color and alpha bits,
+/// endpoint or shared pbits and index bits of each mode.
+#[allow(clippy::type_complexity)]
+const BC7_MODES: [(usize, [u32; 5], bool, bool, [u32; 2]); 8] = [
+    (3, [4, 0, 0, 4, 0], true, false, [3, 0]),
+    (2, [6, 0, 0, 6, 0], false, true, [3, 0]),
+    (3, [6, 0, 0, 5, 0], false, false, [2, 0]),
+    (2, [6, 0, 0, 7, 0], true, false, [2, 0]),
+    (1, [0, 2, 1, 5, 6], false, false, [2, 3]),
+    (1, [0, 2, 0, 7, 8], false, false, [2, 2]),
+    (1, [0, 0, 0, 7, 7], true, false, [4, 0]),
+    (2, [6, 0, 0, 5, 5], true, false, [2, 0]),
+];
+
+/// Subset of each texel for two subsets, a bit per texel.
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, //
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000, //
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE, //
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, //
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A, //
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660, //
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, //
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22, //
+];
+
+/// Subset of each texel for three subsets, two bits per texel.
+const PARTITIONS_3: [u32; 64] = [
+    0xAA68_5050,
+    0x6A5A_5040,
+    0x5A5A_4200,
+    0x5450_A0A8, //
+    0xA5A5_0000,
+    0xA0A0_5050,
+    0x5555_A0A0,
+    0x5A5A_5050, //
+    0xAA55_0000,
+    0xAA55_5500,
+    0xAAAA_5500,
+    0x9090_9090, //
+    0x9494_9494,
+    0xA4A4_A4A4,
+    0xA9A5_9450,
+    0x2A0A_4250, //
+    0xA594_5040,
+    0x0A42_5054,
+    0xA5A5_A500,
+    0x55A0_A0A0, //
+    0xA8A8_5454,
+    0x6A6A_4040,
+    0xA4A4_5000,
+    0x1A1A_0500, //
+    0x0050_A4A4,
+    0xAAA5_9090,
+    0x1469_6914,
+    0x6969_1400, //
+    0xA085_85A0,
+    0xAA82_1414,
+    0x50A4_A450,
+    0x6A5A_0200, //
+    0xA9A5_8000,
+    0x5090_A0A8,
+    0xA8A0_9050,
+    0x2424_2424, //
+    0x00AA_5500,
+    0x2492_4924,
+    0x2449_9224,
+    0x50A5_0A50, //
+    0x500A_A550,
+    0xAAAA_4444,
+    0x6666_0000,
+    0xA5A0_A5A0, //
+    0x50A0_50A0,
+    0x6928_6928,
+    0x44AA_AA44,
+    0x6666_6600, //
+    0xAA44_4444,
+    0x54A8_54A8,
+    0x9580_9580,
+    0x9696_9600, //
+    0xA854_54A8,
+    0x8095_9580,
+    0xAA14_1414,
+    0x9696_0000, //
+    0xAAAA_1414,
+    0xA050_50A0,
+    0xA0A5_A5A0,
+    0x9600_0000, //
+    0x4080_4080,
+    0xA9A8_A9A8,
+    0xAAAA_AA44,
+    0x2A4A_5254, //
+];
+
+/// Anchor texel of the second subset of two.
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, //
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2, //
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, //
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15, //
+];
+
+/// Anchor texels of the second and third subset of three.
+const ANCHORS_3: [[u8; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, //
+        3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15, //
+        8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, //
+        3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3, //
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, //
+        15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8, //
+        15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, //
+        15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8, //
+    ],
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Block bits, read from the least significant one.
+struct Bits(u128);
+
+impl Bits {
+    fn take(&mut self, count: u32) -> u32 {
+        let value = (self.0 & ((1 << count) - 1)) as u32;
+        self.0 >>= count;
+        value
+    }
+}
+
+fn interpolate(e0: u32, e1: u32, index: u32, bits: u32) -> u8 {
+    let weight = match bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    };
+    (((64 - weight) * e0 + weight * e1 + 32) >> 6) as u8
+}
+
+/// Expands a `bits` wide value to 8 bits.
+fn unquantize(value: u32, bits: u32) -> u32 {
+    let value = value << (8 - bits);
+    value | (value >> bits)
+}
+
+fn decode_bc7(block: &[u8], out: &mut [[u8; 4]; 16]) {
+    let mut bits = Bits(u128::from_le_bytes(block.try_into().unwrap()));
+
+    let mode_index = bits.0.trailing_zeros() as usize;
+    if mode_index >= BC7_MODES.len() {
+        // reserved mode
+        *out = [[0; 4]; 16];
+        return;
+    }
+    bits.take(mode_index as u32 + 1);
+    let (subsets, mode_bits, endpoint_pbits, shared_pbits, [index_bits, index2_bits]) =
+        BC7_MODES[mode_index];
+    let [partition_bits, rotation_bits, selector_bits, color_bits, alpha_bits] = mode_bits;
+
+    let partition = bits.take(partition_bits) as usize;
+    let rotation = bits.take(rotation_bits);
+    let selector = bits.take(selector_bits);
+
+    let endpoints = subsets * 2;
+    let mut colors = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for color in &mut colors[..endpoints] {
+            color[channel] = bits.take(color_bits);
+        }
+    }
+    for color in &mut colors[..endpoints] {
+        color[3] = bits.take(alpha_bits);
+    }
+
+    let (mut color_bits, mut alpha_bits) = (color_bits, alpha_bits);
+    if endpoint_pbits || shared_pbits {
+        let per_pbit = if endpoint_pbits { 1 } else { 2 };
+        for pair in colors[..endpoints].chunks_mut(per_pbit) {
+            let pbit = bits.take(1);
+            for color in pair {
+                for c in color.iter_mut() {
+                    *c = (*c << 1) | pbit;
+                }
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    for color in &mut colors[..endpoints] {
+        for c in &mut color[..3] {
+            *c = unquantize(*c, color_bits);
+        }
+        color[3] = if alpha_bits > 0 {
+            unquantize(color[3], alpha_bits)
+        } else {
+            255
+        };
+    }
+
+    let subset = |i: usize| match subsets {
+        2 => (PARTITIONS_2[partition] >> i & 1) as usize,
+        3 => (PARTITIONS_3[partition] >> (2 * i) & 3) as usize,
+        _ => 0,
+    };
+    let is_anchor = |i: usize| match subsets {
+        2 => i == 0 || i == ANCHORS_2[partition] as usize,
+        3 => {
+            i == 0
+                || ANCHORS_3
+                    .iter()
+                    .any(|anchors| i == anchors[partition] as usize)
+        }
+        _ => i == 0,
+    };
+
+    let mut indices = [0; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        *index = bits.take(index_bits - is_anchor(i) as u32);
+    }
+    let mut indices2 = [0; 16];
+    if index2_bits > 0 {
+        for (i, index) in indices2.iter_mut().enumerate() {
+            *index = bits.take(index2_bits - (i == 0) as u32);
+        }
+    }
+
+    for (i, texel) in out.iter_mut().enumerate() {
+        let s = subset(i);
+        let (e0, e1) = (colors[2 * s], colors[2 * s + 1]);
+
+        let (color, alpha) = if index2_bits == 0 {
+            ((indices[i], index_bits), (indices[i], index_bits))
+        } else if selector == 0 {
+            ((indices[i], index_bits), (indices2[i], index2_bits))
+        } else {
+            ((indices2[i], index2_bits), (indices[i], index_bits))
+        };
+
+        for c in 0..3 {
+            texel[c] = interpolate(e0[c], e1[c], color.0, color.1);
+        }
+        texel[3] = interpolate(e0[3], e1[3], alpha.0, alpha.1);
+
+        match rotation {
+            1 => texel.swap(0, 3),
+            2 => texel.swap(1, 3),
+            3 => texel.swap(2, 3),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes bits from the least significant one.
+    struct Writer(u128, u32);
+
+    impl Writer {
+        fn put(&mut self, value: u32, count: u32) {
+            self.0 |= (value as u128) << self.1;
+            self.1 += count;
+        }
+    }
+
+    fn dds(four_cc: &[u8; 4], width: u32, height: u32, levels: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        bytes[..4].copy_from_slice(b"DDS ");
+        bytes[4..8].copy_from_slice(&124u32.to_le_bytes());
+        bytes[8..12].copy_from_slice(&0x2_1007u32.to_le_bytes());
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        bytes[28..32].copy_from_slice(&levels.to_le_bytes());
+        bytes[76..80].copy_from_slice(&32u32.to_le_bytes());
+        bytes[84..88].copy_from_slice(four_cc);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn bc1() {
+        // red and blue, texel 1 is a third of the way
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0b1000, 0, 0, 0b0100_0000];
+        let mut out = [[0; 4]; 16];
+        decode_bc1(&block, &mut out, false);
+        assert_eq!(out[0], [255, 0, 0, 255]);
+        assert_eq!(out[1], [170, 0, 85, 255]);
+        assert_eq!(out[15], [0, 0, 255, 255]);
+
+        // c0 <= c1 has transparent black
+        let block = [0x1F, 0x00, 0x00, 0xF8, 0b11, 0, 0, 0];
+        decode_bc1(&block, &mut out, false);
+        assert_eq!(out[0], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bc3_alpha() {
+        let mut block = [0; 16];
+        block[0] = 255;
+        block[1] = 0;
+        // texel 0 uses a0, texel 1 uses a1, texel 2 uses (6 * a0 + a1) / 7
+        block[2] = 0b1000_1000;
+        block[3] = 0b0000_0000;
+        let mut out = [[0; 4]; 16];
+        decode_bc3(&block, &mut out);
+        assert_eq!([out[0][3], out[1][3], out[2][3]], [255, 0, 218]);
+    }
+
+    #[test]
+    fn bc7_mode6() {
+        let mut w = Writer(0, 0);
+        w.put(1 << 6, 7);
+        for _ in 0..4 {
+            // e0 is 127 with pbit 1 and e1 is 0 with pbit 0
+            w.put(127, 7);
+            w.put(0, 7);
+        }
+        w.put(1, 1);
+        w.put(0, 1);
+        w.put(0, 3);
+        w.put(15, 4);
+        w.put(8, 4);
+        assert!(w.1 < 128);
+
+        let mut out = [[0; 4]; 16];
+        decode_bc7(&w.0.to_le_bytes(), &mut out);
+        assert_eq!(out[0], [255, 255, 255, 255]);
+        assert_eq!(out[1], [0, 0, 0, 0]);
+        assert_eq!(out[2], [120, 120, 120, 120]);
+        assert_eq!(out[3], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn dds_levels() {
+        let solid = [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0];
+        let data: Vec<u8> = solid.iter().chain(&solid).cloned().collect();
+        let file = dds(b"DXT1", 4, 2, 2, &data);
+
+        let compressed = Compressed::from_memory(&file).unwrap();
+        assert_eq!(compressed.format, BlockFormat::Bc1);
+        assert_eq!(compressed.levels.len(), 2);
+
+        let levels = compressed.decode();
+        assert_eq!((levels[1].width, levels[1].height), (2, 1));
+        assert_eq!(levels[1].texels, [255; 8]);
+
+        assert!(Compressed::from_memory(&file[..130]).is_err());
+        assert!(Compressed::from_memory(b"PNG").is_err());
+    }
+
+    #[test]
+    fn bogus_headers() {
+        // a 4x2 image has at most 3 levels
+        let data = [0; 64];
+        assert!(Compressed::from_memory(&dds(b"DXT1", 4, 2, 3, &data)).is_ok());
+        assert!(Compressed::from_memory(&dds(b"DXT1", 4, 2, 4, &data)).is_err());
+        assert!(Compressed::from_memory(&dds(b"DXT1", 4, 2, u32::MAX, &data)).is_err());
+        assert!(Compressed::from_memory(&dds(b"DXT1", 0, 2, 1, &data)).is_err());
+
+        // KTX2 level at an offset that overflows
+        let mut ktx2 = KTX2_MAGIC.to_vec();
+        ktx2.resize(104, 0);
+        ktx2[12..16].copy_from_slice(&131u32.to_le_bytes());
+        ktx2[20..24].copy_from_slice(&4u32.to_le_bytes());
+        ktx2[24..28].copy_from_slice(&4u32.to_le_bytes());
+        ktx2[36..40].copy_from_slice(&1u32.to_le_bytes());
+        ktx2[40..44].copy_from_slice(&1u32.to_le_bytes());
+        ktx2[80..88].copy_from_slice(&u64::MAX.to_le_bytes());
+        ktx2[88..96].copy_from_slice(&8u64.to_le_bytes());
+        assert!(Compressed::from_memory(&ktx2).is_err());
+
+        ktx2[80..88].copy_from_slice(&96u64.to_le_bytes());
+        assert!(Compressed::from_memory(&ktx2).is_ok());
+    }
+}
@@ -0,0 +1,238 @@
+use super::{f16_to_f32, f32_to_f16, linear_to_srgb, srgb_to_linear, ImageSource};
+
+/// Mip chain of an [`Image`](super::Image).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mipmaps {
+    /// Only the base level.
+    None,
+    /// Levels down to 1x1 from texels with premultiplied alpha.
+    Premultiplied,
+    /// Levels down to 1x1 from texels with straight alpha, color is weighted by alpha.
+    Straight,
+}
+
+impl Default for Mipmaps {
+    fn default() -> Self {
+        Mipmaps::None
+    }
+}
+
+impl Mipmaps {
+    /// Levels of a `width` by `height` image, including the base level.
+    pub fn level_count(self, width: u32, height: u32) -> u32 {
+        match self {
+            Mipmaps::None => 1,
+            _ => 32 - width.max(height).max(1).leading_zeros(),
+        }
+    }
+
+    /// Downsampled levels of `source`, without the base level.
+    ///
+    /// Formats [`downsample`] can't read only get the base level.
+    pub fn generate(self, source: &ImageSource) -> Vec<ImageSource> {
+        let premultiplied = match self {
+            Mipmaps::None => return Vec::new(),
+            Mipmaps::Premultiplied => true,
+            Mipmaps::Straight => false,
+        };
+
+        let count = self.level_count(source.width, source.height);
+        let mut levels: Vec<ImageSource> = Vec::with_capacity(count as usize - 1);
+        for _ in 1..count {
+            match downsample(levels.last().unwrap_or(source), premultiplied) {
+                Some(level) => levels.push(level),
+                None => break,
+            }
+        }
+        levels
+    }
+}
+
+/// Halves the size of `source` with a box filter, averaging in linear space.
+///
+/// The last row or column of odd sizes is dropped, as with GPU mip chains.
+/// Returns `None` for formats [`ImageSource`] loaders don't produce.
+pub fn downsample(source: &ImageSource, premultiplied: bool) -> Option<ImageSource> {
+    let channels = Channels::of(source.format)?;
+    let texels = channels.decode(&source.texels);
+    let n = channels.count;
+
+    let (sw, sh) = (source.width as usize, source.height as usize);
+    let (width, height) = ((sw / 2).max(1), (sh / 2).max(1));
+
+    let mut out = vec![0.0; width * height * n];
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0f32; 4];
+            let mut alpha_sum = 0.0;
+            for (dx, dy) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let sx = (2 * x + dx).min(sw - 1);
+                let sy = (2 * y + dy).min(sh - 1);
+                let texel = &texels[(sy * sw + sx) * n..][..n];
+
+                // straight color is weighted by its coverage
+                let weight = match channels.alpha {
+                    Some(a) if !premultiplied => texel[a],
+                    _ => 1.0,
+                };
+                for (i, (c, sum)) in texel.iter().zip(&mut sum).enumerate() {
+                    *sum += if Some(i) == channels.alpha {
+                        *c
+                    } else {
+                        c * weight
+                    };
+                }
+                alpha_sum += weight;
+            }
+
+            let texel = &mut out[(y * width + x) * n..][..n];
+            for (i, c) in texel.iter_mut().enumerate() {
+                *c = if Some(i) == channels.alpha {
+                    sum[i] / 4.0
+                } else if alpha_sum > 0.0 {
+                    sum[i] / alpha_sum
+                } else {
+                    0.0
+                };
+            }
+        }
+    }
+
+    let texels = channels.encode(&out);
+    let (width, height) = (width as u32, height as u32);
+    Some(ImageSource::new(source.format, texels, width, height))
+}
+
+/// Layout of the formats [`ImageSource`] produces.
+struct Channels {
+    count: usize,
+    alpha: Option<usize>,
+    srgb: bool,
+    half: bool,
+}
+
+impl Channels {
+    fn of(format: wgpu::TextureFormat) -> Option<Self> {
+        use wgpu::TextureFormat::*;
+
+        let (count, srgb, half) = match format {
+            R8Unorm => (1, false, false),
+            Rg8Unorm => (2, false, false),
+            Rgba8Unorm | Bgra8Unorm => (4, false, false),
+            Rgba8UnormSrgb | Bgra8UnormSrgb => (4, true, false),
+            Rgba16Float => (4, false, true),
+            _ => return None,
+        };
+        let alpha = if count % 2 == 0 {
+            Some(count - 1)
+        } else {
+            None
+        };
+
+        Some(Self {
+            count,
+            alpha,
+            srgb,
+            half,
+        })
+    }
+
+    fn is_color(&self, index: usize) -> bool {
+        self.srgb && Some(index % self.count) != self.alpha
+    }
+
+    fn decode(&self, texels: &[u8]) -> Vec<f32> {
+        if self.half {
+            let halfs = texels.chunks_exact(2);
+            return halfs
+                .map(|c| f16_to_f32(u16::from_le_bytes([c[0], c[1]])))
+                .collect();
+        }
+
+        let unorm = texels.iter().map(|&c| c as f32 / 255.0).enumerate();
+        unorm
+            .map(|(i, c)| {
+                if self.is_color(i) {
+                    srgb_to_linear(c)
+                } else {
+                    c
+                }
+            })
+            .collect()
+    }
+
+    fn encode(&self, values: &[f32]) -> Vec<u8> {
+        if self.half {
+            let halfs = values.iter().map(|&c| f32_to_f16(c).to_le_bytes());
+            return halfs.flat_map(|c| c.to_vec()).collect();
+        }
+
+        let values = values.iter().enumerate();
+        values
+            .map(|(i, &c)| {
+                if self.is_color(i) {
+                    linear_to_srgb(c)
+                } else {
+                    c
+                }
+            })
+            .map(|c| (c.max(0.0).min(1.0) * 255.0).round() as u8)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(texels: Vec<u8>, width: u32, height: u32) -> ImageSource {
+        ImageSource::new(wgpu::TextureFormat::Rgba8Unorm, texels, width, height)
+    }
+
+    #[test]
+    fn level_count() {
+        assert_eq!(Mipmaps::Premultiplied.level_count(1, 1), 1);
+        assert_eq!(Mipmaps::Premultiplied.level_count(256, 64), 9);
+        assert_eq!(Mipmaps::Straight.level_count(5, 3), 3);
+        assert_eq!(Mipmaps::None.level_count(256, 256), 1);
+
+        let source = rgba(vec![0; 8 * 2 * 4], 8, 2);
+        let sizes: Vec<_> = Mipmaps::Premultiplied
+            .generate(&source)
+            .iter()
+            .map(|m| (m.width, m.height))
+            .collect();
+        assert_eq!(sizes, [(4, 1), (2, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn premultiplied() {
+        let source = rgba(vec![200, 0, 0, 200, 0, 0, 0, 0], 2, 1);
+        let level = downsample(&source, true).unwrap();
+        assert_eq!(level.texels, [100, 0, 0, 100]);
+    }
+
+    #[test]
+    fn straight() {
+        // transparent texels don't darken the color
+        let source = rgba(vec![200, 0, 0, 200, 0, 0, 0, 0], 2, 1);
+        let level = downsample(&source, false).unwrap();
+        assert_eq!(level.texels, [200, 0, 0, 100]);
+    }
+
+    #[test]
+    fn srgb() {
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let source = ImageSource::new(format, vec![255, 255, 255, 255, 0, 0, 0, 255], 2, 1);
+        let level = downsample(&source, true).unwrap();
+        assert_eq!(level.texels, [188, 188, 188, 255]);
+    }
+
+    #[test]
+    fn unsupported_format_keeps_base_level() {
+        let format = wgpu::TextureFormat::R32Float;
+        let source = ImageSource::new(format, vec![0; 4 * 4 * 4], 4, 4);
+        assert!(downsample(&source, true).is_none());
+        assert!(Mipmaps::Premultiplied.generate(&source).is_empty());
+    }
+}
@@ -1,5 +1,13 @@
-use crate::image::{Image, ImageBindGroup};
-use std::sync::Arc;
+use crate::{
+    blend::Blend,
+    filter::{FilterLayout, FilterShader},
+    image::{Image, ImageBindGroup},
+    utils::StagingPool,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 #[derive(Clone, Default)]
 pub struct Vertex {
@@ -15,11 +23,178 @@ impl Vertex {
         }
     }
 
+    pub fn position(&self) -> [f32; 2] {
+        self.position
+    }
+
+    pub fn tex_coord(&self) -> [f32; 2] {
+        self.tex_coord
+    }
+
     pub const fn format() -> [wgpu::VertexAttributeDescriptor; 2] {
         wgpu::vertex_attr_array![0 => Float2, 1 => Float2]
     }
 }
 
+/// Alternative to [`Vertex`] for multi-texture batches.
+///
+/// Takes 16 bytes with a per-vertex color and texture slot, a [`Vertex`] with
+/// just a tint would take 20. Positions are fixed point relative to the origin
+/// of the batch, see [`CompactBatch::set_origin`](crate::batch::CompactBatch::set_origin).
+///
+/// Texture coordinates are normalized with [`pack_uv`](crate::math::pack_uv),
+/// `color` is premultiplied and `texture_id` selects one of [`MAX_SLOTS`] textures.
+#[derive(Clone, Default)]
+#[repr(C)]
+pub struct CompactVertex {
+    position: [i16; 2],
+    tex_coord: [u16; 2],
+    color: [u8; 4],
+    texture_id: [u8; 4],
+}
+
+impl CompactVertex {
+    /// Steps per logical pixel of positions.
+    pub const PRECISION: f32 = 8.0;
+    /// Largest distance from the origin in logical pixels.
+    pub const MAX_OFFSET: f32 = i16::MAX as f32 / Self::PRECISION;
+
+    /// Vertex at `[x, y]` in a batch with `origin`.
+    ///
+    /// Positions further than [`CompactVertex::MAX_OFFSET`] from the origin
+    /// are clamped.
+    pub fn new(
+        origin: [f32; 2],
+        x: f32,
+        y: f32,
+        tex_coord: [u16; 2],
+        color: [u8; 4],
+        texture_id: u8,
+    ) -> Self {
+        let fixed = |c: f32| {
+            let c = (c * Self::PRECISION).round();
+            debug_assert!(
+                c.abs() <= i16::MAX as f32,
+                "position too far from the origin"
+            );
+            c.max(-i16::MAX as f32).min(i16::MAX as f32) as i16
+        };
+        Self {
+            position: [fixed(x - origin[0]), fixed(y - origin[1])],
+            tex_coord,
+            color,
+            texture_id: [texture_id, 0, 0, 0],
+        }
+    }
+
+    /// Position relative to the origin of the batch, in logical pixels.
+    pub fn offset(&self) -> [f32; 2] {
+        let [x, y] = self.position;
+        [x as f32 / Self::PRECISION, y as f32 / Self::PRECISION]
+    }
+
+    pub const fn format() -> [wgpu::VertexAttributeDescriptor; 4] {
+        wgpu::vertex_attr_array![0 => Short2, 1 => Ushort2Norm, 2 => Uchar4Norm, 3 => Uchar4]
+    }
+
+    /// Per-instance batch origin following the vertex attributes.
+    pub const fn origin_format() -> [wgpu::VertexAttributeDescriptor; 1] {
+        wgpu::vertex_attr_array![4 => Float2]
+    }
+}
+
+/// Number of textures in the [`Layout::slots`] bind group.
+pub const MAX_SLOTS: usize = 8;
+
+/// Shaders known to the pipeline cache of [`Layout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ShaderId {
+    /// [`Vertex`] with a single [`Layout::image`].
+    Sprite,
+    /// [`CompactVertex`] with [`Layout::slots`].
+    Compact,
+    /// [`Instance`](crate::batch::Instance)s with [`Layout::slots`].
+    Instanced,
+    /// Frame time graph of [`Perf`](crate::perf::Perf).
+    Perf,
+    /// W3C blend functions of [`AdvancedBlend`](crate::blend::AdvancedBlend).
+    Advanced,
+    /// Fullscreen pass of a [`Filter`](crate::filter::Filter) with [`Layout::filter`].
+    Filter(FilterShader),
+}
+
+/// Stencil usage of a pipeline.
+///
+/// Pipelines other than `Disabled` expect a `Depth24PlusStencil8` attachment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Stencil {
+    Disabled,
+    /// Increments the stencil value wherever the pipeline draws.
+    Write,
+    /// Only draws where the stencil value equals the reference.
+    Test,
+}
+
+impl Stencil {
+    fn state(self) -> Option<wgpu::DepthStencilStateDescriptor> {
+        let (compare, pass_op) = match self {
+            Stencil::Disabled => return None,
+            Stencil::Write => (
+                wgpu::CompareFunction::Always,
+                wgpu::StencilOperation::IncrementClamp,
+            ),
+            Stencil::Test => (wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep),
+        };
+
+        let face = wgpu::StencilStateFaceDescriptor {
+            compare,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+
+        Some(wgpu::DepthStencilStateDescriptor {
+            format: wgpu::TextureFormat::Depth24PlusStencil8,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil_front: face.clone(),
+            stencil_back: face,
+            stencil_read_mask: !0,
+            stencil_write_mask: !0,
+        })
+    }
+}
+
+/// Everything that distinguishes pipelines in the cache of [`Layout`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub shader: ShaderId,
+    pub blend: Blend,
+    pub format: wgpu::TextureFormat,
+    /// Must match [`Target::sample_count`](crate::target::Target::sample_count).
+    pub sample_count: u32,
+    pub stencil: Stencil,
+}
+
+impl PipelineKey {
+    /// Single sampled pipeline without stencil.
+    pub fn new(shader: ShaderId, blend: Blend, format: wgpu::TextureFormat) -> Self {
+        Self {
+            shader,
+            blend,
+            format,
+            sample_count: 1,
+            stencil: Stencil::Disabled,
+        }
+    }
+}
+
+#[derive(Default)]
+struct PipelineCache {
+    shaders: HashMap<ShaderId, Arc<Shader>>,
+    pipelines: HashMap<PipelineKey, Arc<wgpu::RenderPipeline>>,
+}
+
 pub struct Shader {
     pub vs: wgpu::ShaderModule,
     pub fs: wgpu::ShaderModule,
@@ -27,9 +202,46 @@ pub struct Shader {
 
 impl Shader {
     pub fn new(device: &wgpu::Device) -> Self {
-        let vs = crate::load_module(device, include_bytes!("shaders/compiled/sprite.vert.spv"));
-        let fs = crate::load_module(device, include_bytes!("shaders/compiled/sprite.frag.spv"));
-        Self { vs, fs }
+        Self::load(device, ShaderId::Sprite)
+    }
+
+    /// Shader for [`CompactVertex`], sampling from [`Layout::slots`].
+    pub fn compact(device: &wgpu::Device) -> Self {
+        Self::load(device, ShaderId::Compact)
+    }
+
+    pub fn load(device: &wgpu::Device, id: ShaderId) -> Self {
+        let (vs, fs): (&[u8], &[u8]) = match id {
+            ShaderId::Sprite => (
+                include_bytes!("shaders/compiled/sprite.vert.spv"),
+                include_bytes!("shaders/compiled/sprite.frag.spv"),
+            ),
+            ShaderId::Compact => (
+                include_bytes!("shaders/compiled/compact.vert.spv"),
+                include_bytes!("shaders/compiled/slots.frag.spv"),
+            ),
+            ShaderId::Instanced => (
+                include_bytes!("shaders/compiled/instanced.vert.spv"),
+                include_bytes!("shaders/compiled/slots.frag.spv"),
+            ),
+            ShaderId::Perf => (
+                include_bytes!("shaders/compiled/perf.vert.spv"),
+                include_bytes!("shaders/compiled/perf.frag.spv"),
+            ),
+            ShaderId::Advanced => (
+                include_bytes!("shaders/compiled/sprite.vert.spv"),
+                include_bytes!("shaders/compiled/advanced.frag.spv"),
+            ),
+            ShaderId::Filter(shader) => (
+                include_bytes!("shaders/compiled/filter.vert.spv"),
+                shader.spirv(),
+            ),
+        };
+
+        Self {
+            vs: crate::load_module(device, vs),
+            fs: crate::load_module(device, fs),
+        }
     }
 }
 
@@ -37,6 +249,18 @@ pub struct Layout {
     pub projection: wgpu::BindGroupLayout,
     pub image: wgpu::BindGroupLayout,
     pub pipeline: wgpu::PipelineLayout,
+
+    /// A sampler followed by [`MAX_SLOTS`] textures.
+    pub slots: wgpu::BindGroupLayout,
+    pub slots_pipeline: wgpu::PipelineLayout,
+    /// Pipeline layout without any bind groups.
+    pub empty_pipeline: wgpu::PipelineLayout,
+    /// Source and backdrop images followed by a uniform block.
+    pub advanced_pipeline: wgpu::PipelineLayout,
+    pub filter: FilterLayout,
+
+    cache: Mutex<PipelineCache>,
+    pub(crate) staging: StagingPool,
 }
 
 impl Layout {
@@ -74,21 +298,190 @@ impl Layout {
             bind_group_layouts: &[&projection, &combined],
         });
 
+        let mut bindings = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Sampler { comparison: false },
+        }];
+        bindings.extend(
+            (1..=MAX_SLOTS as u32).map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::SampledTexture {
+                    dimension: wgpu::TextureViewDimension::D2,
+                    component_type: wgpu::TextureComponentType::Float,
+                    multisampled: false,
+                },
+            }),
+        );
+
+        let slots = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture slots"),
+            bindings: &bindings,
+        });
+
+        let slots_pipeline = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&projection, &slots],
+        });
+
+        let empty_pipeline = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[],
+        });
+
+        let filter = FilterLayout::new(device, &combined);
+        let advanced_pipeline = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&projection, &combined, &combined, &filter.uniforms],
+        });
+
         Self {
             projection,
             image: combined,
             pipeline,
+
+            slots,
+            slots_pipeline,
+            empty_pipeline,
+            advanced_pipeline,
+            filter,
+
+            cache: Mutex::new(PipelineCache::default()),
+            staging: StagingPool::default(),
         }
     }
 
-    pub fn create_pipeline(
+    /// Submits `encoder` and reuses the staging buffers of batch uploads once
+    /// the GPU is done with them.
+    ///
+    /// Staging buffers of encoders submitted directly to the queue are not
+    /// reused, all encoders with uploads must be submitted before this one.
+    pub fn submit(&self, queue: &wgpu::Queue, encoder: wgpu::CommandEncoder) {
+        queue.submit(&[encoder.finish()]);
+        self.staging.recall();
+    }
+
+    /// Returns the pipeline for `key`, creating it on first use.
+    ///
+    /// Shader modules are loaded once per [`ShaderId`].
+    pub fn cached_pipeline(
+        &self,
+        device: &wgpu::Device,
+        key: PipelineKey,
+    ) -> Arc<wgpu::RenderPipeline> {
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(pipeline) = cache.pipelines.get(&key) {
+            return pipeline.clone();
+        }
+
+        let shader = cache
+            .shaders
+            .entry(key.shader)
+            .or_insert_with(|| Arc::new(Shader::load(device, key.shader)))
+            .clone();
+
+        let color_state = key.blend.clone().into_color_state(key.format);
+        let pipeline = self.build_pipeline(
+            device,
+            &shader,
+            key.shader,
+            color_state,
+            key.sample_count,
+            key.stencil,
+        );
+
+        let pipeline = Arc::new(pipeline);
+        cache.pipelines.insert(key, pipeline.clone());
+        pipeline
+    }
+
+    fn build_pipeline(
         &self,
         device: &wgpu::Device,
         shader: &Shader,
+        id: ShaderId,
         color_state: wgpu::ColorStateDescriptor,
+        sample_count: u32,
+        stencil: Stencil,
     ) -> wgpu::RenderPipeline {
+        let vertex = Vertex::format();
+        let compact = CompactVertex::format();
+        let origin = CompactVertex::origin_format();
+        let instance = crate::batch::Instance::format();
+        let point = wgpu::vertex_attr_array![0 => Float2];
+
+        let buffer = |stride: usize, step_mode, attributes| wgpu::VertexBufferDescriptor {
+            stride: stride as wgpu::BufferAddress,
+            step_mode,
+            attributes,
+        };
+        let per_vertex = wgpu::InputStepMode::Vertex;
+        let per_instance = wgpu::InputStepMode::Instance;
+
+        let point_size = std::mem::size_of::<[f32; 2]>();
+        let (layout, topology, cull_mode, buffers) = match id {
+            ShaderId::Sprite => (
+                &self.pipeline,
+                wgpu::PrimitiveTopology::TriangleList,
+                wgpu::CullMode::Back,
+                vec![buffer(
+                    std::mem::size_of::<Vertex>(),
+                    per_vertex,
+                    &vertex[..],
+                )],
+            ),
+            ShaderId::Compact => (
+                &self.slots_pipeline,
+                wgpu::PrimitiveTopology::TriangleList,
+                wgpu::CullMode::Back,
+                vec![
+                    buffer(
+                        std::mem::size_of::<CompactVertex>(),
+                        per_vertex,
+                        &compact[..],
+                    ),
+                    buffer(point_size, per_instance, &origin[..]),
+                ],
+            ),
+            // mirrored transforms flip the winding
+            ShaderId::Instanced => (
+                &self.slots_pipeline,
+                wgpu::PrimitiveTopology::TriangleList,
+                wgpu::CullMode::None,
+                vec![
+                    buffer(point_size, per_vertex, &point[..]),
+                    buffer(
+                        std::mem::size_of::<crate::batch::Instance>(),
+                        per_instance,
+                        &instance[..],
+                    ),
+                ],
+            ),
+            ShaderId::Perf => (
+                &self.empty_pipeline,
+                wgpu::PrimitiveTopology::LineList,
+                wgpu::CullMode::Back,
+                vec![buffer(point_size, per_vertex, &point[..])],
+            ),
+            ShaderId::Advanced => (
+                &self.advanced_pipeline,
+                wgpu::PrimitiveTopology::TriangleList,
+                wgpu::CullMode::None,
+                vec![buffer(
+                    std::mem::size_of::<Vertex>(),
+                    per_vertex,
+                    &vertex[..],
+                )],
+            ),
+            // the filter vertex shader generates a fullscreen strip
+            ShaderId::Filter(shader) => (
+                shader.pipeline_layout(&self.filter),
+                wgpu::PrimitiveTopology::TriangleStrip,
+                wgpu::CullMode::None,
+                vec![],
+            ),
+        };
+
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            layout: &self.pipeline,
+            layout,
             vertex_stage: wgpu::ProgrammableStageDescriptor {
                 module: &shader.vs,
                 entry_point: "main",
@@ -99,28 +492,38 @@ impl Layout {
             }),
             rasterization_state: Some(wgpu::RasterizationStateDescriptor {
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: wgpu::CullMode::Back,
+                cull_mode,
                 depth_bias: 0,
                 depth_bias_slope_scale: 0.0,
                 depth_bias_clamp: 0.0,
             }),
-            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            primitive_topology: topology,
             color_states: &[color_state],
-            depth_stencil_state: None,
+            depth_stencil_state: stencil.state(),
             vertex_state: wgpu::VertexStateDescriptor {
                 index_format: wgpu::IndexFormat::Uint16,
-                vertex_buffers: &[wgpu::VertexBufferDescriptor {
-                    stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::InputStepMode::Vertex,
-                    attributes: &Vertex::format(),
-                }],
+                vertex_buffers: &buffers,
             },
-            sample_count: 1,
+            sample_count,
             sample_mask: !0,
             alpha_to_coverage_enabled: false,
         })
     }
 
+    /// Uncached pipeline for [`Vertex`] with a custom shader.
+    ///
+    /// `sample_count` must match [`Target::sample_count`](crate::target::Target::sample_count).
+    pub fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        shader: &Shader,
+        color_state: wgpu::ColorStateDescriptor,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        let (id, stencil) = (ShaderId::Sprite, Stencil::Disabled);
+        self.build_pipeline(device, shader, id, color_state, sample_count, stencil)
+    }
+
     pub fn bind_projection(&self, device: &wgpu::Device, buffer: &wgpu::Buffer) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("projection bind group"),
@@ -167,4 +570,48 @@ impl Layout {
         let bind_group = self.bind_texture(device, &view, sampler);
         ImageBindGroup(Arc::new(bind_group))
     }
+
+    /// Binds up to [`MAX_SLOTS`] images, unused slots repeat the last one.
+    pub fn bind_slots(
+        &self,
+        device: &wgpu::Device,
+        images: &[&Image],
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        assert!(!images.is_empty() && images.len() <= MAX_SLOTS);
+
+        let last = images.len() - 1;
+        let views: Vec<_> = (0..MAX_SLOTS)
+            .map(|i| images[i.min(last)].texture.create_default_view())
+            .collect();
+
+        let mut bindings = vec![wgpu::Binding {
+            binding: 0,
+            resource: wgpu::BindingResource::Sampler(sampler),
+        }];
+        bindings.extend(views.iter().enumerate().map(|(i, view)| wgpu::Binding {
+            binding: 1 + i as u32,
+            resource: wgpu::BindingResource::TextureView(view),
+        }));
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("texture slots bind group"),
+            layout: &self.slots,
+            bindings: &bindings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_vertex() {
+        assert_eq!(std::mem::size_of::<CompactVertex>(), 16);
+
+        let vertex = CompactVertex::new([100.0, -50.0], 110.3, -60.0, [0; 2], [0xFF; 4], 3);
+        assert_eq!(vertex.offset(), [10.25, -10.0]);
+        assert_eq!(vertex.texture_id[0], 3);
+    }
 }
@@ -1,19 +1,30 @@
 pub use wgpu;
 
+pub mod assets;
 pub mod batch;
 pub mod blend;
+pub mod context;
+mod error;
+pub mod filter;
 pub mod image;
 pub mod layout;
 pub mod math;
+pub mod renderer;
+pub mod soft;
 pub mod sprite;
 pub mod target;
 pub mod utils;
 
 pub mod perf;
 
+pub use self::error::Error;
+
 #[cfg(feature = "app")]
 pub mod app;
 
+#[cfg(feature = "testing")]
+pub mod testing;
+
 pub fn clear_color(
     encoder: &mut wgpu::CommandEncoder,
     view: &wgpu::TextureView,
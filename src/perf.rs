@@ -1,17 +1,22 @@
-use crate::target::Target;
-use std::time::Instant;
+use crate::{
+    blend,
+    layout::{Layout, PipelineKey, ShaderId},
+    target::Target,
+};
+use std::{sync::Arc, time::Instant};
 
 const CAPACITY: usize = 120;
 
 pub struct Perf {
     ticker: Instant,
     history: [f32; CAPACITY],
-    pipeline: wgpu::RenderPipeline,
+    pipeline: Arc<wgpu::RenderPipeline>,
 }
 
 impl Perf {
-    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
-        let pipeline = create_pipeline(device, format);
+    pub fn new(device: &wgpu::Device, layout: &Layout, format: wgpu::TextureFormat) -> Self {
+        let key = PipelineKey::new(ShaderId::Perf, blend::REPLACE, format);
+        let pipeline = layout.cached_pipeline(device, key);
         Self {
             ticker: Instant::now(),
             history: [0.0; CAPACITY],
@@ -56,50 +61,3 @@ impl Perf {
         rpass.draw(0..count, 0..1);
     }
 }
-
-fn create_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
-    let vs = crate::load_module(device, include_bytes!("shaders/compiled/perf.vert.spv"));
-    let fs = crate::load_module(device, include_bytes!("shaders/compiled/perf.frag.spv"));
-
-    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        bind_group_layouts: &[],
-    });
-
-    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        layout: &layout,
-        vertex_stage: wgpu::ProgrammableStageDescriptor {
-            module: &vs,
-            entry_point: "main",
-        },
-        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-            module: &fs,
-            entry_point: "main",
-        }),
-        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: wgpu::CullMode::Back,
-            depth_bias: 0,
-            depth_bias_slope_scale: 0.0,
-            depth_bias_clamp: 0.0,
-        }),
-        primitive_topology: wgpu::PrimitiveTopology::LineList,
-        color_states: &[wgpu::ColorStateDescriptor {
-            format,
-            alpha_blend: wgpu::BlendDescriptor::REPLACE,
-            color_blend: wgpu::BlendDescriptor::REPLACE,
-            write_mask: wgpu::ColorWrite::ALL,
-        }],
-        depth_stencil_state: None,
-        vertex_state: wgpu::VertexStateDescriptor {
-            index_format: wgpu::IndexFormat::Uint16,
-            vertex_buffers: &[wgpu::VertexBufferDescriptor {
-                stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
-                step_mode: wgpu::InputStepMode::Vertex,
-                attributes: &wgpu::vertex_attr_array![0 => Float2],
-            }],
-        },
-        sample_count: 1,
-        sample_mask: !0,
-        alpha_to_coverage_enabled: false,
-    })
-}
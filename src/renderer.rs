@@ -0,0 +1,202 @@
+use crate::{
+    layout::Layout,
+    target::{Projection, Target},
+};
+use std::ops::Range;
+
+/// Geometry that can be recorded into a shared [`Pass`].
+///
+/// Implemented by the batches, the crate has no graphics or mesh types to
+/// draw this way yet.
+pub trait Draw {
+    /// Uploads pending geometry, called before [`Renderer::begin`] so the pass
+    /// only needs shared borrows.
+    fn prepare(&mut self, encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device);
+
+    /// Records the geometry uploaded by the last [`Draw::prepare`].
+    fn draw<'r>(&'r self, pass: &mut Pass<'r>);
+}
+
+/// Records draws for one target at a time into a single render pass.
+pub struct Renderer {
+    projection: Projection,
+}
+
+impl Renderer {
+    /// Pipelines drawn through the renderer must be created with `layout`.
+    pub fn new(device: &wgpu::Device, layout: &Layout) -> Self {
+        Self {
+            projection: Projection::new(device, layout),
+        }
+    }
+
+    pub fn begin<'r>(&'r mut self, target: Target<'r>) -> Pass<'r> {
+        Pass {
+            projection: &mut self.projection,
+            target,
+            commands: Vec::new(),
+            state: State::default(),
+        }
+    }
+}
+
+enum Command<'r> {
+    Pipeline(&'r wgpu::RenderPipeline),
+    BindGroup(u32, &'r wgpu::BindGroup),
+    VertexBuffer(u32, &'r wgpu::Buffer, wgpu::BufferAddress),
+    IndexBuffer(&'r wgpu::Buffer),
+    Draw(Range<u32>, Range<u32>),
+    DrawIndexed(Range<u32>, i32, Range<u32>),
+}
+
+/// Addresses of the bound resources, zero when unbound.
+#[derive(Default)]
+struct State {
+    pipeline: usize,
+    bind_groups: [usize; 4],
+    vertex_buffers: [(usize, wgpu::BufferAddress); 4],
+    index_buffer: usize,
+}
+
+fn addr<T>(value: &T) -> usize {
+    value as *const T as usize
+}
+
+/// Draw commands for the current target, see [`Renderer::begin`].
+///
+/// Commands are kept until the target changes or the pass is finished, state
+/// changes that don't change anything are dropped. Bind group 0 is always the
+/// projection of the current target.
+#[must_use = "draws are only recorded by `Pass::finish`"]
+pub struct Pass<'r> {
+    projection: &'r mut Projection,
+    target: Target<'r>,
+    commands: Vec<Command<'r>>,
+    state: State,
+}
+
+impl<'r> Pass<'r> {
+    /// Records the draws of `item`, uploaded by [`Draw::prepare`] before the
+    /// pass began.
+    pub fn submit<D: Draw + ?Sized>(&mut self, item: &'r D) {
+        item.draw(self);
+    }
+
+    /// Target the following draws are recorded for.
+    pub fn target(&self) -> &Target<'r> {
+        &self.target
+    }
+
+    pub fn set_pipeline(&mut self, pipeline: &'r wgpu::RenderPipeline) {
+        if self.state.pipeline != addr(pipeline) {
+            self.state.pipeline = addr(pipeline);
+            self.commands.push(Command::Pipeline(pipeline));
+        }
+    }
+
+    /// `index` must not be 0, which is reserved for the projection.
+    pub fn set_bind_group(&mut self, index: u32, bind_group: &'r wgpu::BindGroup) {
+        debug_assert_ne!(index, 0);
+        let slot = &mut self.state.bind_groups[index as usize];
+        if *slot != addr(bind_group) {
+            *slot = addr(bind_group);
+            self.commands.push(Command::BindGroup(index, bind_group));
+        }
+    }
+
+    pub fn set_vertex_buffer(
+        &mut self,
+        slot: u32,
+        buffer: &'r wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+    ) {
+        let bound = &mut self.state.vertex_buffers[slot as usize];
+        if *bound != (addr(buffer), offset) {
+            *bound = (addr(buffer), offset);
+            self.commands
+                .push(Command::VertexBuffer(slot, buffer, offset));
+        }
+    }
+
+    pub fn set_index_buffer(&mut self, buffer: &'r wgpu::Buffer) {
+        if self.state.index_buffer != addr(buffer) {
+            self.state.index_buffer = addr(buffer);
+            self.commands.push(Command::IndexBuffer(buffer));
+        }
+    }
+
+    pub fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        self.commands.push(Command::Draw(vertices, instances));
+    }
+
+    pub fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        self.commands
+            .push(Command::DrawIndexed(indices, base_vertex, instances));
+    }
+
+    /// Switches to `target`, flushing recorded draws if it's a different one.
+    pub fn set_target(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        target: Target<'r>,
+    ) {
+        let same = addr(self.target.view) == addr(target.view)
+            && self.target.resolve_target.map(addr) == target.resolve_target.map(addr)
+            && self.target.width == target.width
+            && self.target.height == target.height
+            && self.target.scale == target.scale
+            && self.target.origin == target.origin;
+
+        if !same {
+            self.flush(encoder, device);
+            self.target = target;
+        }
+    }
+
+    /// Records pending draws into a render pass.
+    pub fn flush(&mut self, encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device) {
+        if self.commands.is_empty() {
+            return;
+        }
+
+        let proj_bind_group = self.projection.update(encoder, device, &self.target);
+
+        let mut rpass = self.target.rpass(encoder);
+        rpass.set_bind_group(0, proj_bind_group, &[]);
+
+        for command in self.commands.drain(..) {
+            match command {
+                Command::Pipeline(pipeline) => rpass.set_pipeline(pipeline),
+                Command::BindGroup(index, bind_group) => {
+                    rpass.set_bind_group(index, bind_group, &[])
+                }
+                Command::VertexBuffer(slot, buffer, offset) => {
+                    rpass.set_vertex_buffer(slot, buffer, offset, 0)
+                }
+                Command::IndexBuffer(buffer) => rpass.set_index_buffer(buffer, 0, 0),
+                Command::Draw(vertices, instances) => rpass.draw(vertices, instances),
+                Command::DrawIndexed(indices, base_vertex, instances) => {
+                    rpass.draw_indexed(indices, base_vertex, instances)
+                }
+            }
+        }
+
+        self.state = State::default();
+    }
+
+    /// Flushes pending draws; must be called before the encoder is finished.
+    pub fn finish(mut self, encoder: &mut wgpu::CommandEncoder, device: &wgpu::Device) {
+        self.flush(encoder, device);
+    }
+}
+
+impl Drop for Pass<'_> {
+    fn drop(&mut self) {
+        debug_assert!(
+            self.commands.is_empty() || std::thread::panicking(),
+            "pass dropped with {} pending commands, call `Pass::finish`",
+            self.commands.len(),
+        );
+    }
+}
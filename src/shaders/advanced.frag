@@ -0,0 +1,155 @@
+#version 450
+
+precision highp float;
+
+layout(location = 0) out vec4 o_Target;
+
+layout(location = 0) in Vertex {
+    vec2 tex_coord;
+} vertex;
+
+layout(set = 1, binding = 0) uniform texture2D t_Source;
+layout(set = 1, binding = 1) uniform sampler s_Source;
+
+layout(set = 2, binding = 0) uniform texture2D t_Backdrop;
+layout(set = 2, binding = 1) uniform sampler s_Backdrop;
+
+layout(set = 3, binding = 0) uniform Params {
+    // physical position of the backdrop copy and its inverse size
+    vec4 backdrop;
+    int mode;
+} params;
+
+// https://www.w3.org/TR/compositing-1/#blending
+
+float color_dodge(float cb, float cs) {
+    if (cb == 0.0) {
+        return 0.0;
+    } else if (cs == 1.0) {
+        return 1.0;
+    } else {
+        return min(1.0, cb / (1.0 - cs));
+    }
+}
+
+float color_burn(float cb, float cs) {
+    if (cb == 1.0) {
+        return 1.0;
+    } else if (cs == 0.0) {
+        return 0.0;
+    } else {
+        return 1.0 - min(1.0, (1.0 - cb) / cs);
+    }
+}
+
+float hard_light(float cb, float cs) {
+    if (cs <= 0.5) {
+        return cb * 2.0 * cs;
+    } else {
+        float s = 2.0 * cs - 1.0;
+        return cb + s - cb * s;
+    }
+}
+
+float soft_light(float cb, float cs) {
+    if (cs <= 0.5) {
+        return cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb);
+    } else {
+        float d;
+        if (cb <= 0.25) {
+            d = ((16.0 * cb - 12.0) * cb + 4.0) * cb;
+        } else {
+            d = sqrt(cb);
+        }
+        return cb + (2.0 * cs - 1.0) * (d - cb);
+    }
+}
+
+float lum(vec3 c) {
+    return dot(c, vec3(0.3, 0.59, 0.11));
+}
+
+vec3 clip_color(vec3 c) {
+    float l = lum(c);
+    float n = min(min(c.r, c.g), c.b);
+    float x = max(max(c.r, c.g), c.b);
+    if (n < 0.0) {
+        c = l + (c - l) * l / (l - n);
+    }
+    if (x > 1.0) {
+        c = l + (c - l) * (1.0 - l) / (x - l);
+    }
+    return c;
+}
+
+vec3 set_lum(vec3 c, float l) {
+    return clip_color(c + (l - lum(c)));
+}
+
+float sat(vec3 c) {
+    return max(max(c.r, c.g), c.b) - min(min(c.r, c.g), c.b);
+}
+
+vec3 set_sat(vec3 c, float s) {
+    float c_min = min(min(c.r, c.g), c.b);
+    float c_max = max(max(c.r, c.g), c.b);
+    if (c_max > c_min) {
+        return (c - c_min) * s / (c_max - c_min);
+    } else {
+        return vec3(0.0);
+    }
+}
+
+vec3 blend(vec3 cb, vec3 cs) {
+    switch (params.mode) {
+    case 1: // multiply
+        return cb * cs;
+    case 2: // screen
+        return cb + cs - cb * cs;
+    case 3: // overlay
+        return vec3(hard_light(cs.r, cb.r), hard_light(cs.g, cb.g), hard_light(cs.b, cb.b));
+    case 4: // darken
+        return min(cb, cs);
+    case 5: // lighten
+        return max(cb, cs);
+    case 6: // color-dodge
+        return vec3(color_dodge(cb.r, cs.r), color_dodge(cb.g, cs.g), color_dodge(cb.b, cs.b));
+    case 7: // color-burn
+        return vec3(color_burn(cb.r, cs.r), color_burn(cb.g, cs.g), color_burn(cb.b, cs.b));
+    case 8: // hard-light
+        return vec3(hard_light(cb.r, cs.r), hard_light(cb.g, cs.g), hard_light(cb.b, cs.b));
+    case 9: // soft-light
+        return vec3(soft_light(cb.r, cs.r), soft_light(cb.g, cs.g), soft_light(cb.b, cs.b));
+    case 10: // difference
+        return abs(cb - cs);
+    case 11: // exclusion
+        return cb + cs - 2.0 * cb * cs;
+    case 12: // hue
+        return set_lum(set_sat(cs, sat(cb)), lum(cb));
+    case 13: // saturation
+        return set_lum(set_sat(cb, sat(cs)), lum(cb));
+    case 14: // color
+        return set_lum(cs, lum(cb));
+    case 15: // luminosity
+        return set_lum(cb, lum(cs));
+    default: // normal
+        return cs;
+    }
+}
+
+void main() {
+    vec2 backdrop_uv = (gl_FragCoord.xy - params.backdrop.xy) * params.backdrop.zw;
+
+    // both are premultiplied
+    vec4 src = texture(sampler2D(t_Source, s_Source), vertex.tex_coord);
+    vec4 dst = texture(sampler2D(t_Backdrop, s_Backdrop), backdrop_uv);
+
+    vec3 cs = src.a > 0.0 ? src.rgb / src.a : vec3(0.0);
+    vec3 cb = dst.a > 0.0 ? dst.rgb / dst.a : vec3(0.0);
+
+    vec3 mixed = src.a * dst.a * clamp(blend(cb, cs), 0.0, 1.0);
+    vec3 color = src.rgb * (1.0 - dst.a) + dst.rgb * (1.0 - src.a) + mixed;
+    float alpha = src.a + dst.a * (1.0 - src.a);
+
+    o_Target = vec4(color, alpha);
+}
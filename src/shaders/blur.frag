@@ -0,0 +1,30 @@
+#version 450
+
+precision mediump float;
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 0) uniform texture2D t_Input;
+layout(set = 1, binding = 1) uniform sampler s_Input;
+
+layout(set = 2, binding = 0) uniform BlurUniforms {
+    vec2 direction;
+    float half_size;
+    float _pad;
+    vec4 weights[2];
+};
+
+void main() {
+    vec4 color = texture(sampler2D(t_Input, s_Input), v_TexCoord) * weights[0].x;
+
+    int count = int(half_size);
+    for (int i = 1; i <= count; i++) {
+        float weight = weights[i / 4][i % 4];
+        vec2 offset = direction * float(i);
+        color += texture(sampler2D(t_Input, s_Input), v_TexCoord + offset) * weight;
+        color += texture(sampler2D(t_Input, s_Input), v_TexCoord - offset) * weight;
+    }
+
+    o_Target = color;
+}
@@ -0,0 +1,33 @@
+#version 450
+
+precision mediump float;
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 0) uniform texture2D t_Input;
+layout(set = 1, binding = 1) uniform sampler s_Input;
+
+layout(set = 2, binding = 0) uniform ColorMatrixUniforms {
+    mat4 matrix;
+    vec4 offset;
+    float alpha;
+};
+
+void main() {
+    vec4 color = texture(sampler2D(t_Input, s_Input), v_TexCoord);
+
+    if (alpha == 0.0) {
+        o_Target = color;
+        return;
+    }
+
+    // un-premultiply alpha before applying the color matrix
+    if (color.a > 0.0) {
+        color.rgb /= color.a;
+    }
+
+    vec4 result = matrix * color + offset;
+    vec3 rgb = mix(color.rgb, result.rgb, alpha) * result.a;
+    o_Target = vec4(rgb, result.a);
+}
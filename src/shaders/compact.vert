@@ -0,0 +1,29 @@
+#version 450
+
+layout(location = 0) in ivec2 a_Position;
+layout(location = 1) in vec2 a_TexCoord;
+layout(location = 2) in vec4 a_Color;
+layout(location = 3) in uvec4 a_TextureId;
+
+layout(location = 4) in vec2 i_Origin;
+
+layout(location = 0) out vec2 v_TexCoord;
+layout(location = 1) out vec4 v_Tint;
+layout(location = 2) flat out uint v_Slot;
+
+layout(set = 0, binding = 0) uniform Globals {
+    mat3 transform;
+} globals;
+
+// steps per logical pixel, see CompactVertex::PRECISION
+const float PRECISION = 8.0;
+
+void main() {
+    vec2 position = i_Origin + vec2(a_Position) / PRECISION;
+
+    v_TexCoord = a_TexCoord;
+    v_Tint = a_Color;
+    v_Slot = a_TextureId.x;
+
+    gl_Position = vec4((globals.transform * vec3(position, 1.0)).xy, 0.0, 1.0);
+}
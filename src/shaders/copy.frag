@@ -0,0 +1,13 @@
+#version 450
+
+precision mediump float;
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 0) uniform texture2D t_Input;
+layout(set = 1, binding = 1) uniform sampler s_Input;
+
+void main() {
+    o_Target = texture(sampler2D(t_Input, s_Input), v_TexCoord);
+}
@@ -0,0 +1,72 @@
+#version 450
+
+precision mediump float;
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 0, binding = 0) uniform FilterGlobals {
+    vec4 input_size;
+    vec4 output_frame;
+};
+
+layout(set = 1, binding = 0) uniform texture2D t_Input;
+layout(set = 1, binding = 1) uniform sampler s_Input;
+
+layout(set = 2, binding = 0) uniform CrtUniforms {
+    float curvature;
+    float line_width;
+    float line_contrast;
+    float vertical_line;
+
+    float noise;
+    float noise_size;
+    float seed;
+    float time;
+
+    float vignetting;
+    float vignetting_alpha;
+    float vignetting_blur;
+};
+
+const float SQRT_2 = 1.414213;
+
+float rand(vec2 co) {
+    return fract(sin(dot(co, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+    vec2 dimensions = input_size.xy;
+    vec2 pixel_coord = v_TexCoord * dimensions;
+    vec2 dir = v_TexCoord - vec2(0.5);
+
+    vec4 color = texture(sampler2D(t_Input, s_Input), v_TexCoord);
+    vec3 rgb = color.rgb;
+
+    if (noise > 0.0 && noise_size > 0.0) {
+        pixel_coord = floor(pixel_coord / noise_size);
+        rgb += (rand(pixel_coord * noise_size * seed) - 0.5) * noise;
+    }
+
+    if (line_width > 0.0) {
+        float c = curvature > 0.0 ? curvature : 1.0;
+        float k = curvature > 0.0 ? (length(dir * dir) * 0.25 * c * c + 0.935 * c) : 1.0;
+        vec2 uv = dir * k;
+        bool vertical = vertical_line > 0.5;
+
+        float v = vertical ? uv.x * dimensions.x : uv.y * dimensions.y;
+        v *= min(1.0, 2.0 / line_width) / c;
+        rgb *= 1.0 + cos(v * 1.2 - time) * 0.5 * line_contrast;
+
+        float segment = vertical ? (dir.x + 0.5) * dimensions.x : (dir.y + 0.5) * dimensions.y;
+        rgb *= 0.99 + ceil(mod(segment, 4.0)) * 0.015;
+    }
+
+    if (vignetting > 0.0) {
+        float outer = SQRT_2 - vignetting * SQRT_2;
+        float darker = clamp((outer - length(dir) * SQRT_2) / (0.00001 + vignetting_blur * SQRT_2), 0.0, 1.0);
+        rgb *= darker + (1.0 - darker) * (1.0 - vignetting_alpha);
+    }
+
+    o_Target = vec4(rgb, color.a);
+}
@@ -0,0 +1,26 @@
+#version 450
+
+precision mediump float;
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 0) uniform texture2D t_Input;
+layout(set = 1, binding = 1) uniform sampler s_Input;
+
+layout(set = 2, binding = 0) uniform DisplacementUniforms {
+    mat3 map_matrix;
+    vec2 scale;
+};
+
+layout(set = 3, binding = 0) uniform texture2D t_Map;
+layout(set = 3, binding = 1) uniform sampler s_Map;
+
+void main() {
+    vec2 map_coord = (map_matrix * vec3(v_TexCoord, 1.0)).xy;
+    vec4 map = texture(sampler2D(t_Map, s_Map), map_coord);
+
+    vec2 offset = (map.xy - vec2(0.5)) * scale;
+    vec2 coord = clamp(v_TexCoord + offset, vec2(0.0), vec2(1.0));
+    o_Target = texture(sampler2D(t_Input, s_Input), coord);
+}
@@ -0,0 +1,14 @@
+#version 450
+
+layout(location = 0) out vec2 v_TexCoord;
+
+layout(set = 0, binding = 0) uniform FilterGlobals {
+    vec4 input_size;
+    vec4 output_frame;
+};
+
+void main() {
+    vec2 uv = vec2(float(gl_VertexIndex & 1), float((gl_VertexIndex >> 1) & 1));
+    v_TexCoord = uv;
+    gl_Position = vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
@@ -0,0 +1,28 @@
+#version 450
+
+layout(location = 0) in vec2 a_Corner;
+
+layout(location = 1) in vec2 i_AxisX;
+layout(location = 2) in vec2 i_AxisY;
+layout(location = 3) in vec2 i_Translation;
+layout(location = 4) in vec4 i_Frame;
+layout(location = 5) in vec4 i_Tint;
+layout(location = 6) in uint i_Slot;
+
+layout(location = 0) out vec2 v_TexCoord;
+layout(location = 1) out vec4 v_Tint;
+layout(location = 2) flat out uint v_Slot;
+
+layout(set = 0, binding = 0) uniform Globals {
+    mat3 transform;
+} globals;
+
+void main() {
+    vec2 position = i_AxisX * a_Corner.x + i_AxisY * a_Corner.y + i_Translation;
+
+    v_TexCoord = mix(i_Frame.xy, i_Frame.zw, a_Corner);
+    v_Tint = i_Tint;
+    v_Slot = i_Slot;
+
+    gl_Position = vec4((globals.transform * vec3(position, 1.0)).xy, 0.0, 1.0);
+}
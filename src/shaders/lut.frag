@@ -0,0 +1,37 @@
+#version 450
+
+precision mediump float;
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 0) uniform texture2D t_Input;
+layout(set = 1, binding = 1) uniform sampler s_Input;
+
+layout(set = 2, binding = 0) uniform LutUniforms {
+    vec4 domain_min;
+    vec4 domain_scale;
+    float intensity;
+    float size;
+};
+
+layout(set = 3, binding = 0) uniform texture3D t_Lut;
+layout(set = 3, binding = 1) uniform sampler s_Lut;
+
+void main() {
+    vec4 color = texture(sampler2D(t_Input, s_Input), v_TexCoord);
+
+    vec3 rgb = color.rgb;
+    if (color.a > 0.0) {
+        rgb /= color.a;
+    }
+
+    // sample between the centers of the first and the last texels
+    vec3 coord = clamp((rgb - domain_min.rgb) * domain_scale.rgb, 0.0, 1.0);
+    coord = coord * ((size - 1.0) / size) + 0.5 / size;
+
+    vec3 graded = texture(sampler3D(t_Lut, s_Lut), coord).rgb;
+    rgb = mix(rgb, graded, intensity);
+
+    o_Target = vec4(rgb * color.a, color.a);
+}
@@ -0,0 +1,27 @@
+#version 450
+
+precision mediump float;
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 0, binding = 0) uniform FilterGlobals {
+    vec4 input_size;
+    vec4 output_frame;
+};
+
+layout(set = 1, binding = 0) uniform texture2D t_Input;
+layout(set = 1, binding = 1) uniform sampler s_Input;
+
+layout(set = 2, binding = 0) uniform PixelateUniforms {
+    vec2 size;
+};
+
+void main() {
+    // snap in logical coordinates so blocks stay in place when the frame moves
+    vec2 coord = output_frame.xy + v_TexCoord * output_frame.zw;
+    coord = floor(coord / size) * size;
+    vec2 uv = (coord - output_frame.xy) / output_frame.zw;
+
+    o_Target = texture(sampler2D(t_Input, s_Input), uv);
+}
@@ -0,0 +1,20 @@
+#version 450
+
+precision mediump float;
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 0) uniform texture2D t_Input;
+layout(set = 1, binding = 1) uniform sampler s_Input;
+
+layout(set = 2, binding = 0) uniform ShadowUniforms {
+    vec2 offset;
+    vec2 _pad;
+    vec4 color;
+};
+
+void main() {
+    float alpha = texture(sampler2D(t_Input, s_Input), v_TexCoord - offset).a;
+    o_Target = min(vec4(color.rgb * color.a, color.a) * alpha, vec4(1.0));
+}
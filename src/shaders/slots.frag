@@ -0,0 +1,34 @@
+#version 450
+
+precision mediump float;
+
+layout(location = 0) in vec2 v_TexCoord;
+layout(location = 1) in vec4 v_Tint;
+layout(location = 2) flat in uint v_Slot;
+
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 0) uniform sampler s_Color;
+layout(set = 1, binding = 1) uniform texture2D t_Color0;
+layout(set = 1, binding = 2) uniform texture2D t_Color1;
+layout(set = 1, binding = 3) uniform texture2D t_Color2;
+layout(set = 1, binding = 4) uniform texture2D t_Color3;
+layout(set = 1, binding = 5) uniform texture2D t_Color4;
+layout(set = 1, binding = 6) uniform texture2D t_Color5;
+layout(set = 1, binding = 7) uniform texture2D t_Color6;
+layout(set = 1, binding = 8) uniform texture2D t_Color7;
+
+void main() {
+    vec4 color;
+
+         if (v_Slot == 0) { color = texture(sampler2D(t_Color0, s_Color), v_TexCoord); }
+    else if (v_Slot == 1) { color = texture(sampler2D(t_Color1, s_Color), v_TexCoord); }
+    else if (v_Slot == 2) { color = texture(sampler2D(t_Color2, s_Color), v_TexCoord); }
+    else if (v_Slot == 3) { color = texture(sampler2D(t_Color3, s_Color), v_TexCoord); }
+    else if (v_Slot == 4) { color = texture(sampler2D(t_Color4, s_Color), v_TexCoord); }
+    else if (v_Slot == 5) { color = texture(sampler2D(t_Color5, s_Color), v_TexCoord); }
+    else if (v_Slot == 6) { color = texture(sampler2D(t_Color6, s_Color), v_TexCoord); }
+    else                  { color = texture(sampler2D(t_Color7, s_Color), v_TexCoord); }
+
+    o_Target = color * v_Tint;
+}
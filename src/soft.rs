@@ -0,0 +1,665 @@
+//! Reference rasterizer drawing [`Quads`] on the CPU.
+//!
+//! Follows the sprite pipeline: positions go through
+//! [`projection`](crate::math::projection), clockwise triangles are culled and
+//! texels are combined with the [`Blend`] factors of the quad. Textures are
+//! decoded to linear floats the way they're sampled, the canvas blends linear
+//! values and encodes them again in [`Canvas::to_image`]. The blend color is
+//! transparent black.
+
+use crate::{
+    batch::Quads,
+    blend::Blend,
+    image::{f16_to_f32, f32_to_f16, linear_to_srgb, srgb_to_linear, ImageSource},
+    layout::Vertex,
+    Error,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sampling {
+    Nearest,
+    Linear,
+}
+
+/// Image converted to linear RGBA.
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    texels: Vec<[f32; 4]>,
+}
+
+impl Texture {
+    /// Decodes 8-bit RGBA or BGRA, `R8Unorm`, `Rg8Unorm` and `Rgba16Float`
+    /// texels like the GPU: sRGB color is linearized, missing color channels
+    /// read as 0 and missing alpha as 1. Fails for other formats.
+    pub fn new(source: &ImageSource) -> Result<Self, Error> {
+        use wgpu::TextureFormat::*;
+
+        let texels = match source.format {
+            Rgba8Unorm | Rgba8UnormSrgb | Bgra8Unorm | Bgra8UnormSrgb => {
+                let bgra = matches!(source.format, Bgra8Unorm | Bgra8UnormSrgb);
+                let srgb = is_srgb(source.format);
+                let texels = source.texels.chunks_exact(4);
+                texels
+                    .map(|c| {
+                        let [r, g, b, a] = [c[0], c[1], c[2], c[3]];
+                        let [r, b] = if bgra { [b, r] } else { [r, b] };
+                        let mut texel = unorm([r, g, b, a]);
+                        if srgb {
+                            for c in &mut texel[..3] {
+                                *c = srgb_to_linear(*c);
+                            }
+                        }
+                        texel
+                    })
+                    .collect()
+            }
+            R8Unorm => source
+                .texels
+                .iter()
+                .map(|&r| unorm([r, 0, 0, 255]))
+                .collect(),
+            Rg8Unorm => {
+                let texels = source.texels.chunks_exact(2);
+                texels.map(|c| unorm([c[0], c[1], 0, 255])).collect()
+            }
+            Rgba16Float => {
+                let texels = source.texels.chunks_exact(8);
+                texels
+                    .map(|c| {
+                        let half = |i: usize| f16_to_f32(u16::from_le_bytes([c[i], c[i + 1]]));
+                        [half(0), half(2), half(4), half(6)]
+                    })
+                    .collect()
+            }
+            format => {
+                let message = format!("unsupported texture format {:?}", format);
+                return Err(Error::Format(message));
+            }
+        };
+
+        Ok(Self {
+            width: source.width,
+            height: source.height,
+            texels,
+        })
+    }
+
+    fn texel(&self, x: i64, y: i64) -> [f32; 4] {
+        let x = x.max(0).min(self.width as i64 - 1) as usize;
+        let y = y.max(0).min(self.height as i64 - 1) as usize;
+        self.texels[y * self.width as usize + x]
+    }
+
+    /// Samples at normalized coordinates, clamping to the edges.
+    pub fn sample(&self, [u, v]: [f32; 2], sampling: Sampling) -> [f32; 4] {
+        let (x, y) = (u * self.width as f32, v * self.height as f32);
+        match sampling {
+            Sampling::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Sampling::Linear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let lerp = |a: [f32; 4], b: [f32; 4], t: f32| {
+                    let mut out = [0.0; 4];
+                    for i in 0..4 {
+                        out[i] = a[i] + (b[i] - a[i]) * t;
+                    }
+                    out
+                };
+
+                let top = lerp(self.texel(x0, y0), self.texel(x0 + 1, y0), fx);
+                let bottom = lerp(self.texel(x0, y0 + 1), self.texel(x0 + 1, y0 + 1), fx);
+                lerp(top, bottom, fy)
+            }
+        }
+    }
+}
+
+fn unorm(texel: [u8; 4]) -> [f32; 4] {
+    let f = |c: u8| c as f32 / 255.0;
+    [f(texel[0]), f(texel[1]), f(texel[2]), f(texel[3])]
+}
+
+fn factor(factor: wgpu::BlendFactor, src: [f32; 4], dst: [f32; 4], i: usize) -> f32 {
+    use wgpu::BlendFactor::*;
+
+    match factor {
+        Zero => 0.0,
+        One => 1.0,
+        SrcColor => src[i],
+        OneMinusSrcColor => 1.0 - src[i],
+        SrcAlpha => src[3],
+        OneMinusSrcAlpha => 1.0 - src[3],
+        DstColor => dst[i],
+        OneMinusDstColor => 1.0 - dst[i],
+        DstAlpha => dst[3],
+        OneMinusDstAlpha => 1.0 - dst[3],
+        SrcAlphaSaturated if i == 3 => 1.0,
+        SrcAlphaSaturated => src[3].min(1.0 - dst[3]),
+        BlendColor => 0.0,
+        OneMinusBlendColor => 1.0,
+    }
+}
+
+/// Combines premultiplied or straight colors the way the GPU blend stage does.
+pub fn blend(blend: &Blend, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+    let mut out = [0.0; 4];
+    for i in 0..4 {
+        let desc = if i == 3 { &blend.alpha } else { &blend.color };
+        let s = src[i] * factor(desc.src_factor, src, dst, i);
+        let d = dst[i] * factor(desc.dst_factor, src, dst, i);
+
+        let value = match desc.operation {
+            wgpu::BlendOperation::Add => s + d,
+            wgpu::BlendOperation::Subtract => s - d,
+            wgpu::BlendOperation::ReverseSubtract => d - s,
+            wgpu::BlendOperation::Min => src[i].min(dst[i]),
+            wgpu::BlendOperation::Max => src[i].max(dst[i]),
+        };
+        out[i] = value.max(0.0).min(1.0);
+    }
+    out
+}
+
+/// Applies the projection the way `sprite.vert` does and maps the result to
+/// physical pixels of a `width` by `height` target.
+pub fn to_pixels(
+    projection: &[[f32; 4]; 4],
+    [x, y]: [f32; 2],
+    width: u32,
+    height: u32,
+) -> [f32; 2] {
+    let [c0, c1, c2] = [projection[0], projection[1], projection[2]];
+    let clip_x = c0[0] * x + c1[0] * y + c2[0];
+    let clip_y = c0[1] * x + c1[1] * y + c2[1];
+    [
+        (clip_x + 1.0) * 0.5 * width as f32,
+        (1.0 - clip_y) * 0.5 * height as f32,
+    ]
+}
+
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+/// Pixels exactly on a top or left edge belong to the triangle, so quads
+/// sharing an edge don't cover any pixel twice.
+fn is_top_left(a: [f32; 2], b: [f32; 2]) -> bool {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    (dy == 0.0 && dx > 0.0) || dy < 0.0
+}
+
+/// RGBA render target in memory.
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    texels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    /// Transparent canvas.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            texels: vec![[0.0; 4]; (width * height) as usize],
+        }
+    }
+
+    pub fn clear(&mut self, color: [f32; 4]) {
+        for texel in &mut self.texels {
+            *texel = color;
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> [f32; 4] {
+        self.texels[(y * self.width + x) as usize]
+    }
+
+    /// Draws `quads` with their blend modes, e.g. [`Batch::quads`] before a
+    /// flush.
+    ///
+    /// [`Batch::quads`]: crate::batch::Batch::quads
+    pub fn draw_quads(
+        &mut self,
+        projection: &[[f32; 4]; 4],
+        quads: &Quads<Vertex>,
+        texture: &Texture,
+        sampling: Sampling,
+    ) {
+        for (blend, vertices) in quads.runs() {
+            self.draw(projection, vertices, texture, sampling, blend);
+        }
+    }
+
+    /// Draws quads of four vertices each, split into triangles as
+    /// [`Quads::add_quad`] does.
+    pub fn draw(
+        &mut self,
+        projection: &[[f32; 4]; 4],
+        vertices: &[Vertex],
+        texture: &Texture,
+        sampling: Sampling,
+        blend: &Blend,
+    ) {
+        let (width, height) = (self.width, self.height);
+        for quad in vertices.chunks_exact(4) {
+            let pos = |i: usize| to_pixels(projection, quad[i].position(), width, height);
+            let corners = [
+                (pos(0), quad[0].tex_coord()),
+                (pos(1), quad[1].tex_coord()),
+                (pos(2), quad[2].tex_coord()),
+                (pos(3), quad[3].tex_coord()),
+            ];
+
+            for &[a, b, c] in &[[0, 1, 2], [0, 2, 3]] {
+                let triangle = [corners[a], corners[b], corners[c]];
+                self.triangle(triangle, texture, sampling, blend);
+            }
+        }
+    }
+
+    fn triangle(
+        &mut self,
+        mut v: [([f32; 2], [f32; 2]); 3],
+        texture: &Texture,
+        sampling: Sampling,
+        blend_state: &Blend,
+    ) {
+        // pixel rows go down, so counter-clockwise triangles in clip space
+        // have a negative area here
+        let area = edge(v[0].0, v[1].0, v[2].0);
+        if area >= 0.0 {
+            return;
+        }
+        v.swap(1, 2);
+        let area = -area;
+
+        let [(p0, uv0), (p1, uv1), (p2, uv2)] = v;
+        let min_x = p0[0].min(p1[0]).min(p2[0]).floor().max(0.0) as u32;
+        let min_y = p0[1].min(p1[1]).min(p2[1]).floor().max(0.0) as u32;
+        let max_x = (p0[0].max(p1[0]).max(p2[0]).ceil().max(0.0) as u32).min(self.width);
+        let max_y = (p0[1].max(p1[1]).max(p2[1]).ceil().max(0.0) as u32).min(self.height);
+
+        let edges = [(p1, p2), (p2, p0), (p0, p1)];
+        let top_left = [
+            is_top_left(p1, p2),
+            is_top_left(p2, p0),
+            is_top_left(p0, p1),
+        ];
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = [x as f32 + 0.5, y as f32 + 0.5];
+
+                let mut w = [0.0; 3];
+                let mut inside = true;
+                for i in 0..3 {
+                    w[i] = edge(edges[i].0, edges[i].1, p);
+                    inside &= w[i] > 0.0 || (w[i] == 0.0 && top_left[i]);
+                }
+                if !inside {
+                    continue;
+                }
+
+                let [l0, l1, l2] = [w[0] / area, w[1] / area, w[2] / area];
+                let u = uv0[0] * l0 + uv1[0] * l1 + uv2[0] * l2;
+                let v = uv0[1] * l0 + uv1[1] * l1 + uv2[1] * l2;
+
+                let src = texture.sample([u, v], sampling);
+                let texel = &mut self.texels[(y * self.width + x) as usize];
+                *texel = blend(blend_state, src, *texel);
+            }
+        }
+    }
+
+    /// Converts to the formats [`Texture::new`] reads, the way a render pass
+    /// stores them: color is sRGB encoded for sRGB formats and channels the
+    /// format lacks are dropped.
+    pub fn to_image(&self, format: wgpu::TextureFormat) -> Result<ImageSource, Error> {
+        use wgpu::TextureFormat::*;
+
+        let (bgra, channels) = match format {
+            Rgba8Unorm | Rgba8UnormSrgb | Rgba16Float => (false, 4),
+            Bgra8Unorm | Bgra8UnormSrgb => (true, 4),
+            R8Unorm => (false, 1),
+            Rg8Unorm => (false, 2),
+            format => {
+                let message = format!("unsupported texture format {:?}", format);
+                return Err(Error::Format(message));
+            }
+        };
+        let srgb = is_srgb(format);
+
+        let mut texels = Vec::with_capacity(self.texels.len() * channels);
+        for &[r, g, b, a] in &self.texels {
+            let [r, b] = if bgra { [b, r] } else { [r, b] };
+            let mut texel = [r, g, b, a];
+            if srgb {
+                for c in &mut texel[..3] {
+                    *c = linear_to_srgb(*c);
+                }
+            }
+
+            for &c in &texel[..channels] {
+                if format == Rgba16Float {
+                    texels.extend_from_slice(&f32_to_f16(c).to_le_bytes());
+                } else {
+                    texels.push((c.max(0.0).min(1.0) * 255.0).round() as u8);
+                }
+            }
+        }
+
+        Ok(ImageSource::new(format, texels, self.width, self.height))
+    }
+}
+
+fn is_srgb(format: wgpu::TextureFormat) -> bool {
+    use wgpu::TextureFormat::*;
+
+    matches!(format, Rgba8UnormSrgb | Bgra8UnormSrgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        batch::Quads,
+        blend,
+        math::{projection, Matrix, Point},
+        sprite::trimmed_vertices,
+    };
+
+    const EPSILON: f32 = 1e-5;
+
+    fn solid(color: [u8; 4]) -> Texture {
+        let source = ImageSource::new(wgpu::TextureFormat::Rgba8Unorm, color.to_vec(), 1, 1);
+        Texture::new(&source).unwrap()
+    }
+
+    fn sprite([min_x, min_y]: [f32; 2], [max_x, max_y]: [f32; 2]) -> [Vertex; 4] {
+        [
+            Vertex::new(max_x, max_y, 1.0, 1.0),
+            Vertex::new(max_x, min_y, 1.0, 0.0),
+            Vertex::new(min_x, min_y, 0.0, 0.0),
+            Vertex::new(min_x, max_y, 0.0, 1.0),
+        ]
+    }
+
+    fn assert_color(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(&expected) {
+            assert!((a - e).abs() < EPSILON, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn shared_edges_are_drawn_once() {
+        let mut canvas = Canvas::new(4, 4);
+        let proj = projection(0.0, 0.0, 4.0, 4.0, 1.0);
+        let texture = solid([64, 64, 64, 64]);
+
+        let mut quads = sprite([0.0, 0.0], [2.0, 4.0]).to_vec();
+        quads.extend_from_slice(&sprite([2.0, 0.0], [4.0, 4.0]));
+        canvas.draw(&proj, &quads, &texture, Sampling::Nearest, &blend::PMA_ADD);
+
+        let expected = [64.0 / 255.0; 4];
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_color(canvas.get(x, y), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn premultiplied_blending() {
+        let mut canvas = Canvas::new(1, 1);
+        canvas.clear([1.0, 0.0, 0.0, 1.0]);
+        let proj = projection(0.0, 0.0, 1.0, 1.0, 1.0);
+        let quad = sprite([0.0, 0.0], [1.0, 1.0]);
+
+        // half transparent green
+        let texture = solid([0, 0x80, 0, 0x80]);
+        canvas.draw(
+            &proj,
+            &quad,
+            &texture,
+            Sampling::Nearest,
+            &blend::PMA_NORMAL,
+        );
+
+        let a = 0x80 as f32 / 255.0;
+        assert_color(canvas.get(0, 0), [1.0 - a, a, 0.0, 1.0]);
+
+        canvas.draw(
+            &proj,
+            &quad,
+            &texture,
+            Sampling::Nearest,
+            &blend::PMA_DARKEN,
+        );
+        assert_color(canvas.get(0, 0), [0.0, a, 0.0, 1.0]);
+
+        canvas.draw(
+            &proj,
+            &quad,
+            &texture,
+            Sampling::Nearest,
+            &blend::PMA_SUBTRACT,
+        );
+        assert_color(canvas.get(0, 0), [0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn projection_origin_and_scale() {
+        let mut canvas = Canvas::new(4, 4);
+        // logical 2x2 view starting at (1, 1), two physical pixels per unit
+        let proj = projection(1.0, 1.0, 4.0, 4.0, 2.0);
+        let texture = solid([0xFF; 4]);
+
+        let quad = sprite([2.0, 1.0], [3.0, 2.0]);
+        canvas.draw(
+            &proj,
+            &quad,
+            &texture,
+            Sampling::Nearest,
+            &blend::PMA_NORMAL,
+        );
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let lit = x >= 2 && y < 2;
+                assert_eq!(
+                    canvas.get(x, y)[3],
+                    if lit { 1.0 } else { 0.0 },
+                    "{} {}",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn transformed_sprite() {
+        let mut canvas = Canvas::new(4, 4);
+        let proj = projection(0.0, 0.0, 4.0, 4.0, 1.0);
+        let texture = solid([0xFF; 4]);
+
+        // 2x1 sprite rotated by 90 degrees around its center at (2, 2)
+        let matrix = Matrix::new(0.0, 1.0, -1.0, 0.0, 2.0, 2.0);
+        let anchor = Point { x: 0.5, y: 0.5 };
+        let [a, b, c, d] = trimmed_vertices(&matrix, anchor, 2.0, 1.0);
+        let quad = [
+            Vertex::new(c[0], c[1], 1.0, 1.0),
+            Vertex::new(b[0], b[1], 1.0, 0.0),
+            Vertex::new(a[0], a[1], 0.0, 0.0),
+            Vertex::new(d[0], d[1], 0.0, 1.0),
+        ];
+        canvas.draw(
+            &proj,
+            &quad,
+            &texture,
+            Sampling::Nearest,
+            &blend::PMA_NORMAL,
+        );
+
+        let lit: Vec<_> = (0..16)
+            .filter(|i| canvas.get(i % 4, i / 4)[3] > 0.0)
+            .map(|i| (i % 4, i / 4))
+            .collect();
+        assert_eq!(lit, vec![(1, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn clockwise_quads_are_culled() {
+        let mut canvas = Canvas::new(2, 2);
+        let proj = projection(0.0, 0.0, 2.0, 2.0, 1.0);
+        let texture = solid([0xFF; 4]);
+
+        // top-left, top-right, bottom-right, bottom-left
+        let quad = [
+            Vertex::new(0.0, 0.0, 0.0, 0.0),
+            Vertex::new(2.0, 0.0, 1.0, 0.0),
+            Vertex::new(2.0, 2.0, 1.0, 1.0),
+            Vertex::new(0.0, 2.0, 0.0, 1.0),
+        ];
+        canvas.draw(
+            &proj,
+            &quad,
+            &texture,
+            Sampling::Nearest,
+            &blend::PMA_NORMAL,
+        );
+
+        for y in 0..2 {
+            for x in 0..2 {
+                assert_eq!(canvas.get(x, y), [0.0; 4], "{} {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn sampling() {
+        let texels = vec![0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        let source = ImageSource::new(wgpu::TextureFormat::Rgba8Unorm, texels, 2, 1);
+        let texture = Texture::new(&source).unwrap();
+
+        let proj = projection(0.0, 0.0, 4.0, 1.0, 1.0);
+        let quad = sprite([0.0, 0.0], [4.0, 1.0]);
+        let assert_red = |canvas: &Canvas, expected: [f32; 4]| {
+            for (x, &red) in expected.iter().enumerate() {
+                assert_color(canvas.get(x as u32, 0), [red, red, red, 1.0]);
+            }
+        };
+
+        let mut canvas = Canvas::new(4, 1);
+        canvas.draw(&proj, &quad, &texture, Sampling::Nearest, &blend::REPLACE);
+        assert_red(&canvas, [0.0, 0.0, 1.0, 1.0]);
+
+        canvas.draw(&proj, &quad, &texture, Sampling::Linear, &blend::REPLACE);
+        assert_red(&canvas, [0.0, 0.25, 0.75, 1.0]);
+    }
+
+    #[test]
+    fn decodes_formats() {
+        use wgpu::TextureFormat::*;
+
+        let decode = |format, texels: Vec<u8>| {
+            let source = ImageSource::new(format, texels, 1, 1);
+            Texture::new(&source).unwrap().texel(0, 0)
+        };
+
+        assert_color(decode(R8Unorm, vec![0xFF]), [1.0, 0.0, 0.0, 1.0]);
+        assert_color(decode(Rg8Unorm, vec![0, 0xFF]), [0.0, 1.0, 0.0, 1.0]);
+        assert_color(
+            decode(Bgra8Unorm, vec![0xFF, 0, 0, 0]),
+            [0.0, 0.0, 1.0, 0.0],
+        );
+
+        let half = |c: f32| f32_to_f16(c).to_le_bytes().to_vec();
+        let texels = [0.25, 0.5, 0.75, 1.0].iter().flat_map(|&c| half(c));
+        let texel = decode(Rgba16Float, texels.collect());
+        assert_color(texel, [0.25, 0.5, 0.75, 1.0]);
+
+        let texel = decode(Rgba8UnormSrgb, vec![188, 188, 188, 188]);
+        assert!((texel[0] - 0.5).abs() < 0.005, "{:?}", texel);
+        assert_eq!(texel[3], 188.0 / 255.0);
+
+        let source = ImageSource::new(Rgba32Float, vec![0; 16], 1, 1);
+        assert!(matches!(Texture::new(&source), Err(Error::Format(_))));
+    }
+
+    #[test]
+    fn to_image_encodes_srgb() {
+        use wgpu::TextureFormat::*;
+
+        let mut canvas = Canvas::new(1, 1);
+        canvas.clear([0.5, 0.0, 1.0, 0.5]);
+
+        let image = canvas.to_image(Rgba8Unorm).unwrap();
+        assert_eq!(image.texels, [128, 0, 255, 128]);
+        let image = canvas.to_image(Bgra8UnormSrgb).unwrap();
+        assert_eq!(image.texels, [255, 0, 188, 128]);
+        let image = canvas.to_image(Rg8Unorm).unwrap();
+        assert_eq!(image.texels, [128, 0]);
+
+        let image = canvas.to_image(Rgba8UnormSrgb).unwrap();
+        let texture = Texture::new(&image).unwrap();
+        assert!((texture.texel(0, 0)[0] - 0.5).abs() < 0.005);
+
+        assert!(canvas.to_image(Depth32Float).is_err());
+    }
+
+    #[test]
+    fn batch_runs_keep_blend_order() {
+        let mut quads = Quads::new(blend::PMA_NORMAL);
+        quads.add_sprite([0.0, 0.0], [1.0, 1.0]);
+        assert_eq!(quads.set_blend(blend::PMA_ADD), 1);
+        quads.add_sprite([0.0, 0.0], [1.0, 1.0]);
+        quads.add_sprite([0.0, 0.0], [1.0, 1.0]);
+        assert_eq!(quads.set_blend(blend::PMA_NORMAL), 0);
+        quads.add_sprite([0.0, 0.0], [1.0, 1.0]);
+
+        let runs: Vec<_> = quads.runs().map(|(b, v)| (b.clone(), v.len())).collect();
+        let expected = vec![
+            (blend::PMA_NORMAL, 4),
+            (blend::PMA_ADD, 8),
+            (blend::PMA_NORMAL, 4),
+        ];
+        assert_eq!(runs, expected);
+
+        let mut canvas = Canvas::new(1, 1);
+        let proj = projection(0.0, 0.0, 1.0, 1.0, 1.0);
+        let texture = solid([0x40, 0, 0, 0x40]);
+        canvas.draw_quads(&proj, &quads, &texture, Sampling::Nearest);
+
+        // normal, add twice, normal
+        let s = 0x40 as f32 / 255.0;
+        let normal = |d: f32| s + d * (1.0 - s);
+        let expected = normal(normal(0.0) + s + s);
+        assert_color(canvas.get(0, 0), [expected, 0.0, 0.0, expected]);
+    }
+
+    #[test]
+    fn per_sprite_blend_keeps_current_blend() {
+        let mut quads = Quads::new(blend::PMA_NORMAL);
+        let add = quads.blend_index(blend::PMA_ADD);
+        assert_eq!(add, 1);
+        assert_eq!(quads.blend(), &blend::PMA_NORMAL);
+
+        quads.add_sprite([0.0, 0.0], [1.0, 1.0]);
+        quads.add_sprite_blend([0.0, 0.0], [1.0, 1.0], add);
+        quads.add_sprite([0.0, 0.0], [1.0, 1.0]);
+
+        let runs: Vec<_> = quads.runs().map(|(b, v)| (b.clone(), v.len())).collect();
+        let expected = vec![
+            (blend::PMA_NORMAL, 4),
+            (blend::PMA_ADD, 4),
+            (blend::PMA_NORMAL, 4),
+        ];
+        assert_eq!(runs, expected);
+    }
+}
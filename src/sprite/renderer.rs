@@ -1,40 +1,30 @@
 use crate::{
     blend::{self, Blend},
-    layout::{Layout, Shader},
+    layout::{Layout, PipelineKey, ShaderId},
 };
+use std::sync::Arc;
 
 pub struct SpritePipeline {
-    pub pipeline: wgpu::RenderPipeline,
+    pub pipeline: Arc<wgpu::RenderPipeline>,
 }
 
 impl SpritePipeline {
     pub fn new(
         device: &wgpu::Device,
         layout: &Layout,
-        shader: &Shader,
         format: wgpu::TextureFormat,
         blend: Blend,
     ) -> Self {
-        let color_state = blend.into_color_state(format);
-        let pipeline = layout.create_pipeline(device, &shader, color_state);
+        let key = PipelineKey::new(ShaderId::Sprite, blend, format);
+        let pipeline = layout.cached_pipeline(device, key);
         Self { pipeline }
     }
 
-    pub fn normal(
-        device: &wgpu::Device,
-        layout: &Layout,
-        shader: &Shader,
-        format: wgpu::TextureFormat,
-    ) -> Self {
-        Self::new(device, layout, shader, format, blend::PMA_NORMAL)
+    pub fn normal(device: &wgpu::Device, layout: &Layout, format: wgpu::TextureFormat) -> Self {
+        Self::new(device, layout, format, blend::PMA_NORMAL)
     }
 
-    pub fn replace(
-        device: &wgpu::Device,
-        layout: &Layout,
-        shader: &Shader,
-        format: wgpu::TextureFormat,
-    ) -> Self {
-        Self::new(device, layout, shader, format, blend::REPLACE)
+    pub fn replace(device: &wgpu::Device, layout: &Layout, format: wgpu::TextureFormat) -> Self {
+        Self::new(device, layout, format, blend::REPLACE)
     }
 }
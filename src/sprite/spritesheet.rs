@@ -1,11 +1,7 @@
 pub mod raw {
+    use crate::Error;
     use std::{collections::HashMap, fs::File, io::prelude::*, path::Path, str::FromStr};
 
-    pub enum Error {
-        Json(serde_json::Error),
-        Io(std::io::Error),
-    }
-
     #[derive(serde::Deserialize)]
     pub struct Rect {
         pub x: f32,
@@ -59,8 +55,8 @@ pub mod raw {
 
     impl Sheet {
         pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
-            let rdr = File::open(path).map_err(Error::Io)?;
-            Self::from_reader(rdr).map_err(Error::Json)
+            let rdr = File::open(path)?;
+            Ok(Self::from_reader(rdr)?)
         }
 
         pub fn from_reader<R: Read>(rdr: R) -> serde_json::Result<Self> {
@@ -1,18 +1,58 @@
-use crate::{cast_slice, layout::Layout, math::projection};
+use crate::{
+    cast_slice,
+    image::{bytes_per_texel, ImageSource},
+    layout::Layout,
+    math::projection,
+    utils::{align, write_buffer, COPY_ROW_ALIGNMENT},
+    Error,
+};
 
 pub struct Target<'a> {
     pub view: &'a wgpu::TextureView,
+    /// Single sampled view `view` is resolved into, if it's multisampled.
+    pub resolve_target: Option<&'a wgpu::TextureView>,
+    /// Samples per pixel of `view`, pipelines drawing into it must use the same.
+    pub sample_count: u32,
     pub width: u32,
     pub height: u32,
     pub scale: f32,
+    /// Logical position of the top-left corner of the view.
+    pub origin: [f32; 2],
 }
 
 impl<'a> Target<'a> {
+    /// Single sampled `view` with its top-left corner at the logical origin.
+    ///
+    /// Other fields can be changed with struct update syntax, e.g.
+    /// `Target { origin, ..target }`.
+    pub fn new(view: &'a wgpu::TextureView, width: u32, height: u32, scale: f32) -> Self {
+        Self {
+            view,
+            resolve_target: None,
+            sample_count: 1,
+            width,
+            height,
+            scale,
+            origin: [0.0, 0.0],
+        }
+    }
+
+    /// Draws into the multisampled `msaa` view instead, resolved into the
+    /// current view.
+    pub fn multisampled(self, msaa: &'a wgpu::TextureView, sample_count: u32) -> Self {
+        Self {
+            view: msaa,
+            resolve_target: Some(self.view),
+            sample_count,
+            ..self
+        }
+    }
+
     pub fn rpass(&self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
         encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
                 attachment: &self.view,
-                resolve_target: None,
+                resolve_target: self.resolve_target,
                 load_op: wgpu::LoadOp::Load,
                 store_op: wgpu::StoreOp::Store,
                 clear_color: wgpu::Color::TRANSPARENT,
@@ -23,23 +63,73 @@ impl<'a> Target<'a> {
 
     pub fn projection(&self, device: &wgpu::Device, layout: &Layout) -> wgpu::BindGroup {
         let (width, height) = (self.width as f32, self.height as f32);
+        let [x, y] = self.origin;
         let usage = wgpu::BufferUsage::UNIFORM;
-        let matrix = projection(0.0, 0.0, width, height, self.scale);
+        let matrix = projection(x, y, width, height, self.scale);
         let buffer = device.create_buffer_with_data(cast_slice(&matrix), usage);
 
         layout.bind_projection(device, &buffer)
     }
 }
 
+/// Projection uniform that is only rewritten when the target changes.
+pub struct Projection {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    key: Option<(u32, u32, f32, [f32; 2])>,
+}
+
+impl Projection {
+    /// `layout` must be the one the pipelines using this projection are created with.
+    pub fn new(device: &wgpu::Device, layout: &Layout) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("projection"),
+            size: 4 * 16,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+        let bind_group = layout.bind_projection(device, &buffer);
+
+        Self {
+            buffer,
+            bind_group,
+            key: None,
+        }
+    }
+
+    /// Updates the projection for `target` and returns its bind group.
+    pub fn update(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        target: &Target,
+    ) -> &wgpu::BindGroup {
+        let key = (target.width, target.height, target.scale, target.origin);
+        if self.key != Some(key) {
+            let (width, height) = (target.width as f32, target.height as f32);
+            let [x, y] = target.origin;
+            let matrix = projection(x, y, width, height, target.scale);
+            write_buffer(encoder, device, &self.buffer, 0, cast_slice(&matrix));
+            self.key = Some(key);
+        }
+        &self.bind_group
+    }
+}
+
 pub struct RenderTarget {
+    /// Single sampled texture, multisampled drawing is resolved into it.
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub bind_group: wgpu::BindGroup,
     pub width: u32,
     pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
+
+    msaa: Option<(wgpu::Texture, wgpu::TextureView)>,
 }
 
 impl RenderTarget {
+    /// `sample_count` is 1 or 2, 4 or 8 for a multisampled color attachment.
     pub fn new(
         device: &wgpu::Device,
         layout: &Layout,
@@ -47,20 +137,28 @@ impl RenderTarget {
         format: wgpu::TextureFormat,
         width: u32,
         height: u32,
+        sample_count: u32,
     ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+
+        let msaa = multisampled(device, format, size, sample_count);
+
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("render target"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth: 1,
-            },
+            size,
             array_layer_count: 1,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            usage: wgpu::TextureUsage::SAMPLED
+                | wgpu::TextureUsage::OUTPUT_ATTACHMENT
+                | wgpu::TextureUsage::COPY_SRC
+                | wgpu::TextureUsage::COPY_DST,
         });
 
         let view = texture.create_default_view();
@@ -72,14 +170,27 @@ impl RenderTarget {
             bind_group,
             width,
             height,
+            format,
+            sample_count,
+
+            msaa,
+        }
+    }
+
+    /// Color attachment and its resolve target.
+    fn attachment(&self) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match &self.msaa {
+            Some((_, msaa)) => (msaa, Some(&self.view)),
+            None => (&self.view, None),
         }
     }
 
     pub fn clear_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        let (attachment, resolve_target) = self.attachment();
         encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                attachment: &self.view,
-                resolve_target: None,
+                attachment,
+                resolve_target,
                 load_op: wgpu::LoadOp::Clear,
                 store_op: wgpu::StoreOp::Store,
                 clear_color: wgpu::Color::TRANSPARENT,
@@ -88,12 +199,125 @@ impl RenderTarget {
         })
     }
 
+    /// Clears to transparent black with an otherwise empty render pass.
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        let _rpass = self.clear_pass(encoder);
+    }
+
+    pub fn load_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        let (attachment, resolve_target) = self.attachment();
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment,
+                resolve_target,
+                load_op: wgpu::LoadOp::Load,
+                store_op: wgpu::StoreOp::Store,
+                clear_color: wgpu::Color::TRANSPARENT,
+            }],
+            depth_stencil_attachment: None,
+        })
+    }
+
+    /// Copies the texture into memory, waiting for the GPU.
+    ///
+    /// Texels keep the format of the target, `unpremul` converts them from
+    /// premultiplied alpha.
+    pub fn read_pixels(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        unpremul: bool,
+    ) -> Result<ImageSource, Error> {
+        futures::executor::block_on(self.read_pixels_async(device, queue, unpremul))
+    }
+
+    pub async fn read_pixels_async(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        unpremul: bool,
+    ) -> Result<ImageSource, Error> {
+        let row = (self.width * bytes_per_texel(self.format)) as wgpu::BufferAddress;
+        let padded_row = align(row, COPY_ROW_ALIGNMENT);
+        let size = padded_row * self.height as wgpu::BufferAddress;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("read pixels"),
+            size,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let label = Some("read pixels");
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label });
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &self.texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::BufferCopyView {
+                buffer: &buffer,
+                offset: 0,
+                bytes_per_row: padded_row as u32,
+                rows_per_image: 0,
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth: 1,
+            },
+        );
+        queue.submit(&[encoder.finish()]);
+
+        let mapping = buffer.map_read(0, size);
+        device.poll(wgpu::Maintain::Wait);
+        let mapping = mapping.await?;
+
+        let mut texels = Vec::with_capacity((row * self.height as wgpu::BufferAddress) as usize);
+        for padded in mapping.as_slice().chunks_exact(padded_row as usize) {
+            texels.extend_from_slice(&padded[..row as usize]);
+        }
+
+        let source = ImageSource::new(self.format, texels, self.width, self.height);
+        Ok(if unpremul { source.unpremul() } else { source })
+    }
+
     pub fn target(&self, scale: f32) -> Target {
-        Target {
-            view: &self.view,
-            width: self.width,
-            height: self.height,
-            scale,
+        let target = Target::new(&self.view, self.width, self.height, scale);
+        match &self.msaa {
+            Some((_, msaa)) => target.multisampled(msaa, self.sample_count),
+            None => target,
         }
     }
 }
+
+/// Multisampled color attachment, `None` if `sample_count` is 1.
+pub(crate) fn multisampled(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+    sample_count: u32,
+) -> Option<(wgpu::Texture, wgpu::TextureView)> {
+    assert!(
+        [1, 2, 4, 8].contains(&sample_count),
+        "unsupported sample count {}",
+        sample_count
+    );
+    if sample_count == 1 {
+        return None;
+    }
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("multisampled framebuffer"),
+        size,
+        array_layer_count: 1,
+        mip_level_count: 1,
+        sample_count,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+    });
+    let view = texture.create_default_view();
+    Some((texture, view))
+}
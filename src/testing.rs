@@ -0,0 +1,203 @@
+//! Golden image tests.
+//!
+//! Scenes are rendered offscreen and compared with PNGs stored next to the
+//! tests. A missing reference is an error, set `PIXI_BLESS=1` to write
+//! references from the rendered images and review them before committing.
+//!
+//! Tests are skipped without an adapter, set `PIXI_REQUIRE_ADAPTER=1` to fail
+//! instead, e.g. on CI. Works with software rasterizers, e.g. lavapipe selected
+//! with `VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json`.
+//!
+//! Needs the `testing` feature: `cargo test --features testing`.
+
+use crate::{context::Context, image::ImageSource, layout::Layout, target::RenderTarget};
+use std::path::PathBuf;
+
+/// Texels differing by more than the tolerance in any channel.
+pub struct Mismatch {
+    pub count: usize,
+    /// Opaque red where texels differ, faded expected image elsewhere.
+    pub diff: ImageSource,
+}
+
+/// Compares 8-bit images of the same format channel by channel.
+///
+/// Returns `None` if all channels are within `tolerance`.
+pub fn compare(expected: &ImageSource, actual: &ImageSource, tolerance: u8) -> Option<Mismatch> {
+    let same_size = expected.width == actual.width && expected.height == actual.height;
+    let (width, height) = (actual.width, actual.height);
+
+    let mut count = 0;
+    let mut texels = Vec::with_capacity(actual.texels.len());
+    if same_size && expected.texels.len() == actual.texels.len() {
+        let pairs = expected
+            .texels
+            .chunks_exact(4)
+            .zip(actual.texels.chunks_exact(4));
+        for (e, a) in pairs {
+            let differs = e.iter().zip(a).any(|(&e, &a)| {
+                let delta = (e as i16 - a as i16).abs();
+                delta > tolerance as i16
+            });
+            if differs {
+                count += 1;
+                texels.extend_from_slice(&[0xFF, 0, 0, 0xFF]);
+            } else {
+                texels.extend(e[..3].iter().map(|&c| c / 4));
+                texels.push(0xFF);
+            }
+        }
+    } else {
+        count = (width * height) as usize;
+        texels.resize(actual.texels.len(), 0xFF);
+    }
+
+    if count == 0 {
+        return None;
+    }
+
+    let diff = ImageSource::new(actual.format, texels, width, height);
+    Some(Mismatch { count, diff })
+}
+
+/// Offscreen renderer for golden image tests.
+pub struct Golden {
+    pub context: Context,
+    pub layout: Layout,
+    pub sampler: wgpu::Sampler,
+    pub format: wgpu::TextureFormat,
+    /// Directory of the reference PNGs.
+    pub dir: PathBuf,
+    /// Maximum difference per channel.
+    pub tolerance: u8,
+}
+
+/// Headless context for GPU tests, `None` if there is no adapter so tests
+/// can be skipped.
+///
+/// Panics instead if `PIXI_REQUIRE_ADAPTER=1` is set.
+pub fn context() -> Option<Context> {
+    let context = Context::headless();
+    if context.is_none() && env_flag("PIXI_REQUIRE_ADAPTER") {
+        panic!("no adapter, but PIXI_REQUIRE_ADAPTER=1 is set");
+    }
+    context
+}
+
+impl Golden {
+    /// Returns `None` if there is no adapter, see [`context`].
+    pub fn new(dir: impl Into<PathBuf>) -> Option<Self> {
+        let context = context()?;
+        let layout = Layout::new(&context.device);
+        let sampler = crate::nearest_sampler(&context.device);
+
+        Some(Self {
+            context,
+            layout,
+            sampler,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            dir: dir.into(),
+            tolerance: 2,
+        })
+    }
+
+    /// Renders into a cleared target of `width` by `height` pixels and reads it back.
+    pub fn render(
+        &self,
+        width: u32,
+        height: u32,
+        draw: impl FnOnce(&mut wgpu::CommandEncoder, &Self, &RenderTarget),
+    ) -> ImageSource {
+        let Context { device, queue, .. } = &self.context;
+        let (layout, sampler, format) = (&self.layout, &self.sampler, self.format);
+        let target = RenderTarget::new(device, layout, sampler, format, width, height, 1);
+
+        let label = Some("golden");
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label });
+        target.clear(&mut encoder);
+        draw(&mut encoder, self, &target);
+        layout.submit(queue, encoder);
+
+        target
+            .read_pixels(device, queue, false)
+            .expect("failed to read pixels")
+    }
+
+    /// Compares `actual` with `<dir>/<name>.png`.
+    ///
+    /// With `PIXI_BLESS=1` the reference is written instead. On mismatch `<name>.actual.png` and `<name>.diff.png` are written next
+    /// to the reference and the error describes them.
+    pub fn check(&self, name: &str, actual: &ImageSource) -> Result<(), String> {
+        let path = self.dir.join(format!("{}.png", name));
+        if env_flag("PIXI_BLESS") {
+            std::fs::create_dir_all(&self.dir).map_err(|err| err.to_string())?;
+            return actual.save_png(&path).map_err(|err| err.to_string());
+        }
+
+        if !path.exists() {
+            return Err(format!(
+                "{}: missing reference, run with PIXI_BLESS=1 to write it",
+                path.display(),
+            ));
+        }
+
+        let expected = image::open(&path).map_err(|err| err.to_string())?.to_rgba();
+        let (width, height) = expected.dimensions();
+        let expected = ImageSource::new(actual.format, expected.into_raw(), width, height);
+
+        match compare(&expected, actual, self.tolerance) {
+            None => Ok(()),
+            Some(Mismatch { count, diff }) => {
+                let actual_path = self.dir.join(format!("{}.actual.png", name));
+                let diff_path = self.dir.join(format!("{}.diff.png", name));
+                let _ = actual.save_png(&actual_path);
+                let _ = diff.save_png(&diff_path);
+
+                Err(format!(
+                    "{}: {} texels differ by more than {}, see {}",
+                    path.display(),
+                    count,
+                    self.tolerance,
+                    diff_path.display(),
+                ))
+            }
+        }
+    }
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var_os(name).map_or(false, |v| v == "1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(texels: Vec<u8>) -> ImageSource {
+        let width = texels.len() as u32 / 4;
+        ImageSource::new(wgpu::TextureFormat::Rgba8Unorm, texels, width, 1)
+    }
+
+    #[test]
+    fn within_tolerance() {
+        let expected = source(vec![10, 20, 30, 255, 0, 0, 0, 0]);
+        let actual = source(vec![12, 18, 30, 254, 0, 0, 0, 2]);
+        assert!(compare(&expected, &actual, 2).is_none());
+    }
+
+    #[test]
+    fn mismatch() {
+        let expected = source(vec![10, 20, 30, 255, 0, 0, 0, 0]);
+        let actual = source(vec![10, 20, 30, 255, 0, 0, 0, 3]);
+        let mismatch = compare(&expected, &actual, 2).unwrap();
+        assert_eq!(mismatch.count, 1);
+        assert_eq!(&mismatch.diff.texels[4..], &[0xFF, 0, 0, 0xFF]);
+    }
+
+    #[test]
+    fn size_mismatch() {
+        let expected = source(vec![0; 8]);
+        let actual = source(vec![0; 12]);
+        assert_eq!(compare(&expected, &actual, 255).unwrap().count, 3);
+    }
+}
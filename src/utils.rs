@@ -1,3 +1,10 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll},
+};
+
 pub(crate) fn quad_indices16() -> impl Iterator<Item = u16> {
     (0..(0x1_0000 / 4) * 6).map(|i| (i / 6 * 4 + [0, 1, 2, 0, 2, 3][i % 6]) as u16)
 }
@@ -6,3 +13,229 @@ pub(crate) fn quad_indices16() -> impl Iterator<Item = u16> {
 pub(crate) fn quad_indices32() -> impl Iterator<Item = u32> {
     (0..(0x1_0000_0000 / 4) * 6).map(|i| (i / 6 * 4 + [0, 1, 2, 0, 2, 3][i % 6]) as u32)
 }
+
+/// Copies `data` into `dst` through a staging buffer.
+pub(crate) fn write_buffer(
+    encoder: &mut wgpu::CommandEncoder,
+    device: &wgpu::Device,
+    dst: &wgpu::Buffer,
+    offset: wgpu::BufferAddress,
+    data: &[u8],
+) {
+    if data.is_empty() {
+        return;
+    }
+
+    let size = data.len() as wgpu::BufferAddress;
+    let staging = device.create_buffer_with_data(data, wgpu::BufferUsage::COPY_SRC);
+    encoder.copy_buffer_to_buffer(&staging, 0, dst, offset, size);
+}
+
+/// Staging buffers of [`RingBuffer`] writes, reused across frames.
+///
+/// Each write fills a mapped buffer, unmaps it and records a copy. Buffers are
+/// mapped again when [`Layout::submit`](crate::layout::Layout::submit) submits
+/// the copies and become free once the mapping resolves. Without recalls, the
+/// oldest buffers are dropped instead.
+#[derive(Clone, Default)]
+pub(crate) struct StagingPool {
+    inner: Arc<Mutex<Staging>>,
+}
+
+type MapWrite = Pin<Box<dyn Future<Output = MapWriteResult> + Send>>;
+type MapWriteResult = Result<wgpu::BufferWriteMapping, wgpu::BufferAsyncErr>;
+
+struct Chunk {
+    buffer: wgpu::Buffer,
+    size: wgpu::BufferAddress,
+}
+
+#[derive(Default)]
+struct Staging {
+    /// Mapped and ready for writing.
+    free: Vec<(Chunk, wgpu::BufferWriteMapping)>,
+    /// Read by recorded copies, which may not be submitted yet.
+    used: Vec<Chunk>,
+    /// Waiting for submitted copies to finish.
+    pending: Vec<(Chunk, MapWrite)>,
+}
+
+impl StagingPool {
+    const MIN_SIZE: wgpu::BufferAddress = 4096;
+    /// Used buffers kept until the next recall.
+    const MAX_USED: usize = 256;
+
+    /// Copies `data` into `dst` through a staging buffer from the pool.
+    pub fn write(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        dst: &wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        data: &[u8],
+    ) {
+        if data.is_empty() {
+            return;
+        }
+
+        let mut staging = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        staging.poll(device);
+
+        let size = data.len() as wgpu::BufferAddress;
+        let chunk = match staging.take(size) {
+            Some((chunk, mut mapping)) => {
+                // dropping the mapping unmaps the buffer
+                mapping.as_slice()[..data.len()].copy_from_slice(data);
+                chunk
+            }
+            None => {
+                let size = size.next_power_of_two().max(Self::MIN_SIZE);
+                let mut mapped = device.create_buffer_mapped(&wgpu::BufferDescriptor {
+                    label: Some("staging"),
+                    size,
+                    usage: wgpu::BufferUsage::MAP_WRITE | wgpu::BufferUsage::COPY_SRC,
+                });
+                mapped.data[..data.len()].copy_from_slice(data);
+                let buffer = mapped.finish();
+                Chunk { buffer, size }
+            }
+        };
+
+        encoder.copy_buffer_to_buffer(&chunk.buffer, 0, dst, offset, size);
+
+        staging.used.push(chunk);
+        if staging.used.len() > Self::MAX_USED {
+            staging.used.remove(0);
+        }
+    }
+
+    /// Maps buffers of submitted writes again, to be reused once the GPU is
+    /// done with them.
+    pub fn recall(&self) {
+        let mut staging = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        for chunk in std::mem::take(&mut staging.used) {
+            let mapping: MapWrite = Box::pin(chunk.buffer.map_write(0, chunk.size));
+            staging.pending.push((chunk, mapping));
+        }
+    }
+}
+
+impl Staging {
+    /// Moves buffers with resolved mappings into the free list.
+    fn poll(&mut self, device: &wgpu::Device) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        device.poll(wgpu::Maintain::Poll);
+
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let mut index = 0;
+        while index < self.pending.len() {
+            match self.pending[index].1.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => {
+                    let (chunk, _) = self.pending.swap_remove(index);
+                    if let Ok(mapping) = result {
+                        self.free.push((chunk, mapping));
+                    }
+                }
+                Poll::Pending => index += 1,
+            }
+        }
+    }
+
+    /// Smallest free buffer of at least `size` bytes.
+    fn take(&mut self, size: wgpu::BufferAddress) -> Option<(Chunk, wgpu::BufferWriteMapping)> {
+        let index = (0..self.free.len())
+            .filter(|&i| self.free[i].0.size >= size)
+            .min_by_key(|&i| self.free[i].0.size)?;
+        Some(self.free.swap_remove(index))
+    }
+}
+
+/// GPU buffer reused across frames.
+///
+/// Each write is staged into the next free range and wraps around to the start
+/// once the end is reached. The buffer grows when a write doesn't fit at all.
+pub(crate) struct RingBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsage,
+    buffer: wgpu::Buffer,
+    capacity: wgpu::BufferAddress,
+    cursor: wgpu::BufferAddress,
+    staging: StagingPool,
+}
+
+impl RingBuffer {
+    const ALIGNMENT: wgpu::BufferAddress = 4;
+
+    pub fn new(
+        device: &wgpu::Device,
+        staging: &StagingPool,
+        label: &'static str,
+        usage: wgpu::BufferUsage,
+        capacity: wgpu::BufferAddress,
+    ) -> Self {
+        let usage = usage | wgpu::BufferUsage::COPY_DST;
+        let capacity = align(capacity.max(Self::ALIGNMENT), Self::ALIGNMENT);
+        Self {
+            label,
+            usage,
+            buffer: Self::create(device, label, usage, capacity),
+            capacity,
+            cursor: 0,
+            staging: staging.clone(),
+        }
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        label: &'static str,
+        usage: wgpu::BufferUsage,
+        size: wgpu::BufferAddress,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage,
+        })
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// Stages `data` into the buffer and returns its offset.
+    pub fn write(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        data: &[u8],
+    ) -> wgpu::BufferAddress {
+        let size = align(data.len() as wgpu::BufferAddress, Self::ALIGNMENT);
+
+        if size > self.capacity {
+            self.capacity = size.max(self.capacity * 2).next_power_of_two();
+            self.buffer = Self::create(device, self.label, self.usage, self.capacity);
+            self.cursor = 0;
+        } else if self.cursor + size > self.capacity {
+            self.cursor = 0;
+        }
+
+        let offset = self.cursor;
+        self.staging
+            .write(encoder, device, &self.buffer, offset, data);
+        self.cursor += size;
+        offset
+    }
+}
+
+/// Alignment of `bytes_per_row` in copies between buffers and textures.
+pub(crate) const COPY_ROW_ALIGNMENT: wgpu::BufferAddress = 256;
+
+pub(crate) fn align(
+    value: wgpu::BufferAddress,
+    alignment: wgpu::BufferAddress,
+) -> wgpu::BufferAddress {
+    (value + alignment - 1) / alignment * alignment
+}
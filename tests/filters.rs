@@ -0,0 +1,249 @@
+//! Filters on the GPU compared with their CPU references, skipped without an
+//! adapter unless `PIXI_REQUIRE_ADAPTER=1`.
+//!
+//! Run with `cargo test --features testing`.
+
+use pixi::{
+    blend::{AdvancedBlend, BlendMode},
+    context::Context,
+    filter::{reference::Pixels, BlurFilter, DropShadowFilter, Filter, FilterSystem, GlowFilter},
+    layout::Layout,
+    math::Frame,
+    target::RenderTarget,
+    wgpu,
+};
+use std::sync::Arc;
+
+/// References work on stored values, so a non-sRGB format is used.
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+/// 64 texels of 4 bytes fill the 256 byte row alignment of copies.
+const SIZE: u32 = 64;
+/// Intermediate passes are rounded to 8 bits on the GPU.
+const TOLERANCE: f32 = 4.0 / 255.0;
+
+fn context() -> Option<Context> {
+    let context = pixi::testing::context();
+    if context.is_none() {
+        eprintln!("no adapter, skipping filter test");
+    }
+    context
+}
+
+fn system(context: &Context) -> FilterSystem {
+    let layout = Arc::new(Layout::new(&context.device));
+    FilterSystem::new(&context.device, layout, FORMAT)
+}
+
+/// Premultiplied opaque square with a translucent stripe through it.
+fn input() -> Pixels {
+    let mut pixels = Pixels::new(SIZE, SIZE);
+    for y in 16..48 {
+        for x in 16..48 {
+            pixels.set(x, y, [0.8, 0.4, 0.2, 1.0]);
+        }
+    }
+    for y in 28..36 {
+        for x in 8..56 {
+            pixels.set(x, y, [0.0, 0.25, 0.5, 0.5]);
+        }
+    }
+    pixels
+}
+
+/// Runs `filter` over `input` and reads the result back.
+fn apply(
+    context: &Context,
+    system: &mut FilterSystem,
+    filter: &dyn Filter,
+    input: &Pixels,
+) -> Pixels {
+    let Context { device, queue, .. } = context;
+    let source = input.to_source(FORMAT).unwrap();
+
+    let label = Some("filter test");
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label });
+
+    let input = system.acquire(device, SIZE, SIZE);
+    let output = system.acquire(device, SIZE, SIZE);
+    upload(&mut encoder, device, &input, &source.texels);
+
+    filter.apply(&mut encoder, device, system, &input, &output);
+    queue.submit(&[encoder.finish()]);
+
+    let result = output.read_pixels(device, queue, false).unwrap();
+    system.release(input);
+    system.release(output);
+    Pixels::from_source(&result).unwrap()
+}
+
+fn upload(
+    encoder: &mut wgpu::CommandEncoder,
+    device: &wgpu::Device,
+    target: &RenderTarget,
+    texels: &[u8],
+) {
+    let buffer = device.create_buffer_with_data(texels, wgpu::BufferUsage::COPY_SRC);
+    encoder.copy_buffer_to_texture(
+        wgpu::BufferCopyView {
+            buffer: &buffer,
+            offset: 0,
+            bytes_per_row: target.width * 4,
+            rows_per_image: 0,
+        },
+        wgpu::TextureCopyView {
+            texture: &target.texture,
+            mip_level: 0,
+            array_layer: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::Extent3d {
+            width: target.width,
+            height: target.height,
+            depth: 1,
+        },
+    );
+}
+
+fn assert_close(name: &str, actual: &Pixels, expected: &Pixels) {
+    let difference = actual.max_difference(expected);
+    assert!(
+        difference <= TOLERANCE,
+        "{}: GPU result differs from the reference by {} ({}/255)",
+        name,
+        difference,
+        difference * 255.0,
+    );
+}
+
+#[test]
+fn blur() {
+    let context = match context() {
+        Some(context) => context,
+        None => return,
+    };
+    let mut system = system(&context);
+    let filter = BlurFilter::new(&context.device, &system, 4.0);
+
+    let input = input();
+    let actual = apply(&context, &mut system, &filter, &input);
+    assert_close("blur", &actual, &filter.reference(&input, 1.0));
+}
+
+#[test]
+fn drop_shadow() {
+    let context = match context() {
+        Some(context) => context,
+        None => return,
+    };
+    let mut system = system(&context);
+    let filter = DropShadowFilter::new(&context.device, &system);
+
+    let input = input();
+    let actual = apply(&context, &mut system, &filter, &input);
+    assert_close("drop shadow", &actual, &filter.reference(&input, 1.0));
+}
+
+#[test]
+fn glow() {
+    let context = match context() {
+        Some(context) => context,
+        None => return,
+    };
+    let mut system = system(&context);
+    let filter = GlowFilter::new(&context.device, &system, 4.0);
+
+    let input = input();
+    let actual = apply(&context, &mut system, &filter, &input);
+    assert_close("glow", &actual, &filter.reference(&input, 1.0));
+}
+
+/// Premultiplied gradient with varying hue and alpha, rounded like on upload.
+fn gradient() -> Pixels {
+    let mut pixels = Pixels::new(SIZE, SIZE);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let (u, v) = (x as f32 / SIZE as f32, y as f32 / SIZE as f32);
+            let a = 0.25 + 0.75 * v;
+            pixels.set(x, y, [u * a, (1.0 - u) * a, 0.5 * a, a]);
+        }
+    }
+    rounded(&pixels)
+}
+
+/// `pixels` rounded to the 8 bits they are uploaded with.
+fn rounded(pixels: &Pixels) -> Pixels {
+    Pixels::from_source(&pixels.to_source(FORMAT).unwrap()).unwrap()
+}
+
+#[test]
+fn advanced_blend() {
+    let context = match context() {
+        Some(context) => context,
+        None => return,
+    };
+    let mut system = system(&context);
+    let Context { device, queue, .. } = &context;
+    let layout = system.layout.clone();
+    let mut blender = AdvancedBlend::new(device, &layout, FORMAT);
+
+    let (source, backdrop) = (gradient(), rounded(&input()));
+    let frame = Frame::new(0.0, 0.0, SIZE as f32, SIZE as f32);
+    let modes = [
+        BlendMode::Multiply,
+        BlendMode::Overlay,
+        BlendMode::ColorDodge,
+        BlendMode::SoftLight,
+        BlendMode::Hue,
+        BlendMode::Color,
+        BlendMode::Add,
+    ];
+
+    for &mode in &modes {
+        let label = Some("advanced blend test");
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label });
+
+        let src = system.acquire(device, SIZE, SIZE);
+        let dst = system.acquire(device, SIZE, SIZE);
+        upload(
+            &mut encoder,
+            device,
+            &src,
+            &source.to_source(FORMAT).unwrap().texels,
+        );
+        upload(
+            &mut encoder,
+            device,
+            &dst,
+            &backdrop.to_source(FORMAT).unwrap().texels,
+        );
+
+        blender.draw(
+            &mut encoder,
+            device,
+            &layout,
+            &src.bind_group,
+            frame,
+            mode,
+            &dst,
+            1.0,
+        );
+        queue.submit(&[encoder.finish()]);
+
+        let result = dst.read_pixels(device, queue, false).unwrap();
+        system.release(src);
+        system.release(dst);
+        let actual = Pixels::from_source(&result).unwrap();
+
+        let mut expected = Pixels::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let (s, d) = (
+                    source.get(x as i32, y as i32),
+                    backdrop.get(x as i32, y as i32),
+                );
+                expected.set(x, y, AdvancedBlend::reference(mode, s, d));
+            }
+        }
+        assert_close(&format!("{:?}", mode), &actual, &expected);
+    }
+}
@@ -0,0 +1,158 @@
+//! Golden image tests, skipped without an adapter unless `PIXI_REQUIRE_ADAPTER=1`.
+//!
+//! References live in `tests/golden`, see [`pixi::testing`] for updating them.
+//! Run with `cargo test --features testing`.
+
+use pixi::{
+    batch::Batch,
+    blend::{self, Blend},
+    image::{Image, ImageSource},
+    layout::Vertex,
+    math::{Frame, Matrix, Point},
+    renderer::{Draw, Renderer},
+    sprite::untrimmed_vertices,
+    testing::Golden,
+    wgpu,
+};
+
+fn golden() -> Option<Golden> {
+    let golden = Golden::new("tests/golden");
+    if golden.is_none() {
+        eprintln!("no adapter, skipping golden image test");
+    }
+    golden
+}
+
+fn load(golden: &Golden, path: &str) -> Image {
+    let device = &golden.context.device;
+    let label = Some("load");
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label });
+    let image = Image::srgb_premul(&mut encoder, device, path).unwrap();
+    golden.context.submit(encoder);
+    image
+}
+
+/// Draws `quads` of `image` with `blend` over `backdrop` covering the target.
+fn draw(
+    golden: &Golden,
+    backdrop: Option<&Image>,
+    image: &Image,
+    blend: Blend,
+    quads: &[[Vertex; 4]],
+    size: u32,
+) -> ImageSource {
+    golden.render(size, size, |encoder, golden, target| {
+        let device = &golden.context.device;
+        let (layout, sampler, format) = (&golden.layout, &golden.sampler, golden.format);
+        let mut renderer = Renderer::new(device, layout);
+
+        let mut under = backdrop.map(|backdrop| {
+            let bind_group = layout.bind_image(device, backdrop, sampler);
+            let mut batch = Batch::new(device, layout, format, blend::PMA_NORMAL, bind_group);
+            batch.add_sprite([0.0, 0.0], [size as f32, size as f32]);
+            batch
+        });
+
+        let bind_group = layout.bind_image(device, image, sampler);
+        let mut batch = Batch::new(device, layout, format, blend, bind_group);
+        for quad in quads {
+            batch.add_quad(quad.clone());
+        }
+
+        if let Some(under) = under.as_mut() {
+            under.prepare(encoder, device);
+        }
+        batch.prepare(encoder, device);
+
+        let mut pass = renderer.begin(target.target(1.0));
+        if let Some(under) = &under {
+            pass.submit(under);
+        }
+        pass.submit(&batch);
+        pass.finish(encoder, device);
+    })
+}
+
+/// Quad in the counter-clockwise order of [`Batch::add_sprite`].
+fn quad([min_x, min_y]: [f32; 2], [max_x, max_y]: [f32; 2]) -> [Vertex; 4] {
+    [
+        Vertex::new(max_x, max_y, 1.0, 1.0), // 11
+        Vertex::new(max_x, min_y, 1.0, 0.0), // 10
+        Vertex::new(min_x, min_y, 0.0, 0.0), // 00
+        Vertex::new(min_x, max_y, 0.0, 1.0), // 01
+    ]
+}
+
+#[test]
+fn sprite() {
+    let golden = match golden() {
+        Some(golden) => golden,
+        None => return,
+    };
+
+    let bunny = load(&golden, "examples/assets/bunny.png");
+    let (w, h) = (bunny.width as f32, bunny.height as f32);
+    let quads = [quad([4.0, 4.0], [4.0 + w, 4.0 + h])];
+    let actual = draw(&golden, None, &bunny, blend::PMA_NORMAL, &quads, 64);
+    golden.check("sprite", &actual).unwrap();
+}
+
+#[test]
+fn blend() {
+    let golden = match golden() {
+        Some(golden) => golden,
+        None => return,
+    };
+
+    let dst = load(&golden, "examples/assets/blending/dst.png");
+    let src = load(&golden, "examples/assets/blending/src.png");
+    let quads = [quad([0.0, 0.0], [64.0, 64.0])];
+
+    let presets = [
+        ("pma_normal", blend::PMA_NORMAL),
+        ("pma_add", blend::PMA_ADD),
+        ("pma_mul", blend::PMA_MUL),
+        ("pma_screen", blend::PMA_SCREEN),
+        ("pma_subtract", blend::PMA_SUBTRACT),
+        ("pma_darken", blend::PMA_DARKEN),
+        ("pma_lighten", blend::PMA_LIGHTEN),
+        ("pma_src_atop", blend::PMA_SRC_ATOP),
+        ("pma_dst_out", blend::PMA_DST_OUT),
+        ("pma_xor", blend::PMA_XOR),
+    ];
+
+    let mut errors = Vec::new();
+    for (name, blend) in presets.iter().cloned() {
+        let actual = draw(&golden, Some(&dst), &src, blend, &quads, 64);
+        if let Err(err) = golden.check(&format!("blend_{}", name), &actual) {
+            errors.push(err);
+        }
+    }
+    assert!(errors.is_empty(), "{:#?}", errors);
+}
+
+#[test]
+fn trimmed() {
+    let golden = match golden() {
+        Some(golden) => golden,
+        None => return,
+    };
+
+    let bunny = load(&golden, "examples/assets/bunny.png");
+    let (w, h) = (bunny.width as f32, bunny.height as f32);
+
+    // the bunny as the trimmed part of a texture twice its size
+    let trim = Frame::new(w / 2.0, h / 2.0, w, h);
+    let anchor = Point { x: 0.5, y: 0.5 };
+    let matrix = Matrix::new(1.0, 0.0, 0.0, 1.0, 32.0, 32.0);
+    let [a, b, c, d] = untrimmed_vertices(&matrix, anchor, w * 2.0, h * 2.0, trim);
+
+    let quads = [[
+        Vertex::new(c[0], c[1], 1.0, 1.0),
+        Vertex::new(b[0], b[1], 1.0, 0.0),
+        Vertex::new(a[0], a[1], 0.0, 0.0),
+        Vertex::new(d[0], d[1], 0.0, 1.0),
+    ]];
+    let actual = draw(&golden, None, &bunny, blend::PMA_NORMAL, &quads, 64);
+    golden.check("trimmed", &actual).unwrap();
+}